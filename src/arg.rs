@@ -5,5 +5,6 @@ use clap::Parser;
 #[derive(Parser, Debug)]
 #[command(version)]
 pub struct Args {
-    pub path: PathBuf,
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
 }
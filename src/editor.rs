@@ -1,49 +1,204 @@
 mod buffer;
+mod buffers;
 mod command;
 mod mode;
+mod motion;
+mod operator;
+mod options;
 mod position;
+mod register;
+mod search;
+mod sequence;
 mod status;
+mod substitute;
+mod swap;
+mod tabs;
 mod terminal;
 mod utils;
+mod window;
 
 use super::arg::Args;
-use super::escape;
+use super::escape::{self, clipboard::Selection};
 use super::event::Key;
-use super::highlight::Highlight;
+use super::highlight::{Highlight, Overlay};
 use super::input::EventIterator;
+use super::system::{clipboard, size::TermSize, tty, watch::Watcher};
 
-use buffer::{cursor::Direction, line::Line, Buffer};
-use command::Command;
+use buffer::{
+    cursor::{Cursor, Direction},
+    encoding::Decode,
+    history::{self, Travel},
+    line::Line,
+    Buffer,
+};
+use buffers::Buffers;
+use command::{
+    history::History,
+    parse::{self, Address, ExCommand, Location},
+    registry, Command,
+};
 use mode::Mode;
+use motion::{Motion, Range};
+use operator::Operator;
+use options::Options;
 use position::Position;
-use status::{message::Message, Status};
+use register::{Register, Registers};
+use search::Search;
+use sequence::{Action, NormalCommand, Parsed, Sequence, Target};
+use status::{message::Message, Status, COMMAND_LINE_HEIGHT};
+use substitute::Substitute;
+use swap::{Crashed, Journal};
+use tabs::Tabs;
 use terminal::Terminal;
+use window::{Rect, Windows};
 
-use std::io::{self, Result};
+use unicode_segmentation::UnicodeSegmentation;
+
+use std::io;
+use std::mem;
+use std::os::fd::AsRawFd;
+use std::time::Duration;
 
 const LINE_NUMBER_COLUMN_GAP: usize = 1;
 
+// A change that can be repeated with '.', along with the keys that were typed if the change
+// started an insert session
+#[derive(Clone)]
+struct Change {
+    command: NormalCommand,
+    keys: Vec<Key>,
+    // The shape of the selection a visual operator acted on, which is repeated from the cursor
+    shape: Option<Shape>,
+}
+
+// How far a selection reaches from its start. The columns are where it ends on its last line, in
+// graphemes for visual mode and in columns of the screen for blocks
+#[derive(Clone, Copy)]
+struct Shape {
+    mode: Mode,
+    lines: usize,
+    columns: usize,
+}
+
+// What a window shows, while it is being drawn
+struct View<'a> {
+    buffer: &'a Buffer,
+    cursor: &'a Cursor,
+    rect: Rect,
+    active: bool,
+}
+
+// An insert session started with I or A on a block selection, once it ends the typed text is
+// inserted on the rest of the lines in the block as well
+struct BlockInsert {
+    top: usize,
+    bottom: usize,
+    // The column of the screen the text goes in at
+    column: usize,
+    append: bool,
+}
+
 pub struct Editor {
     terminal: Terminal,
+    // The buffer being shown, the rest of the open buffers are kept in the buffer list
     buffer: Buffer,
+    buffers: Buffers,
+    // The windows of the tab page being shown, the other tab pages keep their own
+    windows: Windows,
+    tabs: Tabs,
     mode: Mode,
     command: Command,
+    command_history: History,
+    search_history: History,
+    options: Options,
     status: Status,
+    registers: Registers,
+    search: Search,
+    // A substitution with the c flag that is waiting for an answer
+    substitute: Option<Substitute>,
+    journal: Journal,
+    // Changes left behind by a crash that are waiting for an answer on whether to recover them
+    recovery: Option<Crashed>,
+    // Changes left behind by a crash along with the number of their buffer, which are asked about
+    // once the buffer is shown in normal mode, so that whatever was being typed isn't cut off
+    recoveries: Vec<(usize, Crashed)>,
+    // Watches the files of the buffers for other programs writing to them, when inotify works
+    watcher: Option<Watcher>,
+    // Buffers with changes whose files were written by something else, which are asked about once
+    // they are shown
+    conflicts: Vec<usize>,
+    sequence: Sequence,
+    last_change: Option<Change>,
+    recording: Option<Change>,
+    // The end of the selection that stays in place while the cursor moves in visual mode
+    anchor: Position,
+    block_insert: Option<BlockInsert>,
+    // Where the cursor and the view were before the last normal mode command. The g that gt and gT
+    // start with has taken them to the top by the time the t is typed, so they go back here first
+    before_command: (Position, Position),
 }
 
 impl Editor {
-    pub fn new(args: Args) -> Result<Self> {
-        Ok(Self {
+    pub fn new(args: Args) -> io::Result<Self> {
+        let (first, rest) = args
+            .paths
+            .split_first()
+            .ok_or(io::ErrorKind::InvalidInput)?;
+
+        let new = !first.exists();
+
+        history::file::prune();
+
+        // The first file is shown, the rest are opened in the background
+        let options = Options::default();
+        let decode = Decode::Detect(&options.fileencodings);
+
+        let mut buffers = Buffers::default();
+        let buffer = Buffer::from_file(first, buffers.next_number(), decode)?;
+
+        for path in rest {
+            let number = buffers.next_number();
+            buffers.hide(Buffer::from_file(path, number, decode)?);
+        }
+
+        let mut editor = Self {
             terminal: Terminal::new()?,
-            buffer: Buffer::from_file(&args.path)?,
+            windows: Windows::new(buffer.number),
+            buffer,
+            buffers,
+            tabs: Tabs::default(),
             mode: Mode::Normal,
             command: Command::default(),
+            command_history: History::load("command-history"),
+            search_history: History::load("search-history"),
+            options,
             status: Status::default(),
-        })
+            registers: Registers::default(),
+            search: Search::default(),
+            substitute: None,
+            journal: Journal::new(),
+            recovery: None,
+            recoveries: Vec::new(),
+            watcher: Watcher::new().ok(),
+            conflicts: Vec::new(),
+            sequence: Sequence::default(),
+            last_change: None,
+            recording: None,
+            anchor: Position::default(),
+            block_insert: None,
+            before_command: (Position::default(), Position::default()),
+        };
+
+        if new {
+            let message = format!("\"{}\" [New]", first.display());
+            editor.status.message = Some(Message::new(&message));
+        }
+
+        Ok(editor)
     }
 
-    fn line_number(&self, number: usize, focus: bool) -> String {
-        let indent = utils::digits(self.buffer.data().len());
+    fn line_number(buffer: &Buffer, number: usize, focus: bool) -> String {
+        let indent = utils::digits(buffer.line_count());
         let digits = utils::digits(number);
 
         let color = if focus {
@@ -57,59 +212,201 @@ impl Editor {
             " ".repeat(indent - digits),
             color,
             escape::color::RESET,
-            " ".repeat(utils::ln_offset(&self.buffer.data()) - indent)
+            " ".repeat(utils::ln_offset(buffer.line_count()) - indent)
         )
     }
 
-    fn draw_line(&self, line: &Line, index: usize) {
-        let start = self.buffer.cursor.offset.x;
-        let end = self.terminal.size.width as usize + start - utils::ln_offset(&self.buffer.data());
+    fn draw_line(&self, view: &View, line: &Line, index: usize) {
+        // The offset is in columns, as graphemes can be wider than one column
+        let start = view.cursor.offset.x;
+        let end =
+            (view.rect.width + start).saturating_sub(utils::ln_offset(view.buffer.line_count()));
+
+        let overlays = self.overlays(view, line, index);
+
+        let cells = line.cells(start, end);
+        let render = cells.highlight(view.buffer.highlights(index), &overlays);
+
+        let ln = Self::line_number(view.buffer, index + 1, view.cursor.position.y == index);
+        print!("{ln}{render}");
+    }
+
+    // The graphemes of a line that are selected in visual mode
+    fn selection_columns(&self, y: usize, line: &Line) -> Option<(usize, usize)> {
+        let cursor = self.buffer.cursor.position;
+
+        let start = self.anchor.min(cursor);
+        let end = self.anchor.max(cursor);
+
+        if y < start.y || y > end.y {
+            return None;
+        }
+
+        match self.mode {
+            Mode::Visual => {
+                let from = if y == start.y { start.x } else { 0 };
+                let to = if y == end.y { end.x + 1 } else { line.len() };
+
+                Some((from, to))
+            }
+            Mode::VisualLine => Some((0, line.len())),
+            Mode::VisualBlock => {
+                let (_, _, left, right) = self.block();
+                Some(line.span(left, right))
+            }
+            _ => None,
+        }
+    }
+
+    fn overlays(&self, view: &View, line: &Line, y: usize) -> Vec<Overlay> {
+        let mut overlays = Vec::new();
+
+        for (start, end) in self.search.highlights(line.as_str(), self.options.hlsearch) {
+            overlays.push(Overlay {
+                start,
+                end,
+                style: escape::color::YELLOW_BACKGROUND,
+            });
+        }
+
+        // The substitution and the selection belong to the window being used
+        if !view.active {
+            return overlays;
+        }
+
+        // The match that a substitution is asking about stands out from the rest
+        if let Some(found) = self.substitute.as_ref().and_then(|s| s.current.as_ref()) {
+            if found.y == y {
+                overlays.push(Overlay {
+                    start: found.columns.0,
+                    end: found.columns.1,
+                    style: escape::color::INVERT,
+                });
+            }
+        }
+
+        if let Some((start, end)) = self.selection_columns(y, line) {
+            overlays.push(Overlay {
+                start,
+                end,
+                style: escape::color::INVERT,
+            });
+        }
+
+        overlays
+    }
+
+    // The part of the screen the windows are laid out in, which is everything between the tab line
+    // and the command line
+    fn area(&self) -> Rect {
+        let top = status::tab_line_height(self.tabs.count());
+        let reserved = top.saturating_add(COMMAND_LINE_HEIGHT);
+
+        Rect {
+            x: 0,
+            y: top as usize,
+            width: self.terminal.size.width as usize,
+            height: self.terminal.size.height.saturating_sub(reserved) as usize,
+        }
+    }
+
+    fn window_rect(&self) -> Rect {
+        self.windows.rects(self.area())[self.windows.active]
+    }
+
+    // The size of the text area of the window being used, which the screen motions and scrolling
+    // work with
+    fn window_size(&self) -> TermSize {
+        self.window_rect().text_size()
+    }
+
+    fn window_buffer(&self, number: usize) -> Option<&Buffer> {
+        if number == self.buffer.number {
+            Some(&self.buffer)
+        } else {
+            self.buffers.get(number)
+        }
+    }
+
+    fn draw_window(&self, view: &View, width: usize) {
+        let rect = &view.rect;
+        let offset = view.cursor.offset.y;
+
+        // Windows that don't reach the right edge of the screen have a separator next to them
+        let separator = rect.x + rect.width < width;
+
+        for index in 0..rect.height.saturating_sub(1) {
+            let row = rect.y + index + 1;
 
-        let highlights = &line.highlights;
-        let mut render = line.render(start, end);
+            print!("{}", escape::cursor::Goto(row, rect.x + 1));
 
-        if let Some(highlights) = highlights {
-            render = render.highlight(highlights, start);
+            if let Some(line) = view.buffer.line(offset + index) {
+                self.draw_line(view, &line, offset + index);
+            }
+
+            if separator {
+                let goto = escape::cursor::Goto(row, rect.x + rect.width + 1);
+
+                print!("{goto}{}", escape::color::BRIGHT_BLACK_FOREGROUND);
+                print!("│{}", escape::color::RESET);
+            }
         }
 
-        let ln = self.line_number(index + 1, self.buffer.cursor.position.y == index);
-        print!("{ln}{render}\r\n");
+        let mode = view.active.then_some(&self.mode);
+        Status::draw_window(mode, view.buffer, &view.cursor.position, rect);
     }
 
     fn draw(&self) {
-        print!("{}", escape::cursor::RESET);
+        let area = self.area();
 
-        let height = self.terminal.size.height as usize;
-        let offset = self.buffer.cursor.offset.y;
+        if self.tabs.count() > 1 {
+            Status::draw_tab_line(&self.tab_labels(), self.tabs.active, area.width);
+        }
 
-        let lines = self.buffer.data();
+        // The windows only draw over their own part of each row, so the rows are cleared first
+        for row in area.y + 1..=area.y + area.height {
+            let goto = escape::cursor::Goto(row, 0);
+            print!("{goto}{}", escape::clear::ENTIRE_LINE);
+        }
 
-        for index in 0..height {
-            print!("{}", escape::clear::ENTIRE_LINE);
+        let windows = self.windows.windows();
+        let rects = self.windows.rects(area);
 
-            if let Some(line) = lines.get(offset + index) {
-                self.draw_line(line, offset + index);
+        for (index, (window, rect)) in windows.into_iter().zip(rects).enumerate() {
+            let view = if index == self.windows.active {
+                View {
+                    buffer: &self.buffer,
+                    cursor: &self.buffer.cursor,
+                    rect,
+                    active: true,
+                }
             } else {
-                println!();
-            }
+                let (Some(buffer), Some(cursor)) =
+                    (self.window_buffer(window.buffer), &window.cursor)
+                else {
+                    continue;
+                };
+
+                View {
+                    buffer,
+                    cursor,
+                    rect,
+                    active: false,
+                }
+            };
+
+            self.draw_window(&view, area.width);
         }
 
-        self.status.draw(
-            &self.terminal.size,
-            &self.mode,
-            self.buffer.file_name(),
-            &self.buffer.cursor.position,
-            &self.command,
-        );
+        self.status
+            .draw(&self.terminal.size, &self.mode, &self.command);
     }
 
     fn initial_draw(&mut self) {
-        self.buffer.regenerate_highlights();
+        self.update_windows();
         self.draw();
 
-        let offset = utils::ln_offset(&self.buffer.data());
-
-        print!("{}", escape::cursor::Goto(0, offset + 1));
+        print!("{}", self.buffer.cursor.goto(&self.window_rect()));
         print!("{}", escape::cursor::BLINKING_BLOCK);
         Terminal::flush();
     }
@@ -117,45 +414,150 @@ impl Editor {
     fn redraw(&self) {
         self.draw();
 
-        if self.mode != Mode::Command {
-            print!("{}", self.buffer.cursor);
+        if !self.mode.is_prompt() {
+            print!("{}", self.buffer.cursor.goto(&self.window_rect()));
         }
 
         Terminal::flush();
     }
 
+    // Edits made through one window can leave the cursors of the other windows on the same buffer
+    // past the end of the text, and the lines every window shows need their highlights
+    fn update_windows(&mut self) {
+        let rects = self.windows.rects(self.area());
+
+        for (window, rect) in self.windows.windows_mut().into_iter().zip(rects) {
+            let size = rect.text_size();
+
+            if let Some(cursor) = &mut window.cursor {
+                cursor.overstep(&Mode::Normal);
+                cursor.scroll(&size);
+            }
+
+            let offset = window
+                .cursor
+                .as_ref()
+                .map_or(self.buffer.cursor.offset.y, |cursor| cursor.offset.y);
+
+            let lines = offset..offset + size.height as usize;
+
+            if window.buffer == self.buffer.number {
+                self.buffer.regenerate_highlights(lines);
+            } else if let Some(buffer) = self.buffers.get_mut(window.buffer) {
+                buffer.regenerate_highlights(lines);
+            }
+        }
+    }
+
     pub fn run(&mut self) {
+        self.track_buffer();
+        self.ask_about_recovery();
         self.initial_draw();
 
-        let mut keys = io::stdin().lock().keys();
+        let stdin = tty::stdin().expect("to read stdin");
+        let input = stdin.as_raw_fd();
+        let mut keys = stdin.keys();
 
         loop {
             if self.mode == Mode::Exit {
                 break;
             }
 
-            if let Some(Ok(key)) = keys.next() {
+            self.watch_files();
+
+            // Files that other programs write to are dealt with while waiting for a key
+            let files = !keys.inner.is_pending()
+                && self
+                    .watcher
+                    .as_ref()
+                    .is_some_and(|watcher| watcher.wait(input));
+
+            if files {
+                self.check_files();
+            } else if let Some(Ok(key)) = keys.next() {
                 self.handle_key(key);
             }
 
+            // Everything typed during an insert session is undone as one change, so the edits are
+            // only grouped once insert mode is left, the same goes for substituting with confirmation
+            if !matches!(self.mode, Mode::Insert | Mode::Confirm) {
+                self.buffer.commit();
+            }
+
+            let size = self.window_size();
+
             self.buffer.cursor.overstep(&self.mode);
-            self.buffer.cursor.scroll(&self.terminal.size);
-            self.buffer.regenerate_highlights();
+            self.buffer.cursor.scroll(&size);
+            self.update_windows();
+            self.track_buffer();
+            self.ask_about_recovery();
+            self.ask_about_changes();
 
             self.redraw();
         }
+
+        self.journal.finish();
     }
 
     fn handle_key_insert(&mut self, key: Key) {
+        if key != Key::Escape {
+            if let Some(recording) = &mut self.recording {
+                recording.keys.push(key);
+            }
+
+            self.type_key(key);
+            return;
+        }
+
+        if let Some(block) = self.block_insert.take() {
+            self.finish_block_insert(&block);
+        }
+
+        if let Some(change) = self.recording.take() {
+            self.registers
+                .set(register::LAST_INSERT, Self::inserted_text(&change.keys));
+
+            // A count given to i inserts the typed text that many times
+            if change.command.action == Action::Insert {
+                for _ in 1..change.command.count() {
+                    for key in &change.keys {
+                        self.type_key(*key);
+                    }
+                }
+            }
+
+            self.last_change = Some(change);
+        }
+
+        self.mode = Mode::Normal;
+        print!("{}", escape::cursor::BLINKING_BLOCK);
+        self.buffer.cursor.step(Direction::Left);
+    }
+
+    // The text left behind by typing the keys of an insert session, for the '.' register
+    fn inserted_text(keys: &[Key]) -> String {
+        let mut text = String::new();
+
+        for key in keys {
+            match key {
+                Key::Char(character) => text.push(*character),
+                Key::Enter => text.push('\n'),
+                Key::Tab => text.push('\t'),
+                Key::Backspace => {
+                    text.pop();
+                }
+                _ => {}
+            }
+        }
+
+        text
+    }
+
+    fn type_key(&mut self, key: Key) {
         match key {
             Key::ArrowLeft | Key::ArrowDown | Key::ArrowUp | Key::ArrowRight => {
                 self.buffer.cursor.step(Direction::from(key))
             }
-            Key::Escape => {
-                self.mode = Mode::Normal;
-                print!("{}", escape::cursor::BLINKING_BLOCK);
-                self.buffer.cursor.step(Direction::Left);
-            }
             Key::Char(character) => {
                 self.buffer.insert(character);
                 self.buffer.cursor.step(Direction::Right);
@@ -181,94 +583,1288 @@ impl Editor {
         }
     }
 
+    fn undo(&mut self) -> Option<Message> {
+        if self.buffer.undo() {
+            None
+        } else {
+            Some(Message::new("Already at oldest change"))
+        }
+    }
+
+    fn redo(&mut self) -> Option<Message> {
+        if self.buffer.redo() {
+            None
+        } else {
+            Some(Message::new("Already at newest change"))
+        }
+    }
+
+    // Parses the argument of :earlier and :later, which is either a count of changes or a time
+    // followed by s, m, h or d
+    fn parse_travel(argument: &str) -> Option<Travel> {
+        let argument = argument.trim();
+
+        if argument.is_empty() {
+            return Some(Travel::Steps(1));
+        }
+
+        let seconds = match argument.chars().last()? {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 60 * 60 * 24,
+            _ => return argument.parse().ok().map(Travel::Steps),
+        };
+
+        let count: u64 = argument[..argument.len() - 1].parse().ok()?;
+
+        Some(Travel::Time(Duration::from_secs(count * seconds)))
+    }
+
+    fn travel(&mut self, argument: &str, earlier: bool) -> Option<Message> {
+        let Some(travel) = Self::parse_travel(argument) else {
+            let invalid = format!("Invalid argument: {argument}");
+            return Some(Message::new_err(&invalid));
+        };
+
+        let moved = if earlier {
+            self.buffer.earlier(travel)
+        } else {
+            self.buffer.later(travel)
+        };
+
+        match (moved, earlier) {
+            (true, _) => None,
+            (false, true) => Some(Message::new("Already at oldest change")),
+            (false, false) => Some(Message::new("Already at newest change")),
+        }
+    }
+
     fn handle_command(&mut self) -> Option<Message> {
-        match self.command.keys() {
-            [Key::Char('q')] => {
-                self.mode = Mode::Exit;
-                None
-            }
-            [Key::Char('w')] => match self.buffer.save() {
-                Ok(bytes) => {
-                    let message = format!("Wrote {bytes} bytes to file");
-                    Some(Message::new(&message))
-                }
-                Err(error) => Some(Message::new_err(&error.to_string())),
-            },
-            keys => {
-                let command: String = keys.iter().map(|key| key.to_string()).collect();
-                let not_found = format!("Not a command: {}", command);
+        let line = self.command.to_string();
+
+        let mut command = match parse::parse(&line) {
+            Ok(command) => command,
+            Err(error) => return Some(Message::new_err(&error)),
+        };
 
-                Some(Message::new_err(&not_found))
+        // Anything after a range that isn't a command name, like :!ls, isn't something alice runs
+        if command.name.is_empty() {
+            if command.bang || !command.argument.is_empty() {
+                let error = format!("Not an editor command: {}", line.trim());
+                return Some(Message::new_err(&error));
             }
+
+            return self.goto_range(&command);
+        }
+
+        match registry::prepare(&mut command) {
+            Ok(definition) => (definition.run)(self, &command),
+            Err(error) => Some(Message::new_err(&error)),
         }
     }
 
-    fn handle_key_command(&mut self, key: Key) {
-        match key {
-            Key::Escape => {
-                self.mode = Mode::Normal;
-                print!("{}", escape::cursor::BLINKING_BLOCK);
-                self.command.clear();
+    // Resolves a location in a range to a line index, which may be past the end of the file
+    fn resolve(&self, location: &Location, current: usize) -> Result<usize, String> {
+        let last = self.buffer.line_count().saturating_sub(1);
+
+        let line = match &location.address {
+            // Line zero is accepted as the first line, like in vim
+            Address::Number(number) => number.saturating_sub(1),
+            Address::Current => current,
+            Address::Last => last,
+            Address::Mark(mark) => self
+                .buffer
+                .marks
+                .get(mark)
+                .map(|position| position.y)
+                .ok_or_else(|| format!("Mark not set: {mark}"))?,
+            Address::Pattern { pattern, forward } => {
+                let regex = if pattern.is_empty() {
+                    self.search
+                        .regex()
+                        .cloned()
+                        .ok_or_else(|| String::from("No previous regular expression"))?
+                } else {
+                    search::compile(pattern, self.options.smartcase)
+                        .map_err(|_| format!("Invalid pattern: {pattern}"))?
+                };
+
+                search::find_line(&regex, &self.buffer.text(), current, *forward)
+                    .ok_or_else(|| format!("Pattern not found: {pattern}"))?
             }
-            Key::Backspace => self.command.delete(),
-            Key::Char(_) => self.command.insert(key),
-            Key::Enter => {
-                self.status.message = self.handle_command();
+        };
 
-                if self.mode != Mode::Exit {
-                    self.command.clear();
-                    self.mode = Mode::Normal;
-                    print!("{}", escape::cursor::BLINKING_BLOCK);
-                }
+        line.checked_add_signed(location.offset)
+            .ok_or_else(|| String::from("Invalid range"))
+    }
+
+    // Resolves the locations of a range in turn. They are looked up from the cursor, until one
+    // comes after a semicolon, from then on they are looked up from the line before it
+    fn resolve_range(&self, range: &[Location]) -> Result<Vec<usize>, String> {
+        let last = self.buffer.line_count().saturating_sub(1);
+
+        let mut current = self.buffer.cursor.position.y;
+        let mut lines: Vec<usize> = Vec::new();
+
+        for location in range {
+            if let (true, Some(line)) = (location.relative, lines.last()) {
+                current = (*line).min(last);
             }
-            _ => {}
+
+            lines.push(self.resolve(location, current)?);
         }
+
+        Ok(lines)
     }
 
-    fn handle_key_normal(&mut self, key: Key) {
-        match key {
-            Key::Char(' ') => {
-                self.mode = Mode::Command;
-                self.status.message = None;
-                print!("{}", escape::cursor::BLINKING_BAR);
-            }
-            Key::Char('h' | 'j' | 'k' | 'l')
-            | Key::ArrowLeft
-            | Key::ArrowDown
-            | Key::ArrowUp
-            | Key::ArrowRight => {
-                self.buffer.cursor.step(Direction::from(key));
-            }
-            Key::Char('g') => {
-                self.buffer.cursor.top();
-            }
-            Key::Char('G') => {
-                self.buffer.cursor.bottom();
-            }
-            Key::Char('f') => {
-                self.buffer.cursor.start();
-            }
-            Key::Char('F') => {
-                self.buffer.cursor.end();
+    // The lines a command acts on, which is the current line when no range was given. A count
+    // makes the command act on that many lines, starting at the end of the range
+    fn command_lines(&self, command: &ExCommand) -> Result<(usize, usize), String> {
+        let current = self.buffer.cursor.position.y;
+        let last = self.buffer.line_count().saturating_sub(1);
+
+        // Only the last two locations count when more are given
+        let (start, end) = match self.resolve_range(&command.range)?[..] {
+            [] => (current, current),
+            [line] => (line, line),
+            [.., start, end] => (start, end),
+        };
+
+        let (start, end) = (start.min(end), start.max(end));
+
+        let (start, end) = match command.count {
+            Some(count) => (end, (end + count.max(1) - 1).min(last)),
+            None => (start, end),
+        };
+
+        if end > last {
+            return Err(String::from("Invalid range"));
+        }
+
+        Ok((start, end))
+    }
+
+    // A range on its own moves the cursor to the last line in it
+    fn goto_range(&mut self, command: &ExCommand) -> Option<Message> {
+        match self.resolve_range(&command.range) {
+            Ok(lines) => {
+                let y = *lines.last()?;
+                let y = y.min(self.buffer.line_count().saturating_sub(1));
+                let x = self.buffer.cursor.first_non_blank(y);
+
+                self.buffer.cursor.position = Position { x, y };
+                None
             }
-            Key::Char('C') => {
-                self.buffer.cursor.center(&self.terminal.size);
+            Err(error) => Some(Message::new_err(&error)),
+        }
+    }
+
+    // Commands like :d and :y take the register to use as their argument
+    fn register_argument(argument: &str) -> Result<Option<char>, String> {
+        let mut chars = argument.chars();
+
+        match (chars.next(), chars.next()) {
+            (None, _) => Ok(None),
+            (Some(register), None) if Registers::writable(register) => Ok(Some(register)),
+            _ => Err(String::from("Invalid register")),
+        }
+    }
+
+    // Runs an operator over the lines of a command, like :d or :>
+    fn operate_lines(&mut self, command: &ExCommand, operator: Operator) -> Option<Message> {
+        let lines = self.command_lines(command).and_then(|lines| {
+            let register = Self::register_argument(&command.argument)?;
+            Ok((lines, register))
+        });
+
+        match lines {
+            Ok(((start, end), register)) => {
+                self.operate(operator, Range::lines(start, end), register);
+                None
             }
-            Key::Char('i') => {
-                self.mode = Mode::Insert;
-                print!("{}", escape::cursor::BLINKING_BAR);
-                self.buffer.cursor.step(Direction::Right);
+            Err(error) => Some(Message::new_err(&error)),
+        }
+    }
+
+    fn delete_lines(&mut self, command: &ExCommand) -> Option<Message> {
+        self.operate_lines(command, Operator::Delete)
+    }
+
+    fn yank_lines(&mut self, command: &ExCommand) -> Option<Message> {
+        self.operate_lines(command, Operator::Yank)
+    }
+
+    fn shift_lines(&mut self, command: &ExCommand, indent: bool) -> Option<Message> {
+        let operator = if indent {
+            Operator::Indent
+        } else {
+            Operator::Dedent
+        };
+
+        self.operate_lines(command, operator)
+    }
+
+    fn mark(&mut self, command: &ExCommand) -> Option<Message> {
+        let mut chars = command.argument.chars();
+
+        let mark = match (chars.next(), chars.next()) {
+            (Some(mark @ 'a'..='z'), None) => mark,
+            (None, _) => return Some(Message::new_err("Argument required")),
+            _ => return Some(Message::new_err("Invalid mark")),
+        };
+
+        match self.command_lines(command) {
+            Ok((_, y)) => {
+                self.buffer.marks.insert(mark, Position { x: 0, y });
+                None
             }
-            _ => {}
+            Err(error) => Some(Message::new_err(&error)),
+        }
+    }
+
+    fn set(&mut self, command: &ExCommand) -> Option<Message> {
+        match self.options.set(&mut self.buffer.format, &command.argument) {
+            Ok(shown) => shown.map(|shown| Message::new(&shown)),
+            Err(error) => Some(Message::new_err(&error)),
+        }
+    }
+
+    // Whether closing the window being used would leave nothing to show
+    fn is_last_window(&self) -> bool {
+        self.windows.count() == 1 && self.tabs.count() == 1
+    }
+
+    fn quit(&mut self, force: bool) -> Option<Message> {
+        // Quitting one of several windows only closes that window, the buffer stays open
+        if !self.is_last_window() {
+            return self.close_window();
+        }
+
+        self.quit_all(force)
+    }
+
+    fn quit_all(&mut self, force: bool) -> Option<Message> {
+        if !force && self.buffer.is_modified() {
+            return Some(Message::new_err(
+                "No write since last change (add ! to override)",
+            ));
+        }
+
+        self.quit_hidden(force)
+    }
+
+    // Quits unless one of the buffers that aren't shown has changes that haven't been written
+    fn quit_hidden(&mut self, force: bool) -> Option<Message> {
+        if let Some(buffer) = self.buffers.modified().filter(|_| !force) {
+            let error = format!(
+                "No write since last change for buffer {} (add ! to override)",
+                buffer.number
+            );
+
+            return Some(Message::new_err(&error));
+        }
+
+        self.mode = Mode::Exit;
+        None
+    }
+
+    fn write_quit(&mut self, command: &ExCommand) -> Option<Message> {
+        let message = self.write(command);
+
+        if message.as_ref().is_some_and(Message::is_error) {
+            return message;
+        }
+
+        if !self.is_last_window() {
+            return self.close_window();
+        }
+
+        self.quit_hidden(command.bang)
+    }
+
+    // Like :wq, but the file is only written when there are changes to write
+    fn exit(&mut self, command: &ExCommand) -> Option<Message> {
+        if self.buffer.is_modified() {
+            self.write_quit(command)
+        } else if !self.is_last_window() {
+            self.close_window()
+        } else {
+            self.quit_hidden(command.bang)
+        }
+    }
+
+    // Brings a buffer out of the buffer list into the window being used, putting the one it
+    // replaces in the list
+    fn show_buffer(&mut self, number: usize) -> bool {
+        let Some(buffer) = self.buffers.take(number) else {
+            return false;
+        };
+
+        self.buffer.commit();
+
+        let previous = std::mem::replace(&mut self.buffer, buffer);
+        self.buffers.hide(previous);
+
+        true
+    }
+
+    fn switch_buffer(&mut self, number: usize) {
+        let previous = self.buffer.number;
+
+        if self.show_buffer(number) {
+            self.buffers.alternate = Some(previous);
+        }
+    }
+
+    // Keeps the buffer and cursor of the window being left in the window
+    fn leave_window(&mut self) {
+        let number = self.buffer.number;
+        let cursor = self.buffer.cursor.clone();
+
+        if let Some(window) = self.windows.get_mut(self.windows.active) {
+            window.buffer = number;
+            window.cursor = Some(cursor);
+        }
+    }
+
+    // Makes a window the one being used, bringing back its buffer and cursor
+    fn enter_window(&mut self, index: usize) {
+        self.windows.active = index;
+
+        let Some(window) = self.windows.get_mut(index) else {
+            return;
+        };
+
+        let number = window.buffer;
+        let cursor = window.cursor.take();
+
+        if number != self.buffer.number {
+            self.show_buffer(number);
+        }
+
+        if let Some(cursor) = cursor {
+            self.buffer.cursor = cursor;
+        }
+    }
+
+    // Each tab page is labelled with the buffer of the window being used in it, along with how many
+    // windows it has and whether any of them show changes that haven't been written
+    fn tab_labels(&self) -> Vec<String> {
+        let current = self.tabs.active;
+
+        let labels = self.tabs.all(&self.windows).into_iter().enumerate();
+
+        labels
+            .map(|(index, windows)| {
+                let buffers: Vec<Option<&Buffer>> = windows
+                    .windows()
+                    .iter()
+                    .enumerate()
+                    .map(|(window_index, window)| {
+                        if index == current && window_index == windows.active {
+                            Some(&self.buffer)
+                        } else {
+                            self.window_buffer(window.buffer)
+                        }
+                    })
+                    .collect();
+
+                let mut label = String::new();
+
+                if buffers.len() > 1 {
+                    label.push_str(&buffers.len().to_string());
+                }
+
+                if buffers.iter().flatten().any(|buffer| buffer.is_modified()) {
+                    label.push('+');
+                }
+
+                if !label.is_empty() {
+                    label.push(' ');
+                }
+
+                if let Some(buffer) = buffers.get(windows.active).copied().flatten() {
+                    label.push_str(&buffer.file_name());
+                }
+
+                label
+            })
+            .collect()
+    }
+
+    // Lists the registers that hold something, or only the ones given as the argument
+    fn list_registers(&self, argument: &str) -> Option<Message> {
+        let mut listing = String::from("Type Name Content");
+
+        for (name, register) in self.registers.list() {
+            if !argument.is_empty() && !argument.contains(name) {
+                continue;
+            }
+
+            let kind = if register.linewise { 'l' } else { 'c' };
+            let content = register.text.replace('\n', "^J").replace('\t', "^I");
+
+            listing.push_str(&format!("\n  {kind}  \"{name}   {content}"));
+        }
+
+        Some(Message::new(&listing))
+    }
+
+    fn search_prompt(&mut self, forward: bool) -> Option<Message> {
+        let pattern = self.command.to_string();
+
+        // An empty pattern searches for the last one again, in the new direction
+        if pattern.is_empty() {
+            self.search.forward = forward;
+        } else {
+            if self
+                .search
+                .set(&pattern, forward, self.options.smartcase)
+                .is_err()
+            {
+                let invalid = format!("Invalid pattern: {pattern}");
+                return Some(Message::new_err(&invalid));
+            }
+
+            self.registers.set(register::LAST_SEARCH, pattern);
+        }
+
+        self.search_next(forward, 1)
+    }
+
+    fn search_next(&mut self, forward: bool, count: usize) -> Option<Message> {
+        if !self.search.is_set() {
+            return Some(Message::new_err("No previous regular expression"));
+        }
+
+        let mut wrapped = false;
+
+        for _ in 0..count {
+            let found = self
+                .search
+                .find(&self.buffer.text(), self.buffer.cursor.position, forward);
+
+            let Some(found) = found else {
+                let not_found = format!("Pattern not found: {}", self.search.pattern);
+                return Some(Message::new_err(&not_found));
+            };
+
+            self.buffer.cursor.position = found.position;
+            wrapped |= found.wrapped;
+        }
+
+        self.search.highlight = true;
+
+        match (wrapped, forward) {
+            (true, true) => Some(Message::new_err("search hit BOTTOM, continuing at TOP")),
+            (true, false) => Some(Message::new_err("search hit TOP, continuing at BOTTOM")),
+            (false, true) => Some(Message::new(&format!("/{}", self.search.pattern))),
+            (false, false) => Some(Message::new(&format!("?{}", self.search.pattern))),
+        }
+    }
+
+    fn search_word(&mut self, forward: bool, count: usize) -> Option<Message> {
+        let Position { x, y } = self.buffer.cursor.position;
+
+        let word = self
+            .buffer
+            .line(y)
+            .and_then(|line| search::word_under(&line.to_string(), x));
+
+        let Some((start, word)) = word else {
+            return Some(Message::new_err("No string under cursor"));
+        };
+
+        // Searching from the start of the word skips over it in both directions, and like in vim the
+        // case of the word always has to match
+        let pattern = format!(r"\b{}\b", regex::escape(&word));
+        _ = self.search.set(&pattern, forward, false);
+
+        self.registers.set(register::LAST_SEARCH, pattern);
+        self.buffer.cursor.position.x = start;
+
+        self.search_next(forward, count)
+    }
+
+    // Moves the cursor to the first match for what has been typed in the search prompt so far
+    fn preview_search(&mut self) {
+        let Mode::Search { forward } = self.mode else {
+            return;
+        };
+
+        let Some(preview) = &self.search.preview else {
+            return;
+        };
+
+        let (position, offset) = (preview.position, preview.offset);
+
+        let pattern = self.command.to_string();
+        let height = self.window_size().height as usize;
+        let smart_case = self.options.smartcase;
+
+        let found =
+            self.search
+                .update_preview(&pattern, &self.buffer.text(), forward, height, smart_case);
+
+        self.buffer.cursor.position = found.unwrap_or(position);
+        self.buffer.cursor.offset = offset;
+    }
+
+    // Puts the cursor and the view back to where they were before the search prompt was opened
+    fn end_preview(&mut self) {
+        if let Some(preview) = self.search.preview.take() {
+            self.buffer.cursor.position = preview.position;
+            self.buffer.cursor.offset = preview.offset;
+        }
+    }
+
+    // The command line and the search prompt each keep their own history
+    fn prompt_history(&mut self) -> &mut History {
+        match self.mode {
+            Mode::Search { .. } => &mut self.search_history,
+            _ => &mut self.command_history,
+        }
+    }
+
+    fn leave_prompt(&mut self) {
+        self.end_preview();
+        self.prompt_history().reset();
+
+        self.mode = Mode::Normal;
+        print!("{}", escape::cursor::BLINKING_BLOCK);
+        self.command.clear();
+    }
+
+    fn handle_key_command(&mut self, key: Key) {
+        // Any key other than Tab accepts the selected candidate
+        if !matches!(key, Key::Tab | Key::BackTab) {
+            self.command.wildmenu = None;
+        }
+
+        match key {
+            Key::Escape => self.leave_prompt(),
+            // Deleting past the start of the line leaves the prompt, like in vim
+            Key::Backspace | Key::Ctrl('h') if self.command.is_empty() => self.leave_prompt(),
+            Key::Backspace | Key::Ctrl('h') => self.command.delete(),
+            Key::Delete => self.command.delete_forward(),
+            Key::Ctrl('w') => self.command.delete_word(),
+            Key::Ctrl('u') => self.command.delete_to_start(),
+            Key::Char(_) => self.command.insert(key),
+            Key::ArrowLeft => self.command.left(),
+            Key::ArrowRight => self.command.right(),
+            Key::Home | Key::Ctrl('b') => self.command.start(),
+            Key::End | Key::Ctrl('e') => self.command.end(),
+            Key::Tab | Key::BackTab if self.mode == Mode::Command => {
+                let buffers = self.buffer_names();
+                self.command.complete(key == Key::BackTab, &buffers);
+            }
+            Key::ArrowUp | Key::ArrowDown => {
+                let typed = self.command.to_string();
+                let history = self.prompt_history();
+
+                let entry = if key == Key::ArrowUp {
+                    history.older(&typed)
+                } else {
+                    history.newer()
+                };
+
+                if let Some(entry) = entry.map(String::from) {
+                    self.command.set(&entry);
+                    self.preview_search();
+                }
+            }
+            Key::Enter => {
+                let line = self.command.to_string();
+                let saved = self.prompt_history().add(line.clone());
+
+                // The search itself starts from where the cursor was before the preview moved it
+                self.end_preview();
+
+                self.status.message = match self.mode {
+                    Mode::Search { forward } => self.search_prompt(forward),
+                    _ => {
+                        self.registers.set(register::LAST_COMMAND, line);
+                        self.handle_command()
+                    }
+                };
+
+                // What the command itself has to say comes first
+                if let (Err(error), None) = (saved, &self.status.message) {
+                    let error = format!("Can't save the history: {error}");
+                    self.status.message = Some(Message::new_err(&error));
+                }
+
+                self.command.clear();
+                print!("{}", escape::cursor::BLINKING_BLOCK);
+
+                // Commands can leave the prompt for another mode, like quitting or confirming
+                if self.mode.is_prompt() {
+                    self.mode = Mode::Normal;
+                }
+            }
+            _ => {}
+        }
+
+        // Editing the line starts the history over from what was typed
+        if matches!(
+            key,
+            Key::Backspace | Key::Ctrl('h' | 'w' | 'u') | Key::Delete | Key::Char(_)
+        ) {
+            self.prompt_history().reset();
+            self.preview_search();
+        }
+    }
+
+    fn handle_key_normal(&mut self, key: Key) {
+        match self.sequence.push(key) {
+            Parsed::Pending => {
+                self.mode = self
+                    .sequence
+                    .operator()
+                    .map_or(Mode::Normal, Mode::OperatorPending);
+            }
+            Parsed::Invalid => self.mode = Mode::Normal,
+            Parsed::Complete(command) => {
+                self.mode = Mode::Normal;
+                self.execute(command);
+            }
+        }
+    }
+
+    fn handle_key_visual(&mut self, key: Key) {
+        match self.sequence.push_visual(key) {
+            Parsed::Pending => {}
+            Parsed::Invalid => {
+                if key == Key::Escape {
+                    self.mode = Mode::Normal;
+                }
+            }
+            Parsed::Complete(command) => self.execute(command),
+        }
+    }
+
+    fn execute(&mut self, command: NormalCommand) {
+        let count = command.count();
+
+        let shape = match command.action {
+            Action::Operator(_, Target::Selection) => self.shape(),
+            _ => None,
+        };
+
+        // Only putting can read from the registers that aren't written to by yanking and deleting
+        if let Some(register) = command.register {
+            let valid = match command.action {
+                Action::Put { .. } => Registers::readable(register),
+                _ => Registers::writable(register),
+            };
+
+            if !valid {
+                self.status.message = Some(Message::new_err("Invalid register"));
+                return;
+            }
+        }
+
+        let cursor = &self.buffer.cursor;
+        let before = mem::replace(&mut self.before_command, (cursor.position, cursor.offset));
+
+        match command.action {
+            Action::Motion(motion) => {
+                let size = self.window_size();
+                motion.apply(&mut self.buffer.cursor, count, &size);
+            }
+            Action::Operator(operator, Target::Selection) => {
+                self.operate_selection(operator, command.register);
+            }
+            Action::Operator(operator, target) => {
+                let range = match target {
+                    Target::Line => {
+                        let y = self.buffer.cursor.position.y;
+                        Range::lines(y, y + count - 1)
+                    }
+                    Target::Motion(motion) => {
+                        // Like in vim, cw changes to the end of the word instead of up to the next one
+                        let motion = match (operator, motion) {
+                            (Operator::Change, Motion::WordForward { big }) => {
+                                Motion::WordEnd { big }
+                            }
+                            _ => motion,
+                        };
+
+                        let size = self.window_size();
+                        motion.range(&mut self.buffer.cursor, count, &size)
+                    }
+                    Target::Selection => unreachable!(),
+                };
+
+                self.operate(operator, range, command.register);
+            }
+            Action::Command => {
+                self.mode = Mode::Command;
+                self.status.message = None;
+                print!("{}", escape::cursor::BLINKING_BAR);
+            }
+            Action::Center => {
+                let size = self.window_size();
+                self.buffer.cursor.center(&size);
+            }
+            Action::Insert => {
+                self.mode = Mode::Insert;
+                print!("{}", escape::cursor::BLINKING_BAR);
+                self.buffer.cursor.step(Direction::Right);
+            }
+            Action::Put { before } => {
+                self.put(before, count, command.register);
+            }
+            Action::Undo => {
+                for _ in 0..count {
+                    self.status.message = self.undo();
+                }
+            }
+            Action::Redo => {
+                for _ in 0..count {
+                    self.status.message = self.redo();
+                }
+            }
+            Action::Repeat => {
+                self.repeat(command.count);
+                return;
+            }
+            Action::Visual(mode) => {
+                if self.mode == mode {
+                    self.mode = Mode::Normal;
+                } else {
+                    if !self.mode.is_visual() {
+                        self.anchor = self.buffer.cursor.position;
+                    }
+
+                    self.mode = mode;
+                }
+            }
+            Action::SwapAnchor => {
+                std::mem::swap(&mut self.anchor, &mut self.buffer.cursor.position);
+            }
+            Action::BlockInsert { append } => {
+                if self.mode == Mode::VisualBlock {
+                    self.begin_block_insert(append);
+                }
+            }
+            Action::Search { forward } => {
+                self.mode = Mode::Search { forward };
+                self.status.message = None;
+
+                let cursor = &self.buffer.cursor;
+                self.search.begin_preview(cursor.position, cursor.offset);
+
+                print!("{}", escape::cursor::BLINKING_BAR);
+            }
+            Action::SearchNext { reverse } => {
+                let forward = self.search.forward != reverse;
+                self.status.message = self.search_next(forward, count);
+            }
+            Action::SearchWord { forward } => {
+                self.status.message = self.search_word(forward, count);
+            }
+            Action::Mark(mark) => {
+                self.buffer.marks.insert(mark, self.buffer.cursor.position);
+            }
+            Action::Window(key) => {
+                self.status.message = self.window_command(key, command.count);
+            }
+            Action::Tab { forward } => {
+                (self.buffer.cursor.position, self.buffer.cursor.offset) = before;
+
+                if forward {
+                    self.next_tab(command.count);
+                } else {
+                    self.previous_tab(command.count());
+                }
+            }
+            Action::Alternate => {
+                self.status.message = self.alternate(command.count);
+            }
+            Action::Exit { write } => {
+                self.status.message = if write {
+                    self.exit(&ExCommand::default())
+                } else {
+                    self.quit(true)
+                };
+            }
+        }
+
+        if command.is_change() {
+            let change = Change {
+                command,
+                keys: Vec::new(),
+                shape,
+            };
+
+            // Changes that start an insert session are finished once it ends, so that the typed
+            // text is repeated as well
+            if self.mode == Mode::Insert {
+                self.recording = Some(change);
+            } else {
+                self.last_change = Some(change);
+            }
+        }
+    }
+
+    // The shape of the current selection, to repeat a visual operator with
+    fn shape(&self) -> Option<Shape> {
+        let cursor = self.buffer.cursor.position;
+
+        let start = self.anchor.min(cursor);
+        let end = self.anchor.max(cursor);
+
+        let columns = match self.mode {
+            Mode::Visual if start.y == end.y => end.x - start.x,
+            Mode::Visual => end.x,
+            Mode::VisualLine => 0,
+            Mode::VisualBlock => {
+                let (_, _, left, right) = self.block();
+                right - left
+            }
+            _ => return None,
+        };
+
+        Some(Shape {
+            mode: self.mode,
+            lines: end.y - start.y,
+            columns,
+        })
+    }
+
+    // Selects the same amount of text as before, starting at the cursor like vim does
+    fn select(&mut self, shape: Shape) {
+        let start = self.buffer.cursor.position;
+        let y = (start.y + shape.lines).min(self.buffer.line_count().saturating_sub(1));
+
+        let x = match shape.mode {
+            Mode::Visual if shape.lines == 0 => start.x + shape.columns,
+            Mode::Visual => shape.columns,
+            Mode::VisualBlock => {
+                let column = self
+                    .buffer
+                    .line(start.y)
+                    .map_or(start.x, |line| line.column(start.x));
+
+                self.column_position(y, column + shape.columns.saturating_sub(1))
+                    .x
+            }
+            _ => start.x,
+        };
+
+        self.anchor = start;
+        self.buffer.cursor.position = Position {
+            x: x.min(self.buffer.line_len(y).saturating_sub(1)),
+            y,
+        };
+        self.mode = shape.mode;
+    }
+
+    // Replays the last change, a count replaces the one it was originally given
+    fn repeat(&mut self, count: Option<usize>) {
+        let Some(change) = self.last_change.clone() else {
+            return;
+        };
+
+        let mut command = change.command;
+
+        if count.is_some() {
+            command.count = count;
+        }
+
+        if let Some(shape) = change.shape {
+            self.select(shape);
+        }
+
+        self.execute(command);
+
+        if self.mode == Mode::Insert {
+            for key in change.keys {
+                self.handle_key_insert(key);
+            }
+
+            self.handle_key_insert(Key::Escape);
+        }
+    }
+
+    fn operate(&mut self, operator: Operator, range: Range, register: Option<char>) {
+        let text = self.buffer.range_text(&range);
+
+        match operator {
+            Operator::Yank => {
+                if !range.linewise {
+                    self.buffer.cursor.position = range.start;
+                }
+            }
+            Operator::Delete => {
+                self.buffer.cursor.position = range.start;
+                self.buffer.delete_range(&range);
+            }
+            Operator::Change => {
+                // Changing lines keeps an empty line around to insert into
+                let range = self.charwise(range);
+
+                self.buffer.cursor.position = range.start;
+                self.buffer.delete_range(&range);
+
+                self.mode = Mode::Insert;
+                print!("{}", escape::cursor::BLINKING_BAR);
+            }
+            Operator::Indent => {
+                self.buffer
+                    .indent(range.start.y, range.end.y, self.options.shiftwidth);
+            }
+            Operator::Dedent => {
+                self.buffer
+                    .dedent(range.start.y, range.end.y, self.options.shiftwidth);
+            }
+            Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+                let range = self.charwise(range);
+                self.convert_case(operator, &range);
+            }
+        }
+
+        if operator != Operator::Yank && range.linewise {
+            self.buffer.cursor.position.x = 0;
+        }
+
+        self.store(operator, register, Register::new(text, range.linewise));
+    }
+
+    fn store(&mut self, operator: Operator, register: Option<char>, value: Register) {
+        if let Some(selection) = register.and_then(Registers::selection) {
+            Self::copy(selection, &value.text);
+        }
+
+        match operator {
+            Operator::Yank => self.registers.yank(register, value),
+            Operator::Delete | Operator::Change => self.registers.delete(register, value),
+            _ => {}
+        }
+    }
+
+    // The terminal is asked to set the clipboard as well, since a helper running on a machine that is
+    // reached over SSH would only set the clipboard on that machine
+    fn copy(selection: Selection, text: &str) {
+        print!("{}", escape::clipboard::Copy(selection, text));
+        _ = clipboard::copy(selection, text);
+    }
+
+    // Reads the clipboard through a helper, falling back to what was last copied from alice
+    fn paste(&self, name: char, selection: Selection) -> Option<Register> {
+        let stored = self.registers.get(name);
+
+        let Ok(text) = clipboard::paste(selection) else {
+            return stored.cloned();
+        };
+
+        match stored {
+            Some(register) if register.text == text => Some(register.clone()),
+            _ => {
+                let linewise = text.ends_with('\n');
+                Some(Register::new(text, linewise))
+            }
+        }
+    }
+
+    // Turns a linewise range into a charwise one covering the same lines, without the line break
+    // at the end of the last line
+    fn charwise(&self, range: Range) -> Range {
+        if !range.linewise {
+            return range;
+        }
+
+        let end = range.end.y.min(self.buffer.line_count().saturating_sub(1));
+        let length = self.buffer.line_len(end);
+
+        Range {
+            start: Position {
+                x: 0,
+                y: range.start.y,
+            },
+            end: Position { x: length, y: end },
+            linewise: false,
+        }
+    }
+
+    fn convert_case(&mut self, operator: Operator, range: &Range) {
+        let text = self.buffer.range_text(range);
+
+        if let Some(converted) = operator.convert_case(&text) {
+            self.buffer.cursor.position = range.start;
+
+            if converted != text {
+                self.buffer.delete_range(range);
+
+                let index = self.buffer.char_index(range.start);
+                self.buffer.insert_str(index, &converted);
+            }
+        }
+    }
+
+    // The top and bottom line of a block selection, and the columns on the screen it starts and
+    // ends at. Lines are cut by their columns rather than their graphemes, so that the block stays
+    // straight when some of the graphemes are wider than others
+    fn block(&self) -> (usize, usize, usize, usize) {
+        let cursor = self.buffer.cursor.position;
+
+        let columns = |position: Position| {
+            self.buffer
+                .line(position.y)
+                .map_or((position.x, position.x + 1), |line| {
+                    let column = line.column(position.x);
+                    (column, column + line.width(position.x))
+                })
+        };
+
+        let (anchor_start, anchor_end) = columns(self.anchor);
+        let (cursor_start, cursor_end) = columns(cursor);
+
+        (
+            self.anchor.y.min(cursor.y),
+            self.anchor.y.max(cursor.y),
+            anchor_start.min(cursor_start),
+            anchor_end.max(cursor_end),
+        )
+    }
+
+    // The position of a column of the screen in a line, for the block selections
+    fn column_position(&self, y: usize, column: usize) -> Position {
+        let x = self
+            .buffer
+            .line(y)
+            .map_or(column, |line| line.index(column));
+
+        Position { x, y }
+    }
+
+    fn operate_selection(&mut self, operator: Operator, register: Option<char>) {
+        let mode = self.mode;
+        self.mode = Mode::Normal;
+
+        let cursor = self.buffer.cursor.position;
+
+        let start = self.anchor.min(cursor);
+        let mut end = self.anchor.max(cursor);
+
+        match mode {
+            Mode::Visual => {
+                end.x += 1;
+
+                let range = Range {
+                    start,
+                    end,
+                    linewise: false,
+                };
+
+                self.operate(operator, range, register);
+            }
+            Mode::VisualLine => self.operate(operator, Range::lines(start.y, end.y), register),
+            Mode::VisualBlock => self.operate_block(operator, register),
+            _ => {}
+        }
+    }
+
+    // Block selections act on the same columns of every line, lines that are too short to reach
+    // into the block are left alone
+    fn operate_block(&mut self, operator: Operator, register: Option<char>) {
+        let (top, bottom, left, right) = self.block();
+
+        let segments: Vec<Range> = (top..=bottom)
+            .map(|y| {
+                let (start, end) = self
+                    .buffer
+                    .line(y)
+                    .map_or((0, 0), |line| line.span(left, right));
+
+                Range {
+                    start: Position { x: start, y },
+                    end: Position { x: end, y },
+                    linewise: false,
+                }
+            })
+            .collect();
+
+        let corner = self.column_position(top, left);
+
+        match operator {
+            Operator::Yank | Operator::Delete | Operator::Change => {
+                let text = segments
+                    .iter()
+                    .map(|segment| self.buffer.range_text(segment))
+                    .collect::<Vec<String>>()
+                    .join("\n");
+
+                self.store(operator, register, Register::new(text, false));
+
+                self.buffer.cursor.position = corner;
+
+                if operator != Operator::Yank {
+                    // Going from the bottom up keeps the positions of the remaining segments intact
+                    for segment in segments.iter().rev() {
+                        self.buffer.delete_range(segment);
+                    }
+                }
+
+                if operator == Operator::Change {
+                    self.anchor = self.column_position(bottom, left);
+                    self.buffer.cursor.position = corner;
+                    self.mode = Mode::VisualBlock;
+                    self.begin_block_insert(false);
+                }
+            }
+            Operator::Indent => self.buffer.indent(top, bottom, self.options.shiftwidth),
+            Operator::Dedent => self.buffer.dedent(top, bottom, self.options.shiftwidth),
+            Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase => {
+                for segment in &segments {
+                    self.convert_case(operator, segment);
+                }
+
+                self.buffer.cursor.position = corner;
+            }
+        }
+    }
+
+    // Fills a line up with spaces until it reaches a column of the screen
+    fn pad_line(&mut self, y: usize, column: usize) {
+        let Some(line) = self.buffer.line(y) else {
+            return;
+        };
+
+        let width = line.column(line.len());
+
+        if width < column {
+            let index = self.buffer.char_index(Position { x: line.len(), y });
+            self.buffer.insert_str(index, &" ".repeat(column - width));
+        }
+    }
+
+    fn begin_block_insert(&mut self, append: bool) {
+        let (top, bottom, left, right) = self.block();
+        let column = if append { right } else { left };
+
+        if append {
+            self.pad_line(top, column);
+        }
+
+        self.mode = Mode::Insert;
+        print!("{}", escape::cursor::BLINKING_BAR);
+
+        self.buffer.cursor.position = self.column_position(top, column);
+        self.block_insert = Some(BlockInsert {
+            top,
+            bottom,
+            column,
+            append,
+        });
+    }
+
+    // Copies the text typed on the first line of the block to the other lines, nothing is copied
+    // when the cursor left the line, as the typed text can't be known then
+    fn finish_block_insert(&mut self, block: &BlockInsert) {
+        let Position { x, y } = self.buffer.cursor.position;
+        let start = self.column_position(block.top, block.column);
+
+        if y != block.top || x <= start.x {
+            return;
+        }
+
+        let typed = self.buffer.range_text(&Range {
+            start,
+            end: Position { x, y },
+            linewise: false,
+        });
+
+        for y in block.top + 1..=block.bottom {
+            if block.append {
+                self.pad_line(y, block.column);
+            } else if self
+                .buffer
+                .line(y)
+                .is_none_or(|line| line.column(line.len()) < block.column)
+            {
+                continue;
+            }
+
+            let index = self
+                .buffer
+                .char_index(self.column_position(y, block.column));
+            self.buffer.insert_str(index, &typed);
+        }
+
+        self.buffer.cursor.position = Position {
+            x: start.x + 1,
+            y: block.top,
+        };
+    }
+
+    fn put(&mut self, before: bool, count: usize, register: Option<char>) {
+        let register = register.unwrap_or(register::UNNAMED);
+
+        let register = match Registers::selection(register) {
+            Some(selection) => self.paste(register, selection),
+            None => self.registers.get(register).cloned(),
+        };
+
+        let Some(mut register) = register else {
+            return;
+        };
+
+        register.text = register.text.repeat(count);
+
+        let Position { x, y } = self.buffer.cursor.position;
+
+        if register.linewise {
+            let y = if before { y } else { y + 1 };
+
+            if y < self.buffer.line_count() {
+                let index = self.buffer.char_index(Position { x: 0, y });
+                self.buffer.insert_str(index, &register.text);
+            } else {
+                // There is no line break after the last line, so one is put in front instead
+                let last = self.buffer.line_count().saturating_sub(1);
+                let index = self.buffer.char_index(Position {
+                    x: self.buffer.line_len(last),
+                    y: last,
+                });
+
+                let text = register.text.strip_suffix('\n').unwrap_or(&register.text);
+                self.buffer.insert_str(index, &format!("\n{text}"));
+            }
+
+            self.buffer.cursor.position = Position { x: 0, y };
+        } else {
+            let x = if before {
+                x
+            } else {
+                x.saturating_add(1).min(self.buffer.line_len(y))
+            };
+
+            let index = self.buffer.char_index(Position { x, y });
+            self.buffer.insert_str(index, &register.text);
+
+            // Single line text leaves the cursor on its last character, anything else leaves it
+            // where the text starts
+            self.buffer.cursor.position.x = if register.text.contains('\n') {
+                x
+            } else {
+                x + register.text.graphemes(true).count().saturating_sub(1)
+            };
         }
     }
 
     fn handle_key(&mut self, key: Key) {
         match self.mode {
             Mode::Exit => unreachable!(),
-            Mode::Normal => self.handle_key_normal(key),
+            Mode::Normal | Mode::OperatorPending(_) => self.handle_key_normal(key),
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => self.handle_key_visual(key),
             Mode::Insert => self.handle_key_insert(key),
-            Mode::Command => self.handle_key_command(key),
+            Mode::Command | Mode::Search { .. } => self.handle_key_command(key),
+            Mode::Confirm => self.handle_key_confirm(key),
+            Mode::Recover => self.handle_key_recover(key),
+            Mode::Changed => self.handle_key_changed(key),
         }
     }
 }
@@ -1,79 +1,256 @@
 pub mod cursor;
+pub mod encoding;
+pub mod format;
+pub mod history;
 pub mod line;
+pub mod stamp;
+pub mod text;
 
-use crate::highlight;
+use crate::editor::{motion::Range, options::BackupCopy, Position};
+use crate::escape::CSI;
+use crate::hash::Fnv;
+use crate::highlight::Syntax;
+use crate::system::file;
 
 use cursor::Cursor;
+use encoding::Decode;
+use format::Format;
+use history::{Edit, History, Travel};
 use line::Line;
+use stamp::Stamp;
+use text::{Text, TextStorage};
 
 use std::{
     cell::{Ref, RefCell},
+    collections::HashMap,
+    env,
     ffi::OsStr,
-    fmt::Write as fmtWrite,
-    fs::File,
-    io::{Read, Result, Write},
-    path::PathBuf,
+    fs::{self, File},
+    hash::Hasher,
+    io::{ErrorKind, Read, Result, Write},
+    ops,
+    path::{self, Path, PathBuf},
     rc::Rc,
 };
 
-use tree_sitter_highlight::HighlightConfiguration;
-
 pub struct Buffer {
     pub cursor: Cursor,
-    config: Option<HighlightConfiguration>,
-    data: Rc<RefCell<Vec<Line>>>,
+    // The number the buffer is known by in the buffer list
+    pub number: usize,
+    // Positions remembered with m, for use in command ranges
+    pub marks: HashMap<char, Position>,
+    // The line breaks, final line break and byte order mark the file is written with
+    pub format: Format,
+    syntax: Option<Syntax>,
+    data: Rc<RefCell<Text>>,
     path: PathBuf,
+    // The colors of the bytes of the lines that have been shown since the text last changed
+    highlights: HashMap<usize, Vec<CSI>>,
+    // Incremented on every edit, so the highlights are only regenerated when the text has changed
+    revision: usize,
+    highlighted: Option<usize>,
+    history: History,
+    // The revision of the history the file was last loaded or saved at, undoing back to it makes the
+    // buffer unmodified again
+    saved: usize,
+    // The format the file was last loaded or saved with, changing it makes the buffer modified
+    saved_format: Format,
+    // What the file looked like when it was last read or written, nothing when it didn't exist
+    stamp: Option<Stamp>,
 }
 
 impl Buffer {
-    pub fn from_file(path: &PathBuf) -> Result<Self> {
-        let mut file = File::open(path)?;
-        let mut data = String::new();
+    pub fn from_file(path: &Path, number: usize, decode: Decode) -> Result<Self> {
+        let (format, data, stamp) = Self::read(path, decode)?;
 
-        file.read_to_string(&mut data)?;
+        let syntax = Syntax::new(path);
+        let text = Text::from(data.as_str());
+        let path = Self::resolve(path)?;
 
-        let mut lines: Vec<Line> = data.lines().map(Line::from).collect();
+        let history = history::file::read(&path, hash(&text)).unwrap_or_default();
 
-        if lines.is_empty() {
-            lines.push(Line::default());
-        }
+        let saved = history.current();
 
-        let config = highlight::config(path);
-        let data = Rc::new(RefCell::new(lines));
+        let data = Rc::new(RefCell::new(text));
         let cursor = Cursor::new(Rc::clone(&data));
-        let path = path.canonicalize()?;
 
         Ok(Self {
             data,
             cursor,
+            number,
+            marks: HashMap::new(),
+            format,
             path,
-            config,
+            syntax,
+            highlights: HashMap::new(),
+            revision: 0,
+            highlighted: None,
+            history,
+            saved,
+            saved_format: format,
+            stamp,
         })
     }
 
-    pub fn regenerate_highlights(&mut self) {
-        if let Some(config) = &self.config {
-            let source = self.data().iter().fold(String::new(), |mut output, line| {
-                _ = writeln!(output, "{line}");
+    // Reads a file and decodes it into the text a buffer stores, along with the format and stamp of
+    // the file
+    fn read(path: &Path, decode: Decode) -> Result<(Format, String, Option<Stamp>)> {
+        let mut bytes = Vec::new();
 
-                output
-            });
+        let stamp = match File::open(path) {
+            Ok(mut file) => {
+                file.read_to_end(&mut bytes)?;
+                Stamp::new(path, &bytes)
+            }
+            // A file that doesn't exist yet is opened as an empty buffer, and created when it is
+            // first written
+            Err(error) if error.kind() == ErrorKind::NotFound => None,
+            Err(error) => return Err(error),
+        };
 
-            if let Ok(colors) = highlight::colors(config, source.as_bytes()) {
-                let mut offset = 0;
+        // The text is stored decoded, with line feeds and without the final line break, the
+        // format it is turned back into when saving is remembered instead
+        let (format, data) = Format::detect(&bytes, decode);
 
-                for line in self.data.borrow_mut().iter_mut() {
-                    line.highlights = Some(colors[offset..offset + line.len()].to_vec());
-                    offset += line.len() + 1;
-                }
-            };
+        Ok((format, data, stamp))
+    }
+
+    // The text of a file the way a buffer would hold it, to compare the buffer with the file
+    pub fn read_contents(path: &Path, decode: Decode) -> Result<String> {
+        Self::read(path, decode).map(|(_, data, _)| data)
+    }
+
+    // Reads the file of the buffer again, in place of the text. The cursors onto the buffer stay
+    // where they were, and the history is the one kept for the new text
+    pub fn reload(&mut self, decode: Decode) -> Result<()> {
+        let (format, data, stamp) = Self::read(&self.path, decode)?;
+
+        let text = Text::from(data.as_str());
+        let history = history::file::read(&self.path, hash(&text)).unwrap_or_default();
+
+        *self.data.borrow_mut() = text;
+
+        self.saved = history.current();
+        self.history = history;
+        self.format = format;
+        self.saved_format = format;
+        self.stamp = stamp;
+
+        if let Some(syntax) = &mut self.syntax {
+            syntax.forget();
         }
+
+        self.revision += 1;
+
+        let y = self.cursor.position.y;
+
+        self.cursor.start();
+        self.cursor.goto_line(y);
+
+        Ok(())
     }
 
-    pub fn data(&self) -> Ref<'_, Vec<Line>> {
+    // Files that don't exist yet can't be canonicalized, so those are only made absolute
+    pub fn resolve(path: &Path) -> Result<PathBuf> {
+        path.canonicalize().or_else(|_| path::absolute(path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // The path relative to the working directory when the file is inside of it
+    pub fn display_path(&self) -> String {
+        let path = env::current_dir()
+            .ok()
+            .and_then(|directory| self.path.strip_prefix(directory).ok())
+            .unwrap_or(&self.path);
+
+        path.display().to_string()
+    }
+
+    // Makes the buffer belong to another file, like after :saveas
+    pub fn set_path(&mut self, path: PathBuf) {
+        self.syntax = Syntax::new(&path);
+        self.path = path;
+
+        self.highlights.clear();
+        self.highlighted = None;
+    }
+
+    // Highlights the lines that are about to be shown. The file is only parsed again once the text
+    // has changed, and then only where it did, the lines that were already highlighted are kept
+    // until then
+    pub fn regenerate_highlights(&mut self, lines: ops::Range<usize>) {
+        let Some(syntax) = &mut self.syntax else {
+            return;
+        };
+
+        let text = self.data.borrow();
+
+        if self.highlighted != Some(self.revision) {
+            syntax.parse(|byte| text.chunks(byte, usize::MAX).next().unwrap_or_default());
+
+            self.highlights.clear();
+            self.highlighted = Some(self.revision);
+        }
+
+        let missing: Vec<usize> = (lines.start..lines.end.min(text.len_lines()))
+            .filter(|y| !self.highlights.contains_key(y))
+            .collect();
+
+        let (Some(&first), Some(&last)) = (missing.first(), missing.last()) else {
+            return;
+        };
+
+        let start = text.line_to_byte(first);
+        let end = text.line_to_byte(last + 1);
+
+        let colors = syntax.colors(start, end, |node| {
+            text.chunks(node.start_byte(), node.end_byte())
+        });
+
+        for y in first..=last {
+            let from = text.line_to_byte(y) - start;
+            let to = text.line_to_byte(y + 1) - start;
+
+            self.highlights.insert(y, colors[from..to].to_vec());
+        }
+    }
+
+    // Changes on every edit, to tell whether the text is still the same
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    pub fn text(&self) -> Ref<'_, Text> {
         self.data.borrow()
     }
 
+    pub fn contents(&self) -> String {
+        self.data.borrow().slice(0, usize::MAX)
+    }
+
+    pub fn line(&self, y: usize) -> Option<Line> {
+        self.data.borrow().line(y)
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.data.borrow().len_lines()
+    }
+
+    pub fn line_len(&self, y: usize) -> usize {
+        self.data.borrow().line_len(y)
+    }
+
+    pub fn char_index(&self, position: Position) -> usize {
+        self.data.borrow().char_index(position)
+    }
+
+    pub fn highlights(&self, y: usize) -> Option<&[CSI]> {
+        self.highlights.get(&y).map(Vec::as_slice)
+    }
+
     pub fn file_name(&self) -> String {
         let file_name = self
             .path
@@ -85,64 +262,342 @@ impl Buffer {
         String::from(file_name)
     }
 
-    pub fn newline(&self) {
-        let mut lines = self.data.borrow_mut();
+    // Whether something else has written to the file since it was last read or written. A file
+    // that has been removed hasn't changed, as writing the buffer brings it back
+    pub fn is_changed_on_disk(&self) -> bool {
+        let Ok(metadata) = fs::metadata(&self.path) else {
+            return false;
+        };
+
+        // The file has been created since
+        let Some(stamp) = &self.stamp else {
+            return true;
+        };
+
+        stamp.is_stale(&metadata)
+            && Stamp::read(&self.path).is_none_or(|current| !stamp.same_contents(&current))
+    }
+
+    // Takes the file as it is now to be the one the buffer was read from, so that writing over it
+    // isn't refused anymore
+    pub fn accept_disk(&mut self) {
+        self.stamp = Stamp::read(&self.path);
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.history.has_pending()
+            || self.history.current() != self.saved
+            || self.format != self.saved_format
+    }
+
+    fn apply(&mut self, edit: &Edit) {
+        let mut data = self.data.borrow_mut();
 
-        if let Some(line) = lines.get_mut(self.cursor.position.y) {
-            let new = line.split(self.cursor.position.x);
+        // The syntax tree is told where the text changed, so that it doesn't have to be parsed
+        // from scratch
+        if let Some(syntax) = &mut self.syntax {
+            let (index, removed, text) = match edit {
+                Edit::Insert { index, text } => (*index, "", text.as_str()),
+                Edit::Remove { index, text } => (*index, text.as_str(), ""),
+            };
+
+            let byte = data.char_to_byte(index);
+            let row = data.byte_to_line(byte);
 
-            lines.insert(self.cursor.position.y.saturating_add(1), new)
+            syntax.edit(byte, (row, byte - data.line_to_byte(row)), removed, text);
         }
+
+        match edit {
+            Edit::Insert { index, text } => data.insert(*index, text),
+            Edit::Remove { index, text } => data.remove(*index, index + text.chars().count()),
+        }
+
+        self.revision = self.revision.wrapping_add(1);
     }
 
-    pub fn insert(&mut self, character: char) {
-        let mut lines = self.data.borrow_mut();
+    fn edit(&mut self, edit: Edit) {
+        self.apply(&edit);
+        self.history.record(edit, self.cursor.position);
+    }
 
-        if character == '\n' {
-            self.newline();
-        } else if let Some(line) = lines.get_mut(self.cursor.position.y) {
-            line.insert(self.cursor.position.x, character);
+    pub fn insert_str(&mut self, index: usize, text: &str) {
+        if text.is_empty() {
+            return;
         }
+
+        let text = text.to_string();
+        self.edit(Edit::Insert { index, text });
     }
 
-    pub fn delete(&mut self) {
-        let mut lines = self.data.borrow_mut();
+    pub fn remove(&mut self, start: usize, end: usize) {
+        let text = self.text().slice(start, end);
+
+        if text.is_empty() {
+            return;
+        }
+
+        let index = start.min(end);
+        self.edit(Edit::Remove { index, text });
+    }
+
+    // Replaces all of the text as one change, like when recovering it from a swap file
+    pub fn replace_all(&mut self, text: &str) {
+        self.remove(0, usize::MAX);
+        self.insert_str(0, text);
+        self.commit();
+    }
+
+    // Groups every edit since the last commit into one undoable change
+    pub fn commit(&mut self) {
+        self.history.commit();
+    }
+
+    // Returns false when there was nothing to move to in the history
+    fn travel(&mut self, change: Option<(Vec<Edit>, Position)>) -> bool {
+        let Some((edits, cursor)) = change else {
+            return false;
+        };
+
+        for edit in &edits {
+            self.apply(edit);
+        }
+
+        self.cursor.position = cursor;
+
+        true
+    }
+
+    // Returns false when there is nothing left to undo
+    pub fn undo(&mut self) -> bool {
+        let change = self.history.undo();
+        self.travel(change)
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let change = self.history.redo();
+        self.travel(change)
+    }
+
+    pub fn earlier(&mut self, travel: Travel) -> bool {
+        let change = self.history.earlier(travel);
+        self.travel(change)
+    }
+
+    pub fn later(&mut self, travel: Travel) -> bool {
+        let change = self.history.later(travel);
+        self.travel(change)
+    }
+
+    // Converts a range into the char indices it spans, linewise ranges include the line break after
+    // the last line, or the one before the first line when the range ends on the last line
+    fn bounds(&self, range: &Range) -> (usize, usize) {
+        let text = self.text();
+
+        if !range.linewise {
+            return (text.char_index(range.start), text.char_index(range.end));
+        }
+
+        let first = range.start.y;
+        let last = range.end.y.min(text.len_lines().saturating_sub(1));
+
+        let mut start = text.char_index(Position { x: 0, y: first });
+
+        let end = if last + 1 < text.len_lines() {
+            text.char_index(Position { x: 0, y: last + 1 })
+        } else {
+            start = start.saturating_sub(usize::from(first > 0));
+            text.char_index(Position {
+                x: text.line_len(last),
+                y: last,
+            })
+        };
+
+        (start, end)
+    }
+
+    pub fn range_text(&self, range: &Range) -> String {
+        if range.linewise {
+            let text = self.text();
+
+            let last = range.end.y.min(text.len_lines().saturating_sub(1));
+            let start = text.char_index(Position {
+                x: 0,
+                y: range.start.y,
+            });
+            let end = text.char_index(Position {
+                x: text.line_len(last),
+                y: last,
+            });
+
+            text.slice(start, end) + "\n"
+        } else {
+            let (start, end) = self.bounds(range);
+            self.text().slice(start, end)
+        }
+    }
+
+    pub fn delete_range(&mut self, range: &Range) {
+        let (start, end) = self.bounds(range);
+        self.remove(start, end);
+    }
+
+    pub fn indent(&mut self, start: usize, end: usize, width: usize) {
+        for y in start..=end.min(self.line_count().saturating_sub(1)) {
+            // Empty lines are left alone, so indenting doesn't leave trailing whitespace behind
+            if self.text().line_len(y) > 0 {
+                let index = self.text().char_index(Position { x: 0, y });
+                self.insert_str(index, &" ".repeat(width));
+            }
+        }
+    }
 
+    pub fn dedent(&mut self, start: usize, end: usize, width: usize) {
+        for y in start..=end.min(self.line_count().saturating_sub(1)) {
+            let Some(line) = self.line(y) else {
+                continue;
+            };
+
+            let line = line.to_string();
+
+            let width = if line.starts_with('\t') {
+                1
+            } else {
+                line.chars()
+                    .take(width)
+                    .take_while(|character| *character == ' ')
+                    .count()
+            };
+
+            let index = self.text().char_index(Position { x: 0, y });
+            self.remove(index, index + width);
+        }
+    }
+
+    pub fn newline(&mut self) {
+        self.insert('\n');
+    }
+
+    pub fn insert(&mut self, character: char) {
+        let index = self.text().char_index(self.cursor.position);
+
+        self.insert_str(index, character.encode_utf8(&mut [0; 4]));
+    }
+
+    pub fn delete(&mut self) {
         let position = self.cursor.position;
-        let length = lines.len();
 
-        if let Some(line) = lines.get_mut(position.y) {
+        let (start, end) = {
+            let text = self.text();
+            let start = text.char_index(position);
+
             // Since the position struct is zero based, we know to combine the lines whenever the x
-            // position is the same as the line length, which is one to the right of the line
-            if position.x == line.len() && position.y.saturating_add(1) < length {
-                let next = lines.remove(position.y.saturating_add(1));
-                lines[position.y].append(&next.to_string());
+            // position is the same as the line length, which is one to the right of the line, in
+            // which case the line break is removed
+            let end = if position.x >= text.line_len(position.y) {
+                start.saturating_add(1)
             } else {
-                line.delete(position.x);
+                text.char_index(Position {
+                    x: position.x.saturating_add(1),
+                    y: position.y,
+                })
+            };
+
+            (start, end)
+        };
+
+        self.remove(start, end);
+    }
+
+    // Writes the text, or a range of its lines, out to any file without it counting as the buffer
+    // being saved
+    pub fn write(
+        &self,
+        path: &Path,
+        lines: Option<(usize, usize)>,
+        append: bool,
+        backupcopy: BackupCopy,
+    ) -> Result<usize> {
+        let text = self.text();
+        let last = text.len_lines().saturating_sub(1);
+
+        // Every line of a range ends with a line break, except for the last line of a file without
+        // one at the end
+        let (part, eol) = match lines {
+            Some((start, end)) => {
+                let from = text.char_index(Position { x: 0, y: start });
+                let to = text.char_index(Position {
+                    x: text.line_len(end),
+                    y: end,
+                });
+
+                let part = Text::from(text.slice(from, to).as_str());
+                (Some(part), end < last || self.format.eol)
             }
+            None => (None, self.format.eol),
+        };
+
+        // The byte order mark only belongs at the start of a file
+        let format = Format {
+            bom: self.format.bom && !append,
+            eol,
+            ..self.format
+        };
+
+        let bytes = format.encode(part.as_ref().unwrap_or(&text))?;
+
+        Self::write_bytes(path, &bytes, append, backupcopy)
+    }
+
+    // A file that is written as a whole is replaced the way backupcopy says
+    fn write_bytes(
+        path: &Path,
+        bytes: &[u8],
+        append: bool,
+        backupcopy: BackupCopy,
+    ) -> Result<usize> {
+        let write = |writer: &mut dyn Write| {
+            writer.write_all(bytes)?;
+            Ok(bytes.len())
+        };
+
+        if append {
+            return file::append(path, write);
+        }
+
+        let rename = match backupcopy {
+            BackupCopy::Auto => file::can_rename(path),
+            BackupCopy::Yes => false,
+            BackupCopy::No => true,
+        };
+
+        if rename {
+            file::replace(path, write)
+        } else {
+            file::overwrite(path, write)
         }
     }
 
-    pub fn save(&self) -> Result<usize> {
-        let mut file = File::create(&self.path)?;
+    pub fn save(&mut self, backupcopy: BackupCopy) -> Result<usize> {
+        let bytes = self.format.encode(&self.text())?;
 
-        let lines = self.data();
+        Self::write_bytes(&self.path, &bytes, false, backupcopy)?;
+        self.stamp = Stamp::new(&self.path, &bytes);
 
-        // This is probably not very effective, as to_vec copies the byte slice, however,
-        // using an iterator to collect the bytes and writing to file once is preferable
-        //
-        // Doing it with an iterator without copying the slice to append the newline is probably
-        // not possible due to rust's ownership rules
-        let data: Vec<u8> = lines
-            .iter()
-            .flat_map(|line| {
-                let mut bytes = line.as_bytes().to_vec();
-                bytes.push(b'\n');
+        // The history is kept next to the other state, so it can be picked up again the next time
+        // the file is opened, as long as the file hasn't been changed by something else since
+        self.history.commit();
+        self.saved = self.history.current();
+        self.saved_format = self.format;
 
-                bytes
-            })
-            .collect();
+        _ = history::file::write(&self.history, &self.path, hash(&self.text()));
 
-        file.write(&data)
+        Ok(bytes.len())
     }
 }
+
+fn hash(text: &Text) -> u64 {
+    let mut hasher = Fnv::default();
+    _ = text.write_to(&mut hasher);
+
+    hasher.finish()
+}
@@ -1,23 +1,27 @@
+mod boundary;
 mod direction;
 
-use super::Line;
+use super::text::{Text, TextStorage};
 
-use crate::editor::{self, Mode, Position};
+use crate::editor::{self, window::Rect, Mode, Position};
 use crate::escape;
 use crate::unix::size::TermSize;
 
 pub use direction::Direction;
 
-use std::{cell::RefCell, fmt::Display, rc::Rc};
+use std::{cell::RefCell, rc::Rc};
 
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Clone)]
 pub struct Cursor {
     pub position: Position,
     pub offset: Position,
-    data: Rc<RefCell<Vec<Line>>>,
+    data: Rc<RefCell<Text>>,
 }
 
 impl Cursor {
-    pub fn new(data: Rc<RefCell<Vec<Line>>>) -> Self {
+    pub fn new(data: Rc<RefCell<Text>>) -> Self {
         Self {
             position: Position::default(),
             offset: Position::default(),
@@ -25,11 +29,35 @@ impl Cursor {
         }
     }
 
+    // The words of a line as grapheme ranges, read from the text in place
+    fn words(&self, y: usize, big: bool) -> Vec<(usize, usize)> {
+        self.data
+            .borrow()
+            .line_text(y)
+            .map_or_else(Vec::new, |line| boundary::words(&line, big))
+    }
+
+    fn height(&self) -> usize {
+        self.data.borrow().len_lines()
+    }
+
+    pub fn first_non_blank(&self, y: usize) -> usize {
+        self.data.borrow().line_text(y).map_or(0, |line| {
+            line.graphemes(true)
+                .position(|grapheme| !boundary::is_blank(grapheme))
+                .unwrap_or(0)
+        })
+    }
+
+    pub fn line_len(&self, y: usize) -> usize {
+        self.data.borrow().line_len(y)
+    }
+
     fn size(&self) -> (usize, usize) {
         let data = self.data.borrow();
 
-        let height = data.len();
-        let length = data.get(self.position.y).unwrap().len();
+        let height = data.len_lines();
+        let length = data.line_len(self.position.y);
 
         (height, length)
     }
@@ -79,7 +107,6 @@ impl Cursor {
 
     pub fn scroll(&mut self, size: &TermSize) {
         let height = size.height.saturating_sub(1) as usize;
-        let width = size.width.saturating_sub(1) as usize;
 
         if self.position.y > self.offset.y.saturating_add(height) {
             self.offset.y = self.position.y.saturating_sub(height);
@@ -87,12 +114,20 @@ impl Cursor {
             self.offset.y = self.position.y;
         }
 
-        let ln_offset = editor::utils::ln_offset(&self.data.borrow());
+        // The text is scrolled sideways by columns rather than graphemes, so that a wide grapheme
+        // is shown whole
+        let ln_offset = editor::utils::ln_offset(self.data.borrow().len_lines());
+        let visible = (size.width as usize).saturating_sub(ln_offset);
+
+        let line = self.data.borrow().line(self.position.y).unwrap_or_default();
 
-        if self.position.x > self.offset.x.saturating_add(width - ln_offset) {
-            self.offset.x = self.position.x.saturating_sub(width - ln_offset);
-        } else if self.position.x < self.offset.x {
-            self.offset.x = self.position.x;
+        let start = line.column(self.position.x);
+        let end = start + line.width(self.position.x);
+
+        if end > self.offset.x.saturating_add(visible) {
+            self.offset.x = end.saturating_sub(visible);
+        } else if start < self.offset.x {
+            self.offset.x = start;
         }
     }
 
@@ -112,7 +147,11 @@ impl Cursor {
     }
 
     pub fn bottom(&mut self) {
-        self.position.y = self.data.borrow().len().saturating_sub(1);
+        self.position.y = self.data.borrow().len_lines().saturating_sub(1);
+    }
+
+    pub fn goto_line(&mut self, y: usize) {
+        self.position.y = y.min(self.height().saturating_sub(1));
     }
 
     pub fn start(&mut self) {
@@ -125,21 +164,398 @@ impl Cursor {
         self.position.x = length.saturating_sub(1);
     }
 
+    // Moves to the start of the next word, stopping at empty lines, or to the end of the text when
+    // there are no words left
+    pub fn word_forward(&mut self, big: bool) {
+        let Position { x, y } = self.position;
+
+        if let Some(&(start, _)) = self.words(y, big).iter().find(|(start, _)| *start > x) {
+            self.position.x = start;
+            return;
+        }
+
+        for y in y.saturating_add(1)..self.height() {
+            if self.line_len(y) == 0 {
+                self.position = Position { x: 0, y };
+                return;
+            }
+
+            if let Some(&(start, _)) = self.words(y, big).first() {
+                self.position = Position { x: start, y };
+                return;
+            }
+        }
+
+        let y = self.height().saturating_sub(1);
+        let x = self.line_len(y);
+
+        self.position = Position { x, y };
+    }
+
+    pub fn word_backward(&mut self, big: bool) {
+        let Position { x, y } = self.position;
+
+        if let Some(&(start, _)) = self
+            .words(y, big)
+            .iter()
+            .rev()
+            .find(|(start, _)| *start < x)
+        {
+            self.position.x = start;
+            return;
+        }
+
+        for y in (0..y).rev() {
+            if self.line_len(y) == 0 {
+                self.position = Position { x: 0, y };
+                return;
+            }
+
+            if let Some(&(start, _)) = self.words(y, big).last() {
+                self.position = Position { x: start, y };
+                return;
+            }
+        }
+
+        self.position = Position::default();
+    }
+
+    pub fn word_end(&mut self, big: bool) {
+        let Position { x, y } = self.position;
+
+        if let Some(&(_, end)) = self
+            .words(y, big)
+            .iter()
+            .find(|(_, end)| end.saturating_sub(1) > x)
+        {
+            self.position.x = end.saturating_sub(1);
+            return;
+        }
+
+        for y in y.saturating_add(1)..self.height() {
+            if let Some(&(_, end)) = self.words(y, big).first() {
+                self.position = Position {
+                    x: end.saturating_sub(1),
+                    y,
+                };
+                return;
+            }
+        }
+    }
+
+    // Paragraphs are separated by empty lines, the motions move to the next or previous empty line
+    // that comes after a paragraph
+    pub fn paragraph_forward(&mut self) {
+        let height = self.height();
+        let empty = |y: usize| self.line_len(y) == 0;
+
+        let mut y = self.position.y;
+
+        while y < height && empty(y) {
+            y += 1;
+        }
+
+        while y < height && !empty(y) {
+            y += 1;
+        }
+
+        self.position = if y < height {
+            Position { x: 0, y }
+        } else {
+            let y = height.saturating_sub(1);
+            let x = self.line_len(y);
+
+            Position { x, y }
+        };
+    }
+
+    pub fn paragraph_backward(&mut self) {
+        let empty = |y: usize| self.line_len(y) == 0;
+
+        let mut y = self.position.y;
+
+        while y > 0 && empty(y) {
+            y -= 1;
+        }
+
+        while y > 0 && !empty(y) {
+            y -= 1;
+        }
+
+        self.position = Position { x: 0, y };
+    }
+
+    // The first sentence start after the given position. A sentence ends with '.', '!' or '?',
+    // optionally followed by closing brackets and quotes, and then a blank or a line break. Empty
+    // lines are sentence boundaries as well
+    fn next_sentence(&self, from: Position) -> Option<Position> {
+        let data = self.data.borrow();
+        let line = |y: usize| data.line_text(y).unwrap_or_default();
+
+        let mut gap = from.y == 0 || {
+            let previous = line(from.y - 1);
+            previous.is_empty() || boundary::ends_sentence(previous.trim_end())
+        };
+        let mut ended = false;
+
+        for y in from.y..data.len_lines() {
+            let line = line(y);
+
+            if line.is_empty() {
+                if y > from.y {
+                    return Some(Position { x: 0, y });
+                }
+
+                gap = true;
+                continue;
+            }
+
+            for (x, grapheme) in line.graphemes(true).enumerate() {
+                let position = Position { x, y };
+
+                if boundary::is_blank(grapheme) {
+                    gap |= ended;
+                    ended = false;
+                    continue;
+                }
+
+                if gap && position > from {
+                    return Some(position);
+                }
+
+                gap = false;
+                ended =
+                    boundary::is_terminator(grapheme) || (ended && boundary::is_closer(grapheme));
+            }
+
+            gap |= ended;
+            ended = false;
+        }
+
+        None
+    }
+
+    pub fn sentence_forward(&mut self) {
+        self.position = self.next_sentence(self.position).unwrap_or_else(|| {
+            let y = self.height().saturating_sub(1);
+            let x = self.line_len(y);
+
+            Position { x, y }
+        });
+    }
+
+    // Walks forward from the start of the paragraph, as sentences can't be found reliably when
+    // reading backwards, moving on to earlier paragraphs when there is no sentence start before
+    // the cursor in the current one
+    pub fn sentence_backward(&mut self) {
+        let from = self.position;
+        let mut y = from.y;
+
+        loop {
+            if self.line_len(y) == 0 {
+                if (Position { x: 0, y }) < from {
+                    self.position = Position { x: 0, y };
+                    return;
+                }
+            } else {
+                while y > 0 && self.line_len(y - 1) > 0 {
+                    y -= 1;
+                }
+
+                let mut candidate = Some(Position {
+                    x: self.first_non_blank(y),
+                    y,
+                });
+                let mut best = None;
+
+                while let Some(position) = candidate.filter(|position| *position < from) {
+                    best = Some(position);
+                    candidate = self.next_sentence(position);
+                }
+
+                if let Some(position) = best {
+                    self.position = position;
+                    return;
+                }
+            }
+
+            if y == 0 {
+                self.position = Position::default();
+                return;
+            }
+
+            y -= 1;
+        }
+    }
+
+    // The amount of lines of the text that are visible on the screen
+    fn visible(&self, size: &TermSize) -> usize {
+        let remaining = self.height().saturating_sub(self.offset.y);
+        (size.height as usize).min(remaining).max(1)
+    }
+
+    fn screen_line(&mut self, line: usize) {
+        let y = self.offset.y + line;
+
+        self.position = Position {
+            x: self.first_non_blank(y),
+            y,
+        };
+    }
+
+    // Moves to the count'th line from the top of the screen
+    pub fn screen_top(&mut self, count: usize, size: &TermSize) {
+        let last = self.visible(size) - 1;
+        self.screen_line(count.saturating_sub(1).min(last));
+    }
+
+    pub fn screen_middle(&mut self, size: &TermSize) {
+        let last = self.visible(size) - 1;
+        self.screen_line(last / 2);
+    }
+
+    // Moves to the count'th line from the bottom of the screen
+    pub fn screen_bottom(&mut self, count: usize, size: &TermSize) {
+        let last = self.visible(size) - 1;
+        self.screen_line(last.saturating_sub(count.saturating_sub(1)));
+    }
+
     pub fn center(&mut self, size: &TermSize) {
         self.offset.y = self.position.y.saturating_sub(size.height as usize / 2);
     }
-}
 
-impl Display for Cursor {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    // Where the cursor is on the screen when the text is shown in a window
+    pub fn goto(&self, rect: &Rect) -> escape::cursor::Goto {
         let position = &self.position;
         let offset = &self.offset;
 
+        let line = self.data.borrow().line(position.y).unwrap_or_default();
+
         let y = position.y.saturating_sub(offset.y).saturating_add(1);
-        let x = position.x.saturating_sub(offset.x).saturating_add(1);
+        let x = line
+            .column(position.x)
+            .saturating_sub(offset.x)
+            .saturating_add(1);
+
+        let ln_offset = editor::utils::ln_offset(self.data.borrow().len_lines());
+
+        escape::cursor::Goto(rect.y + y, rect.x + x + ln_offset)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
 
-        let offset = editor::utils::ln_offset(&self.data.borrow());
+    // A cursor over some text, placed at a column and line
+    pub fn cursor(text: &str, x: usize, y: usize) -> Cursor {
+        let mut cursor = Cursor::new(Rc::new(RefCell::new(Text::from(text))));
+        cursor.position = Position { x, y };
+
+        cursor
+    }
+
+    // Where the cursor ends up after each of the given moves, starting from the origin
+    fn path(text: &str, mut step: impl FnMut(&mut Cursor)) -> Vec<(usize, usize)> {
+        let mut cursor = cursor(text, 0, 0);
+        let mut path = Vec::new();
+
+        loop {
+            step(&mut cursor);
+
+            let position = (cursor.position.x, cursor.position.y);
+
+            if path.last() == Some(&position) {
+                return path;
+            }
+
+            path.push(position);
+        }
+    }
+
+    #[test]
+    fn word_forward_joins_punctuation() {
+        let path = path("foo::bar baz\n\nqux", |cursor| cursor.word_forward(false));
+
+        assert_eq!(path, [(3, 0), (5, 0), (9, 0), (0, 1), (0, 2), (3, 2)]);
+    }
+
+    #[test]
+    fn big_words_only_end_at_blanks() {
+        let path = path("foo::bar baz\nqux", |cursor| cursor.word_forward(true));
+
+        assert_eq!(path, [(9, 0), (0, 1), (3, 1)]);
+    }
+
+    #[test]
+    fn word_forward_counts_graphemes() {
+        let path = path("e\u{301}te\u{301} vu", |cursor| cursor.word_forward(false));
+
+        assert_eq!(path, [(4, 0), (6, 0)]);
+    }
+
+    // Lines this long are split over several chunks of the rope
+    #[test]
+    fn words_of_long_lines() {
+        let text = "word ".repeat(1000);
+        let mut cursor = cursor(&text, 4990, 0);
+
+        cursor.word_forward(false);
+        assert_eq!(cursor.position.x, 4995);
+
+        cursor.word_end(false);
+        assert_eq!(cursor.position.x, 4998);
+    }
+
+    #[test]
+    fn word_backward() {
+        let mut cursor = cursor("one two\n\nthree four", 6, 2);
+        let mut path = Vec::new();
+
+        for _ in 0..5 {
+            cursor.word_backward(false);
+            path.push((cursor.position.x, cursor.position.y));
+        }
+
+        assert_eq!(path, [(0, 2), (0, 1), (4, 0), (0, 0), (0, 0)]);
+    }
+
+    #[test]
+    fn word_end() {
+        let path = path("one two\nthree", |cursor| cursor.word_end(false));
+
+        assert_eq!(path, [(2, 0), (6, 0), (4, 1)]);
+    }
+
+    #[test]
+    fn paragraphs() {
+        let text = "one\ntwo\n\n\nthree\nfour";
+
+        assert_eq!(path(text, Cursor::paragraph_forward), [(0, 2), (4, 5)]);
+
+        let mut cursor = cursor(text, 0, 5);
+        cursor.paragraph_backward();
+
+        assert_eq!((cursor.position.x, cursor.position.y), (0, 3));
+    }
+
+    #[test]
+    fn sentences() {
+        let text = "One. (Two?) Three\nfour.\n\nFive";
+
+        assert_eq!(
+            path(text, Cursor::sentence_forward),
+            [(5, 0), (12, 0), (0, 2), (0, 3), (4, 3)]
+        );
+
+        let mut cursor = cursor(text, 2, 3);
+        let mut path = Vec::new();
+
+        for _ in 0..4 {
+            cursor.sentence_backward();
+            path.push((cursor.position.x, cursor.position.y));
+        }
 
-        write!(f, "{}", escape::cursor::Goto(y, x + offset))
+        assert_eq!(path, [(0, 3), (0, 2), (12, 0), (5, 0)]);
     }
 }
@@ -0,0 +1,77 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+// Characters that may follow the punctuation ending a sentence, such as a closing quote
+const SENTENCE_CLOSERS: [char; 4] = [')', ']', '"', '\''];
+const SENTENCE_TERMINATORS: [char; 3] = ['.', '!', '?'];
+
+#[derive(PartialEq, Clone, Copy)]
+enum Class {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+fn class(segment: &str) -> Class {
+    if segment.chars().all(char::is_whitespace) {
+        Class::Blank
+    } else if segment.chars().any(|c| c.is_alphanumeric() || c == '_') {
+        Class::Word
+    } else {
+        Class::Punctuation
+    }
+}
+
+// Splits a line into words, given as grapheme ranges, using the Unicode word boundaries.
+// Consecutive punctuation is joined into a single word like vim does, so that something like "::"
+// is one word, and big words (WORDs in vim) are any run of non-blank characters
+pub fn words(line: &str, big: bool) -> Vec<(usize, usize)> {
+    let mut words: Vec<(usize, usize, Class)> = Vec::new();
+    let mut x = 0;
+
+    for segment in line.split_word_bounds() {
+        let length = segment.graphemes(true).count();
+        let class = class(segment);
+
+        let start = x;
+        x += length;
+
+        if class == Class::Blank {
+            continue;
+        }
+
+        if let Some(last) = words.last_mut() {
+            let joined = last.1 == start && (big || class == Class::Punctuation && last.2 == class);
+
+            if joined {
+                last.1 = x;
+                continue;
+            }
+        }
+
+        words.push((start, x, class));
+    }
+
+    words
+        .into_iter()
+        .map(|(start, end, _)| (start, end))
+        .collect()
+}
+
+pub fn is_blank(grapheme: &str) -> bool {
+    grapheme.chars().all(char::is_whitespace)
+}
+
+// Whether the text before a point ends a sentence, meaning the next non-blank character starts a
+// new one, given that it is followed by a blank or a line break
+pub fn ends_sentence(text: &str) -> bool {
+    let text = text.trim_end_matches(SENTENCE_CLOSERS);
+    text.ends_with(SENTENCE_TERMINATORS)
+}
+
+pub fn is_terminator(grapheme: &str) -> bool {
+    grapheme.chars().all(|c| SENTENCE_TERMINATORS.contains(&c))
+}
+
+pub fn is_closer(grapheme: &str) -> bool {
+    grapheme.chars().all(|c| SENTENCE_CLOSERS.contains(&c))
+}
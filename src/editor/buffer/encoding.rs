@@ -0,0 +1,355 @@
+use encoding_rs::{DecoderResult, EncoderResult, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+// The bytes that aren't valid in the encoding of a file are kept in the text as characters from
+// the end of the last private use plane, one for each byte, so that they are shown as <xx> and
+// written back exactly as they were
+const ESCAPE: u32 = 0x10ff00;
+
+pub fn escape(byte: u8) -> char {
+    char::from_u32(ESCAPE + u32::from(byte)).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+pub fn escaped(character: char) -> Option<u8> {
+    u32::from(character)
+        .checked_sub(ESCAPE)
+        .and_then(|byte| u8::try_from(byte).ok())
+}
+
+// The encoding a file is read and written in
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Encoding(&'static encoding_rs::Encoding);
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self(UTF_8)
+    }
+}
+
+impl Encoding {
+    // Takes the names vim knows the encodings by as well as the standard labels, like latin1,
+    // utf-16le or iso-8859-2
+    pub fn parse(label: &str) -> Option<Self> {
+        let label = match label {
+            "utf8" => "utf-8",
+            "utf-16" | "ucs-2" | "ucs-2le" => "utf-16le",
+            "ucs-2be" => "utf-16be",
+            _ => label,
+        };
+
+        encoding_rs::Encoding::for_label_no_replacement(label.as_bytes()).map(Self)
+    }
+
+    pub fn name(self) -> String {
+        // Latin-1 is read as windows-1252 like browsers do, which has a character for every byte
+        if self.0 == WINDOWS_1252 {
+            return "latin1".to_string();
+        }
+
+        self.0.name().to_lowercase()
+    }
+
+    pub fn is_utf8(self) -> bool {
+        self.0 == UTF_8
+    }
+
+    // Finds the encoding a file is in from the byte order mark at its start, along with the length
+    // of the mark
+    pub fn sniff(bytes: &[u8]) -> Option<(Self, usize)> {
+        encoding_rs::Encoding::for_bom(bytes).map(|(encoding, length)| (Self(encoding), length))
+    }
+
+    pub fn bom(self) -> &'static [u8] {
+        match self.0 {
+            encoding if encoding == UTF_8 => b"\xef\xbb\xbf",
+            encoding if encoding == UTF_16LE => b"\xff\xfe",
+            encoding if encoding == UTF_16BE => b"\xfe\xff",
+            _ => b"",
+        }
+    }
+
+    // Turns the bytes into text with the bytes that are wrong escaped, returning whether there
+    // weren't any
+    pub fn decode(self, bytes: &[u8]) -> (String, bool) {
+        let mut decoder = self.0.new_decoder_without_bom_handling();
+
+        let capacity = decoder
+            .max_utf8_buffer_length_without_replacement(bytes.len())
+            .unwrap_or(bytes.len());
+
+        let mut text = String::with_capacity(capacity);
+        let mut clean = true;
+        let mut read = 0;
+
+        loop {
+            let decoded = text.len();
+
+            let (result, length) =
+                decoder.decode_to_string_without_replacement(&bytes[read..], &mut text, true);
+
+            read += length;
+            self.escape_lookalikes(&mut text, decoded);
+
+            match result {
+                DecoderResult::InputEmpty => break,
+                DecoderResult::OutputFull => text.reserve(bytes.len() - read + 16),
+                DecoderResult::Malformed(malformed, after) => {
+                    let end = read - usize::from(after);
+                    let start = end - usize::from(malformed);
+
+                    text.extend(bytes[start..end].iter().copied().map(escape));
+                    clean = false;
+                }
+            }
+        }
+
+        (text, clean)
+    }
+
+    // Characters that look like escapes but were really in the file are escaped themselves, so
+    // that they come out as the same bytes again
+    fn escape_lookalikes(self, text: &mut String, from: usize) {
+        if !text[from..]
+            .chars()
+            .any(|character| escaped(character).is_some())
+        {
+            return;
+        }
+
+        for character in text.split_off(from).chars() {
+            if escaped(character).is_none() {
+                text.push(character);
+                continue;
+            }
+
+            let mut bytes = Vec::new();
+            _ = self.encode_run(character.encode_utf8(&mut [0; 4]), &mut bytes);
+
+            text.extend(bytes.into_iter().map(escape));
+        }
+    }
+
+    // Turns the text into bytes, failing with the first character the encoding has no bytes for
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, char> {
+        let mut bytes = Vec::with_capacity(text.len());
+        let mut start = 0;
+
+        for (index, character) in text.char_indices() {
+            if let Some(byte) = escaped(character) {
+                self.encode_run(&text[start..index], &mut bytes)?;
+                bytes.push(byte);
+
+                start = index + character.len_utf8();
+            }
+        }
+
+        self.encode_run(&text[start..], &mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn encode_run(self, text: &str, bytes: &mut Vec<u8>) -> Result<(), char> {
+        match self.0 {
+            encoding if encoding == UTF_8 => bytes.extend_from_slice(text.as_bytes()),
+            // UTF-16 is only ever decoded by encoding_rs, so it is encoded here
+            encoding if encoding == UTF_16LE => {
+                bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            }
+            encoding if encoding == UTF_16BE => {
+                bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            }
+            encoding => {
+                let mut encoder = encoding.new_encoder();
+                let mut read = 0;
+
+                loop {
+                    let (result, length) = encoder.encode_from_utf8_to_vec_without_replacement(
+                        &text[read..],
+                        bytes,
+                        true,
+                    );
+
+                    read += length;
+
+                    match result {
+                        EncoderResult::InputEmpty => break,
+                        EncoderResult::OutputFull => bytes.reserve(text.len() - read + 16),
+                        EncoderResult::Unmappable(character) => return Err(character),
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// How the encoding of a file is picked when it is read
+#[derive(Clone, Copy)]
+pub enum Decode<'a> {
+    // The byte order mark decides, otherwise the first of the encodings the file is valid in
+    Detect(&'a [Encoding]),
+    // Given with ++enc, the file is read in it whatever it looks like
+    Force(Encoding),
+}
+
+impl Decode<'_> {
+    // Returns the text along with the encoding it was read in and whether it had a byte order mark
+    pub fn decode(self, bytes: &[u8]) -> (String, Encoding, bool) {
+        let candidates = match self {
+            Self::Force(encoding) => {
+                let bom = encoding.bom();
+                let has_bom = !bom.is_empty() && bytes.starts_with(bom);
+
+                let (text, _) = encoding.decode(&bytes[if has_bom { bom.len() } else { 0 }..]);
+                return (text, encoding, has_bom);
+            }
+            Self::Detect(candidates) => candidates,
+        };
+
+        if let Some((encoding, length)) = Encoding::sniff(bytes) {
+            let (text, _) = encoding.decode(&bytes[length..]);
+            return (text, encoding, true);
+        }
+
+        for encoding in candidates {
+            let (text, clean) = encoding.decode(bytes);
+
+            // A file with UTF-8 characters in it that has a few broken bytes is still UTF-8, those
+            // are escaped instead of the whole file being read as something else
+            let damaged = encoding.is_utf8()
+                && text
+                    .chars()
+                    .any(|character| !character.is_ascii() && escaped(character).is_none());
+
+            if clean || damaged {
+                return (text, *encoding, false);
+            }
+        }
+
+        // Nothing fits, so the first encoding is used with everything that doesn't fit escaped
+        let encoding = candidates.first().copied().unwrap_or_default();
+        let (text, _) = encoding.decode(bytes);
+
+        (text, encoding, false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoding(label: &str) -> Encoding {
+        Encoding::parse(label).expect("a known encoding")
+    }
+
+    fn round_trip(encoding: Encoding, bytes: &[u8]) -> (String, Vec<u8>) {
+        let (text, _) = encoding.decode(bytes);
+        let written = encoding.encode(&text).expect("to encode");
+
+        (text, written)
+    }
+
+    #[test]
+    fn escapes() {
+        for byte in [0x00, 0x80, 0xff] {
+            assert_eq!(escaped(escape(byte)), Some(byte));
+        }
+
+        assert_eq!(escaped('a'), None);
+        assert_eq!(escape(0x80), '\u{10ff80}');
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let bytes = b"a\xffb\xc3";
+        let (text, written) = round_trip(Encoding::default(), bytes);
+
+        assert_eq!(text, "a\u{10ffff}b\u{10ffc3}");
+        assert_eq!(written, bytes);
+    }
+
+    #[test]
+    fn clean_decode() {
+        assert_eq!(
+            Encoding::default().decode("é".as_bytes()),
+            (String::from("é"), true)
+        );
+        assert!(!Encoding::default().decode(b"\xff").1);
+    }
+
+    // A character that is already an escape is escaped byte by byte, so that it isn't written as
+    // the single byte it stands for
+    #[test]
+    fn lookalikes() {
+        let bytes = "x\u{10ff41}y".as_bytes();
+        let (text, written) = round_trip(Encoding::default(), bytes);
+
+        assert_eq!(text.chars().count(), 6);
+        assert_eq!(written, bytes);
+    }
+
+    #[test]
+    fn invalid_utf16() {
+        let encoding = encoding("utf-16le");
+
+        // An unpaired surrogate followed by an a
+        let bytes = b"\x00\xd8a\x00";
+        let (text, written) = round_trip(encoding, bytes);
+
+        assert!(text.ends_with('a'));
+        assert_eq!(written, bytes);
+    }
+
+    #[test]
+    fn latin1() {
+        let encoding = encoding("latin1");
+        let (text, written) = round_trip(encoding, b"caf\xe9");
+
+        assert_eq!(text, "café");
+        assert_eq!(written, b"caf\xe9");
+        assert_eq!(encoding.name(), "latin1");
+    }
+
+    #[test]
+    fn unmappable() {
+        assert_eq!(encoding("latin1").encode("€ and ☃"), Err('☃'));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(encoding("utf8").name(), "utf-8");
+        assert_eq!(encoding("ucs-2be").name(), "utf-16be");
+        assert!(Encoding::parse("nonsense").is_none());
+    }
+
+    #[test]
+    fn detect() {
+        let candidates = [Encoding::default(), encoding("latin1")];
+
+        let (text, found, bom) = Decode::Detect(&candidates).decode(b"caf\xe9");
+        assert_eq!(
+            (text.as_str(), found.name(), bom),
+            ("café", "latin1".into(), false)
+        );
+
+        // Broken bytes in a file that is otherwise UTF-8 don't make it latin1
+        let (text, found, _) = Decode::Detect(&candidates).decode(b"\xc3\xa9\xff");
+        assert_eq!((text.as_str(), found.is_utf8()), ("é\u{10ffff}", true));
+
+        let (text, found, bom) = Decode::Detect(&candidates).decode(b"\xff\xfea\x00");
+        assert_eq!(
+            (text.as_str(), found.name(), bom),
+            ("a", "utf-16le".into(), true)
+        );
+    }
+
+    #[test]
+    fn force() {
+        let (text, found, bom) = Decode::Force(encoding("latin1")).decode(b"\xef\xbb\xbf");
+
+        assert_eq!(
+            (text.as_str(), found.name(), bom),
+            ("ï»¿", "latin1".into(), false)
+        );
+    }
+}
@@ -0,0 +1,229 @@
+use super::{
+    encoding::{Decode, Encoding},
+    text::{Text, TextStorage},
+};
+
+use std::io::{Error, ErrorKind, Result};
+
+// The line breaks a file uses, which are kept as line feeds in the text
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    Unix,
+    Dos,
+}
+
+impl LineEnding {
+    pub const VALUES: [(&'static str, Self); 2] = [("unix", Self::Unix), ("dos", Self::Dos)];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Unix => "unix",
+            Self::Dos => "dos",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::VALUES
+            .iter()
+            .find(|(value, _)| *value == name)
+            .map(|(_, value)| *value)
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Unix => "\n",
+            Self::Dos => "\r\n",
+        }
+    }
+}
+
+// How a file looked apart from its text, so that it is written back the way it was read
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+    // Whether the last line ends with a line break
+    pub eol: bool,
+    // Whether the file starts with a byte order mark, for the encodings that have one
+    pub bom: bool,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            encoding: Encoding::default(),
+            line_ending: LineEnding::Unix,
+            eol: true,
+            bom: false,
+        }
+    }
+}
+
+impl Format {
+    // Works out the format of the contents of a file and takes it off of them, returning the text.
+    // A file only counts as dos when every line break in it is one, otherwise the carriage returns
+    // are left in the text so that nothing is lost when it is written back
+    pub fn detect(bytes: &[u8], decode: Decode) -> (Self, String) {
+        let (mut data, encoding, bom) = decode.decode(bytes);
+
+        let mut format = Self {
+            encoding,
+            bom,
+            ..Self::default()
+        };
+
+        let breaks = data.matches('\n').count();
+
+        if breaks > 0 && data.matches("\r\n").count() == breaks {
+            data = data.replace("\r\n", "\n");
+            format.line_ending = LineEnding::Dos;
+        }
+
+        // An empty file is taken to want a line break once something is written to it
+        if data.ends_with('\n') {
+            data.pop();
+        } else if !data.is_empty() {
+            format.eol = false;
+        }
+
+        (format, data)
+    }
+
+    // Turns the text into the bytes of the file the way the format says. An empty text becomes an
+    // empty file, the same as vim does for a buffer without any lines
+    pub fn encode(&self, text: &Text) -> Result<Vec<u8>> {
+        let mut data = text.slice(0, usize::MAX);
+
+        if self.line_ending == LineEnding::Dos {
+            data = data.replace('\n', "\r\n");
+        }
+
+        if self.eol && !data.is_empty() {
+            data.push_str(self.line_ending.as_str());
+        }
+
+        let mut bytes = if self.bom {
+            self.encoding.bom().to_vec()
+        } else {
+            Vec::new()
+        };
+
+        let encoded = self.encoding.encode(&data).map_err(|character| {
+            let message = format!(
+                "Conversion error: {character:?} can't be written in {}",
+                self.encoding.name()
+            );
+
+            Error::new(ErrorKind::InvalidData, message)
+        })?;
+
+        bytes.extend(encoded);
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect(bytes: &[u8]) -> (Format, String) {
+        let encodings = [Encoding::default()];
+
+        Format::detect(bytes, Decode::Detect(&encodings))
+    }
+
+    fn round_trip(bytes: &[u8]) -> Format {
+        let (format, data) = detect(bytes);
+        let written = format
+            .encode(&Text::from(data.as_str()))
+            .expect("to encode");
+
+        assert_eq!(written, bytes);
+
+        format
+    }
+
+    #[test]
+    fn unix() {
+        let format = round_trip(b"a\nb\n");
+
+        assert!(format.line_ending == LineEnding::Unix);
+        assert!(format.eol);
+        assert!(!format.bom);
+        assert_eq!(detect(b"a\nb\n").1, "a\nb");
+    }
+
+    #[test]
+    fn dos() {
+        let format = round_trip(b"a\r\nb\r\n");
+
+        assert!(format.line_ending == LineEnding::Dos);
+        assert_eq!(detect(b"a\r\nb\r\n").1, "a\nb");
+    }
+
+    // A single line feed makes the file unix, with the carriage returns kept in the text
+    #[test]
+    fn mixed() {
+        let format = round_trip(b"a\r\nb\n");
+
+        assert!(format.line_ending == LineEnding::Unix);
+        assert_eq!(detect(b"a\r\nb\n").1, "a\r\nb");
+    }
+
+    #[test]
+    fn noeol() {
+        let format = round_trip(b"a\nb");
+
+        assert!(!format.eol);
+
+        let format = round_trip(b"a\r\nb");
+
+        assert!(!format.eol);
+        assert!(format.line_ending == LineEnding::Dos);
+    }
+
+    #[test]
+    fn bom() {
+        let format = round_trip(b"\xef\xbb\xbfa\r\n");
+
+        assert!(format.bom);
+        assert!(format.encoding.is_utf8());
+        assert_eq!(detect(b"\xef\xbb\xbfa\r\n").1, "a");
+
+        let format = round_trip(b"\xff\xfea\x00\n\x00");
+
+        assert!(format.bom);
+        assert_eq!(format.encoding.name(), "utf-16le");
+    }
+
+    #[test]
+    fn empty() {
+        let (format, data) = detect(b"");
+
+        assert!(format.eol);
+        assert!(data.is_empty());
+        assert!(format
+            .encode(&Text::from(""))
+            .expect("to encode")
+            .is_empty());
+    }
+
+    // An empty file that has been written to gets a line break at the end like any other
+    #[test]
+    fn empty_then_written() {
+        let (format, _) = detect(b"");
+
+        assert_eq!(format.encode(&Text::from("a")).expect("to encode"), b"a\n");
+    }
+
+    #[test]
+    fn unmappable() {
+        let format = Format {
+            encoding: Encoding::parse("latin1").expect("a known encoding"),
+            ..Format::default()
+        };
+
+        assert!(format.encode(&Text::from("☃")).is_err());
+    }
+}
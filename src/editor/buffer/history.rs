@@ -0,0 +1,358 @@
+pub mod file;
+
+use crate::editor::Position;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Clone)]
+pub enum Edit {
+    Insert { index: usize, text: String },
+    Remove { index: usize, text: String },
+}
+
+impl Edit {
+    pub fn invert(&self) -> Self {
+        match self {
+            Self::Insert { index, text } => Self::Remove {
+                index: *index,
+                text: text.clone(),
+            },
+            Self::Remove { index, text } => Self::Insert {
+                index: *index,
+                text: text.clone(),
+            },
+        }
+    }
+}
+
+// How far to travel through the history with :earlier and :later
+pub enum Travel {
+    Steps(usize),
+    Time(Duration),
+}
+
+// A group of edits that is undone and redone as one unit, together with where the cursor was when
+// the first edit was made
+pub struct Revision {
+    parent: usize,
+    // The child that was most recently created or undone, which is the one redo follows
+    last_child: Option<usize>,
+    edits: Vec<Edit>,
+    cursor: Position,
+    // Seconds since the unix epoch
+    time: u64,
+}
+
+// An undo tree, the first revision is the root and represents the text as it was loaded. Undoing
+// and then making a new change creates a new branch instead of throwing the undone changes away
+//
+// Revisions are only ever appended, so their index doubles as their chronological order
+pub struct History {
+    revisions: Vec<Revision>,
+    current: usize,
+    pending: Vec<Edit>,
+    pending_cursor: Position,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+impl History {
+    // The revision the text is at, which together with the pending edits tells whether the text
+    // differs from how it was at some earlier point
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    pub fn record(&mut self, edit: Edit, cursor: Position) {
+        if self.pending.is_empty() {
+            self.pending_cursor = cursor;
+        }
+
+        self.pending.push(edit);
+    }
+
+    // Closes the pending group of edits, turning it into a new revision
+    pub fn commit(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let index = self.revisions.len();
+
+        self.revisions.push(Revision {
+            parent: self.current,
+            last_child: None,
+            edits: std::mem::take(&mut self.pending),
+            cursor: self.pending_cursor,
+            time: now(),
+        });
+
+        self.revisions[self.current].last_child = Some(index);
+        self.current = index;
+    }
+
+    // Returns the edits needed to go back to the parent revision, in the order they should be
+    // applied, and where the cursor should be placed
+    pub fn undo(&mut self) -> Option<(Vec<Edit>, Position)> {
+        self.commit();
+
+        if self.current == 0 {
+            return None;
+        }
+
+        let revision = &self.revisions[self.current];
+
+        let edits = revision.edits.iter().rev().map(Edit::invert).collect();
+        let cursor = revision.cursor;
+
+        let parent = revision.parent;
+        self.revisions[parent].last_child = Some(self.current);
+        self.current = parent;
+
+        Some((edits, cursor))
+    }
+
+    pub fn redo(&mut self) -> Option<(Vec<Edit>, Position)> {
+        self.commit();
+
+        let child = self.revisions[self.current].last_child?;
+        let revision = &self.revisions[child];
+
+        let edits = revision.edits.clone();
+        let cursor = revision.cursor;
+
+        self.current = child;
+
+        Some((edits, cursor))
+    }
+
+    pub fn earlier(&mut self, travel: Travel) -> Option<(Vec<Edit>, Position)> {
+        self.commit();
+
+        let target = match travel {
+            Travel::Steps(steps) => self.current.saturating_sub(steps),
+            Travel::Time(duration) => {
+                let time = self.revisions[self.current]
+                    .time
+                    .saturating_sub(duration.as_secs());
+
+                self.last_before(time)
+            }
+        };
+
+        self.goto(target)
+    }
+
+    pub fn later(&mut self, travel: Travel) -> Option<(Vec<Edit>, Position)> {
+        self.commit();
+
+        let target = match travel {
+            Travel::Steps(steps) => self
+                .current
+                .saturating_add(steps)
+                .min(self.revisions.len() - 1),
+            Travel::Time(duration) => {
+                let time = self.revisions[self.current]
+                    .time
+                    .saturating_add(duration.as_secs());
+
+                self.last_before(time).max(self.current)
+            }
+        };
+
+        self.goto(target)
+    }
+
+    // The newest revision that was made at or before the given time
+    fn last_before(&self, time: u64) -> usize {
+        self.revisions
+            .iter()
+            .rposition(|revision| revision.time <= time)
+            .unwrap_or(0)
+    }
+
+    fn ancestors(&self, mut index: usize) -> Vec<usize> {
+        let mut ancestors = vec![index];
+
+        while index != 0 {
+            index = self.revisions[index].parent;
+            ancestors.push(index);
+        }
+
+        ancestors
+    }
+
+    // Moves to any revision in the tree by undoing up to the closest common ancestor, and then
+    // redoing down the branch the target is on
+    fn goto(&mut self, target: usize) -> Option<(Vec<Edit>, Position)> {
+        if target == self.current {
+            return None;
+        }
+
+        let path = self.ancestors(target);
+
+        let mut edits = Vec::new();
+        let mut cursor = self.revisions[self.current].cursor;
+
+        while !path.contains(&self.current) {
+            let (undo, position) = self.undo()?;
+
+            edits.extend(undo);
+            cursor = position;
+        }
+
+        let common = path.iter().position(|index| *index == self.current)?;
+
+        for index in path[..common].iter().rev() {
+            let revision = &self.revisions[*index];
+
+            edits.extend(revision.edits.iter().cloned());
+            cursor = revision.cursor;
+
+            let parent = revision.parent;
+            self.revisions[parent].last_child = Some(*index);
+            self.current = *index;
+        }
+
+        Some((edits, cursor))
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        let root = Revision {
+            parent: 0,
+            last_child: None,
+            edits: Vec::new(),
+            cursor: Position::default(),
+            time: now(),
+        };
+
+        Self {
+            revisions: vec![root],
+            current: 0,
+            pending: Vec::new(),
+            pending_cursor: Position::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(index: usize, text: &str) -> Edit {
+        Edit::Insert {
+            index,
+            text: text.to_string(),
+        }
+    }
+
+    fn at(x: usize, y: usize) -> Position {
+        Position { x, y }
+    }
+
+    // The edits as "+index:text" and "-index:text", so that they can be compared
+    fn describe(edits: &[Edit]) -> Vec<String> {
+        edits
+            .iter()
+            .map(|edit| match edit {
+                Edit::Insert { index, text } => format!("+{index}:{text}"),
+                Edit::Remove { index, text } => format!("-{index}:{text}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn undo_with_nothing_recorded() {
+        let mut history = History::default();
+
+        assert!(history.undo().is_none());
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn pending_edits_are_undone_together_in_reverse() {
+        let mut history = History::default();
+
+        history.record(insert(0, "a"), at(0, 0));
+        history.record(insert(1, "b"), at(1, 0));
+
+        let (edits, cursor) = history.undo().unwrap();
+
+        assert_eq!(describe(&edits), ["-1:b", "-0:a"]);
+        assert_eq!((cursor.x, cursor.y), (0, 0));
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn commits_make_separate_revisions() {
+        let mut history = History::default();
+
+        history.record(insert(0, "a"), at(0, 0));
+        history.commit();
+        history.commit();
+        history.record(insert(1, "b"), at(1, 0));
+        history.commit();
+
+        assert_eq!(describe(&history.undo().unwrap().0), ["-1:b"]);
+        assert_eq!(describe(&history.undo().unwrap().0), ["-0:a"]);
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn redo_replays_the_undone_revision() {
+        let mut history = History::default();
+
+        history.record(insert(0, "a"), at(0, 0));
+        history.record(insert(1, "b"), at(1, 0));
+        history.commit();
+
+        history.undo();
+
+        let (edits, cursor) = history.redo().unwrap();
+
+        assert_eq!(describe(&edits), ["+0:a", "+1:b"]);
+        assert_eq!((cursor.x, cursor.y), (0, 0));
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn new_changes_after_undo_start_a_branch() {
+        let mut history = History::default();
+
+        history.record(insert(0, "a"), at(0, 0));
+        history.commit();
+        history.undo();
+
+        history.record(insert(0, "b"), at(0, 0));
+        history.commit();
+
+        // Both branches hang off the root, so undoing the new one leaves nothing else to undo
+        assert_eq!(describe(&history.undo().unwrap().0), ["-0:b"]);
+        assert!(history.undo().is_none());
+    }
+
+    #[test]
+    fn redo_follows_the_last_undone_branch() {
+        let mut history = History::default();
+
+        history.record(insert(0, "a"), at(0, 0));
+        history.commit();
+        history.undo();
+
+        history.record(insert(0, "b"), at(0, 0));
+        history.commit();
+        history.undo();
+
+        assert_eq!(describe(&history.redo().unwrap().0), ["+0:b"]);
+    }
+}
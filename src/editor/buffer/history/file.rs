@@ -0,0 +1,352 @@
+use super::{Edit, History, Revision};
+
+use crate::editor::Position;
+use crate::system::{dirs, file};
+
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+const UNDO_DIRECTORY: &str = "undo";
+const HEADER: &str = "alice-undo 1";
+
+const INVALID_UNDO_FILE: &str = "Invalid undo file";
+
+// Nothing else ever removes undo files, so the ones that haven't been written in this long are
+// taken to belong to files that are gone or forgotten
+const MAX_AGE: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+// Undo files are named after the full path of the file they belong to
+fn undo_file(path: &Path) -> Result<PathBuf> {
+    Ok(dirs::state_subdirectory(UNDO_DIRECTORY)?.join(dirs::state_name(path)))
+}
+
+// Removes the undo files that are older than MAX_AGE
+pub fn prune() {
+    let Ok(directory) = dirs::state_subdirectory(UNDO_DIRECTORY) else {
+        return;
+    };
+
+    let Ok(entries) = fs::read_dir(directory) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let old = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > MAX_AGE);
+
+        if old {
+            _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+fn invalid() -> Error {
+    Error::new(ErrorKind::InvalidData, INVALID_UNDO_FILE)
+}
+
+// The format is line based, except for the text of an edit, which is prefixed by its length in
+// bytes so that it can contain line breaks:
+//
+// alice-undo 1
+// <content hash> <current revision> <revision count>
+// <parent> <last child or -> <time> <cursor y> <cursor x> <edit count>
+// <i or r> <index> <length>
+// <text>
+//
+// The file is replaced as a whole, so that a crash while writing it doesn't lose the history that
+// was there before
+pub fn write(history: &History, path: &Path, hash: u64) -> Result<()> {
+    file::replace(&undo_file(path)?, |file| {
+        write_to(history, hash, file).map(|()| 0)
+    })?;
+
+    Ok(())
+}
+
+fn write_to(history: &History, hash: u64, file: &mut dyn Write) -> Result<()> {
+    writeln!(file, "{HEADER}")?;
+    writeln!(
+        file,
+        "{hash:016x} {} {}",
+        history.current,
+        history.revisions.len()
+    )?;
+
+    for revision in &history.revisions {
+        let last_child = revision
+            .last_child
+            .map_or(String::from("-"), |child| child.to_string());
+
+        writeln!(
+            file,
+            "{} {last_child} {} {} {} {}",
+            revision.parent,
+            revision.time,
+            revision.cursor.y,
+            revision.cursor.x,
+            revision.edits.len()
+        )?;
+
+        for edit in &revision.edits {
+            let (kind, index, text) = match edit {
+                Edit::Insert { index, text } => ('i', index, text),
+                Edit::Remove { index, text } => ('r', index, text),
+            };
+
+            writeln!(file, "{kind} {index} {}", text.len())?;
+            writeln!(file, "{text}")?;
+        }
+    }
+
+    Ok(())
+}
+
+struct Reader<'a> {
+    data: &'a str,
+}
+
+impl<'a> Reader<'a> {
+    fn line(&mut self) -> Result<&'a str> {
+        let (line, rest) = self.data.split_once('\n').ok_or_else(invalid)?;
+        self.data = rest;
+
+        Ok(line)
+    }
+
+    fn fields<const N: usize>(&mut self) -> Result<[&'a str; N]> {
+        let mut fields = self.line()?.split(' ');
+        let mut result = [""; N];
+
+        for field in &mut result {
+            *field = fields.next().ok_or_else(invalid)?;
+        }
+
+        Ok(result)
+    }
+
+    fn text(&mut self, length: usize) -> Result<&'a str> {
+        let text = self.data.get(..length).ok_or_else(invalid)?;
+        self.data = self.data[length..].strip_prefix('\n').ok_or_else(invalid)?;
+
+        Ok(text)
+    }
+}
+
+fn number<T: std::str::FromStr>(field: &str) -> Result<T> {
+    field.parse().map_err(|_| invalid())
+}
+
+// Returns None when there is no undo file, or when it was written for different content than what
+// is in the file now, in which case the history would no longer apply
+pub fn read(path: &Path, hash: u64) -> Option<History> {
+    let data = fs::read_to_string(undo_file(path).ok()?).ok()?;
+
+    read_from(&data, hash)
+}
+
+fn read_from(data: &str, hash: u64) -> Option<History> {
+    let mut reader = Reader { data };
+
+    if reader.line().ok()? != HEADER {
+        return None;
+    }
+
+    let [stored, current, count] = reader.fields().ok()?;
+
+    if u64::from_str_radix(stored, 16).ok()? != hash {
+        return None;
+    }
+
+    parse(&mut reader, number(current).ok()?, number(count).ok()?).ok()
+}
+
+fn parse(reader: &mut Reader, current: usize, count: usize) -> Result<History> {
+    let mut revisions = Vec::new();
+
+    for _ in 0..count {
+        let [parent, last_child, time, y, x, edits] = reader.fields()?;
+
+        let last_child = match last_child {
+            "-" => None,
+            child => Some(number(child)?),
+        };
+
+        let mut revision = Revision {
+            parent: number(parent)?,
+            last_child,
+            edits: Vec::new(),
+            cursor: Position {
+                x: number(x)?,
+                y: number(y)?,
+            },
+            time: number(time)?,
+        };
+
+        for _ in 0..number(edits)? {
+            let [kind, index, length] = reader.fields()?;
+
+            let index = number(index)?;
+            let text = reader.text(number(length)?)?.to_string();
+
+            revision.edits.push(match kind {
+                "i" => Edit::Insert { index, text },
+                "r" => Edit::Remove { index, text },
+                _ => return Err(invalid()),
+            });
+        }
+
+        revisions.push(revision);
+    }
+
+    let valid = |index: usize| index < revisions.len();
+
+    // Every parent has to come before its child, otherwise walking up the tree might never end
+    let broken = revisions.iter().enumerate().any(|(index, revision)| {
+        (index > 0 && revision.parent >= index)
+            || revision.last_child.is_some_and(|child| !valid(child))
+    });
+
+    if broken || !valid(current) {
+        return Err(invalid());
+    }
+
+    Ok(History {
+        revisions,
+        current,
+        pending: Vec::new(),
+        pending_cursor: Position::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Travel;
+    use super::*;
+
+    const HASH: u64 = 0x0123_4567_89ab_cdef;
+
+    fn position(x: usize, y: usize) -> Position {
+        Position { x, y }
+    }
+
+    fn insert(index: usize, text: &str) -> Edit {
+        Edit::Insert {
+            index,
+            text: text.to_string(),
+        }
+    }
+
+    // Two branches off the root, one of which has a line break and a multibyte character in it,
+    // with the second one undone
+    fn history() -> History {
+        let mut history = History::default();
+
+        history.record(insert(0, "first\nlines"), position(0, 0));
+        history.record(insert(11, "é"), position(5, 1));
+        history.commit();
+
+        history.undo();
+
+        history.record(insert(0, "other"), position(0, 0));
+        history.commit();
+
+        history.record(
+            Edit::Remove {
+                index: 0,
+                text: String::from("o"),
+            },
+            position(0, 0),
+        );
+        history.commit();
+
+        history.undo();
+
+        history
+    }
+
+    fn write(history: &History) -> String {
+        let mut data = Vec::new();
+        write_to(history, HASH, &mut data).expect("to write");
+
+        String::from_utf8(data).expect("undo files to be UTF-8")
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = write(&history());
+        let read = read_from(&data, HASH).expect("to read");
+
+        assert_eq!(write(&read), data);
+    }
+
+    #[test]
+    fn read_history_still_works() {
+        let data = write(&history());
+        let mut read = read_from(&data, HASH).expect("to read");
+
+        assert_eq!(read.current, 2);
+        assert!(read.pending.is_empty());
+
+        let (edits, cursor) = read.redo().expect("the undone revision");
+
+        assert!(matches!(&edits[..], [Edit::Remove { index: 0, text }] if text == "o"));
+        assert_eq!((cursor.x, cursor.y), (0, 0));
+
+        read.undo();
+        read.undo();
+
+        // Revisions are numbered in the order they were made, so this is the first branch
+        let (edits, cursor) = read.later(Travel::Steps(1)).expect("the first branch");
+
+        assert!(matches!(&edits[..], [Edit::Insert { text, .. }, _] if text == "first\nlines"));
+        assert_eq!((cursor.x, cursor.y), (0, 0));
+    }
+
+    #[test]
+    fn stale_hash() {
+        let data = write(&history());
+
+        assert!(read_from(&data, HASH + 1).is_none());
+    }
+
+    #[test]
+    fn wrong_header() {
+        let data = write(&history()).replacen(HEADER, "alice-undo 2", 1);
+
+        assert!(read_from(&data, HASH).is_none());
+    }
+
+    #[test]
+    fn truncated() {
+        let data = write(&history());
+
+        for length in 0..data.len() - 1 {
+            if data.is_char_boundary(length) {
+                assert!(read_from(&data[..length], HASH).is_none(), "{length}");
+            }
+        }
+    }
+
+    // A parent that comes after its child would make undoing loop forever
+    #[test]
+    fn broken_tree() {
+        let data = format!("{HEADER}\n{HASH:016x} 1 2\n0 1 0 0 0 0\n1 - 0 0 0 0\n");
+
+        assert!(read_from(&data, HASH).is_none());
+    }
+
+    #[test]
+    fn current_out_of_range() {
+        let data = format!("{HEADER}\n{HASH:016x} 1 1\n0 - 0 0 0 0\n");
+
+        assert!(read_from(&data, HASH).is_none());
+    }
+}
@@ -1,12 +1,47 @@
-use crate::escape::CSI;
+use super::encoding::escaped;
 
-use std::fmt::Display;
+use crate::highlight::Cell;
 
+use std::{
+    borrow::Cow,
+    fmt::{Display, Write},
+};
+
+use ropey::RopeSlice;
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// How a grapheme is drawn and the amount of columns it takes up. Tabs are drawn as a single space,
+// control characters like ^M and bytes that aren't valid in the encoding of the file like <ff>
+fn display(grapheme: &str) -> (Cow<'_, str>, usize) {
+    let special = |character: char| character.is_control() || escaped(character).is_some();
+
+    if !grapheme.chars().any(special) {
+        return (Cow::Borrowed(grapheme), grapheme.width());
+    }
+
+    let mut text = String::new();
+
+    for character in grapheme.chars() {
+        match (character, escaped(character)) {
+            ('\t', _) => text.push(' '),
+            (_, Some(byte)) => _ = write!(text, "<{byte:02x}>"),
+            _ if character.is_ascii_control() => {
+                text.push('^');
+                text.push(char::from(character as u8 ^ 0x40));
+            }
+            _ if character.is_control() => _ = write!(text, "<{:02x}>", u32::from(character)),
+            _ => text.push(character),
+        }
+    }
+
+    let width = text.width();
+
+    (Cow::Owned(text), width)
+}
 
 #[derive(Default)]
 pub struct Line {
-    pub highlights: Option<Vec<CSI>>,
     data: String,
     length: usize,
 }
@@ -15,70 +50,122 @@ impl Line {
     pub fn new(data: String) -> Self {
         let length = data.graphemes(true).count();
 
-        Self {
-            data,
-            length,
-            highlights: None,
-        }
+        Self { data, length }
     }
 
-    pub fn render(&self, start: usize, end: usize) -> String {
-        let end = end.min(self.data.len());
-        let start = start.min(end);
-
-        self.data[start..end].replace('\t', " ")
+    // The column a grapheme starts at on the screen, counting from the start of the line.
+    // Positions past the end of the line take up a column each, like the cursor in insert mode
+    pub fn column(&self, index: usize) -> usize {
+        let (columns, count) = self
+            .data
+            .graphemes(true)
+            .take(index)
+            .fold((0, 0), |(columns, count), grapheme| {
+                (columns + display(grapheme).1, count + 1)
+            });
+
+        columns + (index - count)
     }
 
-    pub fn len(&self) -> usize {
-        self.length
+    // The index of the grapheme that starts at a column, or the first one after it when the column
+    // is in the middle of a wide grapheme. Past the end of the line every column is a position of
+    // its own, the same way as in column
+    pub fn index(&self, column: usize) -> usize {
+        let mut start = 0;
+
+        for (index, grapheme) in self.data.graphemes(true).enumerate() {
+            if start >= column {
+                return index;
+            }
+
+            start += display(grapheme).1;
+        }
+
+        self.length + column.saturating_sub(start)
     }
 
-    pub fn update(&mut self) {
-        let length = self.data.graphemes(true).count();
-        self.length = length;
+    // The graphemes that take up any of the columns from the start up to the end, given as a
+    // range of indices, which is empty at the end of the line when it doesn't reach the columns
+    pub fn span(&self, start: usize, end: usize) -> (usize, usize) {
+        let mut column = 0;
+        let mut span = None;
+
+        for (index, grapheme) in self.data.graphemes(true).enumerate() {
+            if column >= end {
+                break;
+            }
+
+            let next = column + display(grapheme).1;
+
+            if next > start {
+                let (from, _) = span.unwrap_or((index, index));
+                span = Some((from, index + 1));
+            }
+
+            column = next;
+        }
+
+        span.unwrap_or((self.length, self.length))
     }
 
-    pub fn append(&mut self, data: &str) {
-        self.data.push_str(data);
-        self.update();
+    // The amount of columns the grapheme at an index takes up, which is at least one so that the
+    // cursor always has somewhere to be
+    pub fn width(&self, index: usize) -> usize {
+        self.data
+            .graphemes(true)
+            .nth(index)
+            .map_or(1, |grapheme| display(grapheme).1.max(1))
     }
 
-    pub fn split(&mut self, x: usize) -> Self {
-        let start: String = self.data.graphemes(true).take(x).collect();
-        let end: String = self.data.graphemes(true).skip(x).collect();
+    // The graphemes that are visible between two columns, a wide grapheme that only partly fits
+    // is drawn as spaces
+    pub fn cells(&self, start: usize, end: usize) -> Vec<Cell> {
+        let mut cells = Vec::new();
+        let mut column = 0;
 
-        self.data = start;
-        self.update();
+        for (index, (byte, grapheme)) in self.data.grapheme_indices(true).enumerate() {
+            if column >= end {
+                break;
+            }
 
-        Self::from(end.as_str())
-    }
+            let (text, width) = display(grapheme);
+            let next = column + width;
 
-    pub fn insert(&mut self, x: usize, character: char) {
-        let start: String = self.data.graphemes(true).take(x).collect();
-        let end: String = self.data.graphemes(true).skip(x).collect();
+            if column >= start || next > start {
+                let text = if column < start || next > end {
+                    " ".repeat(next.min(end) - column.max(start))
+                } else {
+                    text.into_owned()
+                };
 
-        let new = format!("{start}{character}{end}");
+                cells.push(Cell { text, byte, index });
+            }
 
-        self.data = new;
-        self.update();
-    }
+            column = next;
+        }
 
-    pub fn delete(&mut self, x: usize) {
-        let start: String = self.data.graphemes(true).take(x).collect();
-        let end: String = self.data.graphemes(true).skip(x + 1).collect();
+        cells
+    }
 
-        self.data = format!("{start}{end}");
-        self.update();
+    pub fn len(&self) -> usize {
+        self.length
     }
 
-    pub fn as_bytes(&self) -> &[u8] {
-        self.data.as_bytes()
+    pub fn as_str(&self) -> &str {
+        &self.data
     }
 }
 
-impl From<&str> for Line {
-    fn from(data: &str) -> Self {
-        Self::new(data.to_owned())
+impl From<RopeSlice<'_>> for Line {
+    fn from(slice: RopeSlice<'_>) -> Self {
+        let mut data = String::from(slice);
+
+        // Lines in the rope keep their line break, which isn't part of the line itself
+        if data.ends_with('\n') {
+            data.pop();
+        }
+
+        Self::new(data)
     }
 }
 
@@ -87,3 +174,41 @@ impl Display for Line {
         write!(f, "{}", self.data)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_graphemes_take_two_columns() {
+        let line = Line::new(String::from("a漢b"));
+
+        assert_eq!(line.column(2), 3);
+        assert_eq!(line.index(3), 2);
+        assert_eq!(line.index(2), 2);
+    }
+
+    #[test]
+    fn past_the_end() {
+        let line = Line::new(String::from("a漢"));
+
+        assert_eq!(line.index(5), 4);
+        assert_eq!(line.index(line.column(4)), 4);
+    }
+
+    #[test]
+    fn spans_take_the_graphemes_partly_in_them() {
+        let line = Line::new(String::from("a漢b"));
+
+        assert_eq!(line.span(1, 3), (1, 2));
+        assert_eq!(line.span(2, 4), (1, 3));
+        assert_eq!(line.span(0, 1), (0, 1));
+    }
+
+    #[test]
+    fn spans_past_the_end_are_empty() {
+        let line = Line::new(String::from("ab"));
+
+        assert_eq!(line.span(3, 5), (2, 2));
+    }
+}
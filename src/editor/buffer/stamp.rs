@@ -0,0 +1,95 @@
+use crate::hash::Fnv;
+
+use std::{
+    fs::{self, Metadata},
+    hash::Hasher,
+    path::Path,
+    time::SystemTime,
+};
+
+// What a file looked like when it was last read or written, to tell whether something else has
+// written to it since
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Stamp {
+    modified: Option<SystemTime>,
+    length: u64,
+    hash: u64,
+}
+
+impl Stamp {
+    // The stamp of a file that has just been read or written as the bytes
+    pub fn new(path: &Path, bytes: &[u8]) -> Option<Self> {
+        let metadata = fs::metadata(path).ok()?;
+
+        let mut hasher = Fnv::default();
+        hasher.write(bytes);
+
+        Some(Self {
+            modified: metadata.modified().ok(),
+            length: metadata.len(),
+            hash: hasher.finish(),
+        })
+    }
+
+    // The stamp of the file as it is now, or nothing when it can't be read
+    pub fn read(path: &Path) -> Option<Self> {
+        let bytes = fs::read(path).ok()?;
+
+        Self::new(path, &bytes)
+    }
+
+    // Whether the file may have been written since, going by its size and modification time
+    pub fn is_stale(&self, metadata: &Metadata) -> bool {
+        metadata.len() != self.length || metadata.modified().ok() != self.modified
+    }
+
+    // Whether the contents are different, a file that was only touched is the same file
+    pub fn same_contents(&self, other: &Self) -> bool {
+        self.hash == other.hash && self.length == other.length
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::Duration;
+
+    fn stamp(seconds: u64, bytes: &[u8]) -> Stamp {
+        let mut hasher = Fnv::default();
+        hasher.write(bytes);
+
+        Stamp {
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds)),
+            length: bytes.len() as u64,
+            hash: hasher.finish(),
+        }
+    }
+
+    #[test]
+    fn touched_files_have_the_same_contents() {
+        let before = stamp(1, b"text\n");
+        let after = stamp(2, b"text\n");
+
+        assert!(before != after);
+        assert!(before.same_contents(&after));
+    }
+
+    #[test]
+    fn changed_contents() {
+        let before = stamp(1, b"text\n");
+
+        assert!(!before.same_contents(&stamp(1, b"next\n")));
+        assert!(!before.same_contents(&stamp(1, b"text\n\n")));
+    }
+
+    #[test]
+    fn unknown_modification_times() {
+        let before = Stamp {
+            modified: None,
+            ..stamp(0, b"text\n")
+        };
+
+        assert!(before.same_contents(&stamp(3, b"text\n")));
+    }
+}
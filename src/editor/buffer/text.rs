@@ -0,0 +1,205 @@
+use super::line::Line;
+
+use crate::editor::Position;
+
+use std::{
+    borrow::Cow,
+    io::{Result, Write},
+};
+
+use ropey::{iter::Chunks, Rope, RopeSlice};
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete};
+
+// The storage used by buffers, swapping out the rope only requires implementing TextStorage with
+// another backing structure
+#[derive(Clone, Default)]
+pub struct Text(Rope);
+
+impl From<&str> for Text {
+    fn from(text: &str) -> Self {
+        Self(Rope::from_str(text))
+    }
+}
+
+// Positions are always given as a line index and a grapheme index inside of that line, while the
+// underlying storage is indexed by chars
+pub trait TextStorage {
+    fn len_lines(&self) -> usize;
+    fn line(&self, y: usize) -> Option<Line>;
+    fn line_text(&self, y: usize) -> Option<Cow<'_, str>>;
+    fn line_len(&self, y: usize) -> usize;
+    fn line_to_byte(&self, y: usize) -> usize;
+    fn byte_to_line(&self, byte: usize) -> usize;
+    fn char_to_byte(&self, index: usize) -> usize;
+    fn char_index(&self, position: Position) -> usize;
+    fn insert(&mut self, index: usize, text: &str);
+    fn remove(&mut self, start: usize, end: usize);
+    fn slice(&self, start: usize, end: usize) -> String;
+    fn chunks(&self, start: usize, end: usize) -> impl Iterator<Item = &[u8]>;
+    fn snapshot(&self) -> Self;
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<usize>;
+}
+
+impl TextStorage for Text {
+    fn len_lines(&self) -> usize {
+        self.0.len_lines()
+    }
+
+    fn line(&self, y: usize) -> Option<Line> {
+        self.0.get_line(y).map(Line::from)
+    }
+
+    // The text of a line without its line break, which is only copied when it is split over
+    // several chunks of the rope
+    fn line_text(&self, y: usize) -> Option<Cow<'_, str>> {
+        self.0.get_line(y).map(|line| Cow::from(content(line)))
+    }
+
+    fn line_len(&self, y: usize) -> usize {
+        self.0
+            .get_line(y)
+            .map_or(0, |line| Graphemes::new(content(line)).count())
+    }
+
+    fn line_to_byte(&self, y: usize) -> usize {
+        self.0.line_to_byte(y.min(self.0.len_lines()))
+    }
+
+    fn byte_to_line(&self, byte: usize) -> usize {
+        self.0.byte_to_line(byte.min(self.0.len_bytes()))
+    }
+
+    fn char_to_byte(&self, index: usize) -> usize {
+        self.0.char_to_byte(index.min(self.0.len_chars()))
+    }
+
+    fn char_index(&self, position: Position) -> usize {
+        let y = position.y.min(self.0.len_lines().saturating_sub(1));
+        let start = self.0.line_to_char(y);
+
+        let Some(line) = self.0.get_line(y).map(content) else {
+            return start;
+        };
+
+        // Positions past the end of the line end up at its end
+        let byte = if position.x == 0 {
+            0
+        } else {
+            Graphemes::new(line)
+                .take(position.x)
+                .last()
+                .unwrap_or_default()
+        };
+
+        start + line.byte_to_char(byte)
+    }
+
+    fn insert(&mut self, index: usize, text: &str) {
+        self.0.insert(index.min(self.0.len_chars()), text);
+    }
+
+    fn remove(&mut self, start: usize, end: usize) {
+        let end = end.min(self.0.len_chars());
+        let start = start.min(end);
+
+        self.0.remove(start..end);
+    }
+
+    fn slice(&self, start: usize, end: usize) -> String {
+        let end = end.min(self.0.len_chars());
+        let start = start.min(end);
+
+        self.0.slice(start..end).to_string()
+    }
+
+    // The bytes between two byte offsets as they are stored, which don't have to be on the
+    // boundaries of chars
+    fn chunks(&self, start: usize, end: usize) -> impl Iterator<Item = &[u8]> {
+        let end = end.min(self.0.len_bytes());
+        let start = start.min(end);
+
+        let (chunks, first, _, _) = self.0.chunks_at_byte(start);
+
+        chunks.scan(first, move |offset, chunk| {
+            let from = *offset;
+            *offset += chunk.len();
+
+            let bytes = chunk.as_bytes();
+            (from < end).then(|| &bytes[start.saturating_sub(from)..(end - from).min(bytes.len())])
+        })
+    }
+
+    // Ropes share their nodes between clones, so this is cheap regardless of the file size
+    fn snapshot(&self) -> Self {
+        self.clone()
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> Result<usize> {
+        let mut written = 0;
+
+        for chunk in self.0.chunks() {
+            writer.write_all(chunk.as_bytes())?;
+            written += chunk.len();
+        }
+
+        Ok(written)
+    }
+}
+
+// A line without its line break
+fn content(line: RopeSlice<'_>) -> RopeSlice<'_> {
+    let length = line.len_chars();
+
+    if length > 0 && line.char(length - 1) == '\n' {
+        line.slice(..length - 1)
+    } else {
+        line
+    }
+}
+
+// The byte offsets the graphemes of a slice end at, found by going over the chunks of the rope so
+// that long lines never have to be copied out of it
+struct Graphemes<'a> {
+    slice: RopeSlice<'a>,
+    chunks: Chunks<'a>,
+    chunk: &'a str,
+    chunk_start: usize,
+    cursor: GraphemeCursor,
+}
+
+impl<'a> Graphemes<'a> {
+    fn new(slice: RopeSlice<'a>) -> Self {
+        let mut chunks = slice.chunks();
+        let chunk = chunks.next().unwrap_or_default();
+
+        Self {
+            slice,
+            chunks,
+            chunk,
+            chunk_start: 0,
+            cursor: GraphemeCursor::new(0, slice.len_bytes(), true),
+        }
+    }
+}
+
+impl Iterator for Graphemes<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            match self.cursor.next_boundary(self.chunk, self.chunk_start) {
+                Ok(boundary) => return boundary,
+                Err(GraphemeIncomplete::NextChunk) => {
+                    self.chunk_start += self.chunk.len();
+                    self.chunk = self.chunks.next().unwrap_or_default();
+                }
+                // Some graphemes can only be told apart by looking at what comes before them
+                Err(GraphemeIncomplete::PreContext(index)) => {
+                    let (chunk, start, _, _) = self.slice.chunk_at_byte(index.saturating_sub(1));
+                    self.cursor.provide_context(chunk, start);
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use super::buffer::Buffer;
+
+use std::path::Path;
+
+// The buffers that are open but not being shown, the one being shown is owned by the editor
+// itself. Buffers are numbered in the order they were opened and keep their number until they are
+// deleted, like in vim
+#[derive(Default)]
+pub struct Buffers {
+    // Kept sorted by number
+    hidden: Vec<Buffer>,
+    last_number: usize,
+    // The buffer that was shown before the current one, which Ctrl-^ switches to
+    pub alternate: Option<usize>,
+}
+
+impl Buffers {
+    pub fn next_number(&mut self) -> usize {
+        self.last_number += 1;
+        self.last_number
+    }
+
+    pub fn hide(&mut self, buffer: Buffer) {
+        let index = self
+            .hidden
+            .partition_point(|hidden| hidden.number < buffer.number);
+
+        self.hidden.insert(index, buffer);
+    }
+
+    pub fn get(&self, number: usize) -> Option<&Buffer> {
+        self.hidden.iter().find(|buffer| buffer.number == number)
+    }
+
+    pub fn get_mut(&mut self, number: usize) -> Option<&mut Buffer> {
+        self.hidden
+            .iter_mut()
+            .find(|buffer| buffer.number == number)
+    }
+
+    pub fn take(&mut self, number: usize) -> Option<Buffer> {
+        let index = self
+            .hidden
+            .iter()
+            .position(|buffer| buffer.number == number)?;
+
+        Some(self.hidden.remove(index))
+    }
+
+    // Every buffer including the current one, in the order of their numbers
+    pub fn all<'a>(&'a self, current: &'a Buffer) -> Vec<&'a Buffer> {
+        let mut all: Vec<&Buffer> = self.hidden.iter().collect();
+
+        let index = all.partition_point(|buffer| buffer.number < current.number);
+        all.insert(index, current);
+
+        all
+    }
+
+    pub fn find(&self, path: &Path) -> Option<usize> {
+        self.hidden
+            .iter()
+            .find(|buffer| buffer.path() == path)
+            .map(|buffer| buffer.number)
+    }
+
+    // Finds a buffer by its number or by part of its path, which has to match only one buffer
+    pub fn lookup(&self, current: &Buffer, name: &str) -> Result<usize, String> {
+        let all = self.all(current);
+
+        if let Ok(number) = name.parse() {
+            return all
+                .iter()
+                .any(|buffer| buffer.number == number)
+                .then_some(number)
+                .ok_or_else(|| format!("Buffer {number} does not exist"));
+        }
+
+        let matches: Vec<usize> = all
+            .iter()
+            .filter(|buffer| buffer.display_path().contains(name))
+            .map(|buffer| buffer.number)
+            .collect();
+
+        match matches[..] {
+            [number] => Ok(number),
+            [] => Err(format!("No matching buffer for {name}")),
+            _ => Err(format!("More than one match for {name}")),
+        }
+    }
+
+    // The hidden buffer a count of steps away from the current one, wrapping around the list
+    pub fn step(&self, current: usize, steps: usize, forward: bool) -> Option<usize> {
+        let hidden: Vec<usize> = self.hidden.iter().map(|buffer| buffer.number).collect();
+
+        step(&hidden, current, steps, forward)
+    }
+
+    pub fn modified(&self) -> Option<&Buffer> {
+        self.hidden.iter().find(|buffer| buffer.is_modified())
+    }
+}
+
+// Steps through the sorted numbers of the hidden buffers, with the current buffer in between them
+fn step(hidden: &[usize], current: usize, steps: usize, forward: bool) -> Option<usize> {
+    if hidden.is_empty() {
+        return None;
+    }
+
+    let count = hidden.len() + 1;
+
+    // Where the current buffer would be in the list if it was hidden
+    let position = hidden.partition_point(|number| *number < current);
+
+    let target = if forward {
+        (position + steps) % count
+    } else {
+        (position + count - steps % count) % count
+    };
+
+    // The current buffer sits between the hidden ones, so it shifts the ones after it
+    match target.cmp(&position) {
+        std::cmp::Ordering::Less => Some(hidden[target]),
+        std::cmp::Ordering::Equal => Some(current),
+        std::cmp::Ordering::Greater => Some(hidden[target - 1]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_hidden() {
+        assert_eq!(step(&[], 1, 1, true), None);
+    }
+
+    #[test]
+    fn forward_wraps_around() {
+        let hidden = [1, 3, 4];
+
+        assert_eq!(step(&hidden, 2, 1, true), Some(3));
+        assert_eq!(step(&hidden, 2, 2, true), Some(4));
+        assert_eq!(step(&hidden, 2, 3, true), Some(1));
+        assert_eq!(step(&hidden, 5, 1, true), Some(1));
+    }
+
+    #[test]
+    fn backward_wraps_around() {
+        let hidden = [1, 3, 4];
+
+        assert_eq!(step(&hidden, 2, 1, false), Some(1));
+        assert_eq!(step(&hidden, 2, 2, false), Some(4));
+        assert_eq!(step(&[2, 3], 1, 1, false), Some(3));
+    }
+
+    #[test]
+    fn a_full_circle_ends_on_the_current_buffer() {
+        let hidden = [1, 3];
+
+        assert_eq!(step(&hidden, 2, 3, true), Some(2));
+        assert_eq!(step(&hidden, 2, 3, false), Some(2));
+        assert_eq!(step(&hidden, 2, 7, true), Some(3));
+    }
+}
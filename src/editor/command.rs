@@ -1,31 +1,182 @@
+pub mod buffers;
+pub mod changed;
+pub mod complete;
+pub mod files;
+pub mod history;
+pub mod parse;
+pub mod recovery;
+pub mod registry;
+pub mod substitute;
+pub mod tabs;
+pub mod windows;
+
 use super::Key;
 
+use complete::Wildmenu;
+
 use std::fmt::Display;
 
+// The line typed at the command or search prompt, along with the position of the cursor in it
 #[derive(Default)]
-pub struct Command(pub Vec<Key>);
+pub struct Command {
+    keys: Vec<Key>,
+    cursor: usize,
+    pub wildmenu: Option<Wildmenu>,
+}
+
+fn is_word(key: &Key) -> bool {
+    matches!(key, Key::Char(character) if character.is_alphanumeric() || *character == '_')
+}
 
 impl Command {
     pub fn clear(&mut self) {
-        self.0.clear();
+        self.keys.clear();
+        self.cursor = 0;
+        self.wildmenu = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    // Replaces the whole line, like when going through the history
+    pub fn set(&mut self, line: &str) {
+        self.keys = line.chars().map(Key::Char).collect();
+        self.cursor = self.keys.len();
     }
 
     pub fn insert(&mut self, key: Key) {
-        self.0.push(key);
+        self.keys.insert(self.cursor, key);
+        self.cursor += 1;
     }
 
+    // Removes the key before the cursor
     pub fn delete(&mut self) {
-        self.0.pop();
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.keys.remove(self.cursor);
+        }
+    }
+
+    // Removes the key under the cursor
+    pub fn delete_forward(&mut self) {
+        if self.cursor < self.keys.len() {
+            self.keys.remove(self.cursor);
+        }
     }
 
-    pub fn keys(&self) -> &[Key] {
-        &self.0[..]
+    // Removes the word before the cursor along with the blanks after it, like Ctrl-w in vim
+    pub fn delete_word(&mut self) {
+        let mut start = self.cursor;
+
+        while start > 0 && self.keys[start - 1] == Key::Char(' ') {
+            start -= 1;
+        }
+
+        if start > 0 && is_word(&self.keys[start - 1]) {
+            while start > 0 && is_word(&self.keys[start - 1]) {
+                start -= 1;
+            }
+        } else {
+            start = start.saturating_sub(1);
+        }
+
+        self.keys.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    pub fn delete_to_start(&mut self) {
+        self.keys.drain(..self.cursor);
+        self.cursor = 0;
+    }
+
+    pub fn left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.keys.len());
+    }
+
+    pub fn start(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn end(&mut self) {
+        self.cursor = self.keys.len();
+    }
+
+    pub fn before_cursor(&self) -> String {
+        self.keys[..self.cursor]
+            .iter()
+            .map(Key::to_string)
+            .collect()
+    }
+
+    // The column the cursor is drawn at, counting from the start of the typed text
+    pub fn cursor_column(&self) -> usize {
+        self.before_cursor().chars().count()
+    }
+
+    fn replace(&mut self, start: usize, text: &str) {
+        let keys: Vec<Key> = text.chars().map(Key::Char).collect();
+        let length = keys.len();
+
+        self.keys.splice(start..self.cursor, keys);
+        self.cursor = start + length;
+    }
+
+    // Completes the word before the cursor, when there are several candidates they are cycled
+    // through on every call
+    pub fn complete(&mut self, reverse: bool, buffers: &[String]) {
+        if let Some(mut wildmenu) = self.wildmenu.take() {
+            let count = wildmenu.candidates.len();
+
+            wildmenu.selected = if reverse {
+                (wildmenu.selected + count - 1) % count
+            } else {
+                (wildmenu.selected + 1) % count
+            };
+
+            self.replace(wildmenu.start, &wildmenu.candidates[wildmenu.selected]);
+            self.wildmenu = Some(wildmenu);
+
+            return;
+        }
+
+        let Some((start, candidates)) = complete::candidates(&self.before_cursor(), buffers) else {
+            return;
+        };
+
+        let selected = if reverse {
+            candidates.len().saturating_sub(1)
+        } else {
+            0
+        };
+
+        let Some(candidate) = candidates.get(selected) else {
+            return;
+        };
+
+        self.replace(start, &candidate.clone());
+
+        if candidates.len() > 1 {
+            self.wildmenu = Some(Wildmenu {
+                candidates,
+                selected,
+                start,
+            });
+        }
     }
 }
 
 impl Display for Command {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let keys = self.0.iter().map(|key| key.to_string()).collect::<String>();
+        let keys = self
+            .keys
+            .iter()
+            .map(|key| key.to_string())
+            .collect::<String>();
 
         write!(f, "{keys}")
     }
@@ -0,0 +1,133 @@
+use super::parse::ExCommand;
+use crate::editor::{status::message::Message, Editor};
+
+impl Editor {
+    pub fn next_buffer(&mut self, command: &ExCommand, forward: bool) -> Option<Message> {
+        let count = command.count.unwrap_or(1);
+
+        if let Some(number) = self.buffers.step(self.buffer.number, count, forward) {
+            self.switch_buffer(number);
+        }
+
+        None
+    }
+
+    pub fn buffer(&mut self, command: &ExCommand) -> Option<Message> {
+        if command.argument.is_empty() {
+            return None;
+        }
+
+        match self.buffers.lookup(&self.buffer, &command.argument) {
+            Ok(number) => {
+                self.switch_buffer(number);
+                None
+            }
+            Err(error) => Some(Message::new_err(&error)),
+        }
+    }
+
+    // Ctrl-^ switches to the alternate buffer, or to the buffer with the count as its number
+    pub fn alternate(&mut self, count: Option<usize>) -> Option<Message> {
+        let number = match (count, self.buffers.alternate) {
+            (Some(number), _) => number,
+            (None, Some(alternate)) => alternate,
+            (None, None) => return Some(Message::new_err("No alternate file")),
+        };
+
+        match self.buffers.lookup(&self.buffer, &number.to_string()) {
+            Ok(number) => {
+                self.switch_buffer(number);
+                None
+            }
+            Err(error) => Some(Message::new_err(&error)),
+        }
+    }
+
+    pub fn delete_buffer(&mut self, command: &ExCommand) -> Option<Message> {
+        let number = if command.argument.is_empty() {
+            self.buffer.number
+        } else {
+            match self.buffers.lookup(&self.buffer, &command.argument) {
+                Ok(number) => number,
+                Err(error) => return Some(Message::new_err(&error)),
+            }
+        };
+
+        if number == self.buffer.number {
+            if !command.bang && self.buffer.is_modified() {
+                return Some(Message::new_err(
+                    "No write since last change (add ! to override)",
+                ));
+            }
+
+            // The alternate buffer takes the place of the deleted one when there is one, it can be
+            // the same buffer after going through windows and tab pages that show it
+            let next = self
+                .buffers
+                .alternate
+                .filter(|alternate| *alternate != number)
+                .or_else(|| self.buffers.step(number, 1, true));
+
+            let Some(next) = next else {
+                return Some(Message::new_err("Cannot delete the last buffer"));
+            };
+
+            self.switch_buffer(next);
+        }
+
+        if let Some(buffer) = self.buffers.take(number) {
+            if !command.bang && buffer.is_modified() {
+                let error =
+                    format!("No write since last change for buffer {number} (add ! to override)");
+
+                self.buffers.hide(buffer);
+                return Some(Message::new_err(&error));
+            }
+        }
+
+        if self.buffers.alternate == Some(number) {
+            self.buffers.alternate = None;
+        }
+
+        self.journal.remove(number);
+        self.close_windows_on(number);
+
+        None
+    }
+
+    // Lists the open buffers like :ls in vim, marking the current buffer with %, the alternate
+    // one with # and the ones with changes with +
+    pub fn list_buffers(&self) -> Option<Message> {
+        let mut listing = Vec::new();
+
+        for buffer in self.buffers.all(&self.buffer) {
+            let flag = if buffer.number == self.buffer.number {
+                '%'
+            } else if Some(buffer.number) == self.buffers.alternate {
+                '#'
+            } else {
+                ' '
+            };
+
+            let modified = if buffer.is_modified() { '+' } else { ' ' };
+            let line = buffer.cursor.position.y.saturating_add(1);
+
+            listing.push(format!(
+                "{:>3} {flag} {modified} \"{}\" line {line}",
+                buffer.number,
+                buffer.display_path()
+            ));
+        }
+
+        Some(Message::new(&listing.join("\n")))
+    }
+
+    // The paths of the open buffers, which buffer arguments are completed with
+    pub fn buffer_names(&self) -> Vec<String> {
+        self.buffers
+            .all(&self.buffer)
+            .iter()
+            .map(|buffer| buffer.display_path())
+            .collect()
+    }
+}
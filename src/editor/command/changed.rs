@@ -0,0 +1,139 @@
+use crate::editor::{
+    buffer::{encoding::Decode, Buffer},
+    mode::Mode,
+    status::message::Message,
+    swap, Editor, Key,
+};
+use crate::system::watch::Watcher;
+
+fn changed_question(name: &str) -> String {
+    format!(
+        "{name} has been changed by another program, and the buffer has changes too\n\
+         (r)eload it, (k)eep the changes, (d)iff them or Esc to decide later"
+    )
+}
+
+impl Editor {
+    pub fn watch_files(&mut self) {
+        if let Some(watcher) = &mut self.watcher {
+            let buffers = self.buffers.all(&self.buffer);
+            watcher.watch(buffers.iter().map(|buffer| buffer.path()));
+        }
+    }
+
+    // Reads the files other programs have written to again, the buffers with changes of their own
+    // are asked about instead
+    pub fn check_files(&mut self) {
+        let Some(changes) = self.watcher.as_mut().map(Watcher::changes) else {
+            return;
+        };
+
+        let changed: Vec<(usize, bool)> = self
+            .buffers
+            .all(&self.buffer)
+            .iter()
+            .filter(|buffer| {
+                changes
+                    .as_ref()
+                    .is_none_or(|changes| changes.contains(buffer.path()))
+            })
+            .filter(|buffer| buffer.is_changed_on_disk())
+            .map(|buffer| (buffer.number, buffer.is_modified()))
+            .collect();
+
+        let encodings = self.options.fileencodings.clone();
+        let mut reloaded = Vec::new();
+
+        for (number, modified) in changed {
+            if modified {
+                if !self.conflicts.contains(&number) {
+                    self.conflicts.push(number);
+                }
+
+                continue;
+            }
+
+            match self.reread(number, Decode::Detect(&encodings)) {
+                Ok(name) => reloaded.push(name),
+                Err(error) => self.status.message = Some(Message::new_err(&error)),
+            }
+        }
+
+        if !reloaded.is_empty() {
+            let message = format!(
+                "\"{}\" changed on disk and was reloaded",
+                reloaded.join("\", \"")
+            );
+            self.status.message = Some(Message::new(&message));
+        }
+    }
+
+    // Asks about the buffer being shown when its file was written by something else while it had
+    // changes, once nothing else is going on
+    pub fn ask_about_changes(&mut self) {
+        let number = self.buffer.number;
+
+        if self.mode != Mode::Normal || !self.conflicts.contains(&number) {
+            return;
+        }
+
+        // The changes may have been undone or the file written since
+        if !self.buffer.is_modified() || !self.buffer.is_changed_on_disk() {
+            self.conflicts.retain(|conflict| *conflict != number);
+            return;
+        }
+
+        let question = changed_question(&self.buffer.file_name());
+
+        self.mode = Mode::Changed;
+        self.status.message = Some(Message::new_prompt(&question));
+    }
+
+    pub fn handle_key_changed(&mut self, key: Key) {
+        let number = self.buffer.number;
+
+        self.status.message = match key {
+            Key::Char('r') => {
+                let encodings = self.options.fileencodings.clone();
+
+                self.reread(number, Decode::Detect(&encodings))
+                    .err()
+                    .map(|error| Message::new_err(&error))
+            }
+            Key::Char('k') => {
+                self.buffer.accept_disk();
+                Some(Message::new(
+                    "Kept the changes, :w writes them over the file",
+                ))
+            }
+            Key::Char('d') => {
+                let path = self.buffer.path().to_path_buf();
+                let decode = Decode::Force(self.buffer.format.encoding);
+
+                let disk = match Buffer::read_contents(&path, decode) {
+                    Ok(disk) => disk,
+                    Err(error) => {
+                        self.status.message = Some(Message::new_err(&error.to_string()));
+                        return;
+                    }
+                };
+
+                // The question is asked again below the differences
+                let limit = (self.terminal.size.height as usize).saturating_sub(3);
+                let diff = swap::diff(&self.buffer.contents(), &disk, limit);
+
+                let question = changed_question(&self.buffer.file_name());
+                let message = format!("{diff}\n{question}");
+
+                self.status.message = Some(Message::new_prompt(&message));
+                return;
+            }
+            // Left for later, writing over the file still has to be forced
+            Key::Escape => None,
+            _ => return,
+        };
+
+        self.conflicts.retain(|conflict| *conflict != number);
+        self.mode = Mode::Normal;
+    }
+}
@@ -0,0 +1,103 @@
+use super::{parse, registry};
+use crate::editor::options;
+
+use std::{fs, path::Path};
+
+// What the argument of a command is completed with
+#[derive(Clone, Copy, PartialEq)]
+pub enum Completion {
+    Nothing,
+    Path,
+    Option,
+    Buffer,
+}
+
+// The candidates shown above the status bar while completing, the selected one has been put in
+// the command line from start up to the cursor
+pub struct Wildmenu {
+    pub candidates: Vec<String>,
+    pub selected: usize,
+    pub start: usize,
+}
+
+fn paths(word: &str) -> Vec<String> {
+    let (directory, prefix) = match word.rfind('/') {
+        Some(index) => (&word[..=index], &word[index + 1..]),
+        None => ("", word),
+    };
+
+    let path = if directory.is_empty() { "." } else { directory };
+
+    let Ok(entries) = fs::read_dir(Path::new(path)) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+
+            // Hidden files are only shown when asked for
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+
+            let separator = if entry.path().is_dir() { "/" } else { "" };
+
+            Some(format!("{directory}{name}{separator}"))
+        })
+        .collect();
+
+    candidates.sort();
+    candidates
+}
+
+fn options(word: &str) -> Vec<String> {
+    // Switches can be completed after no and inv as well
+    let (prefix, name) = ["no", "inv"]
+        .iter()
+        .find_map(|prefix| Some((*prefix, word.strip_prefix(prefix)?)))
+        .unwrap_or(("", word));
+
+    options::NAMES
+        .iter()
+        .filter(|(option, _)| option.starts_with(name))
+        .map(|(option, _)| format!("{prefix}{option}"))
+        .collect()
+}
+
+// Finds what the text before the cursor can be completed with, returning the char index the
+// completed word starts at along with the candidates for it. Buffers are completed with the paths
+// of the open buffers
+pub fn candidates(line: &str, buffers: &[String]) -> Option<(usize, Vec<String>)> {
+    let command = parse::parse(line).ok()?;
+
+    // Still typing the name of the command
+    if command.argument.is_empty() && !command.bang && line.ends_with(&command.name) {
+        let start = line.chars().count() - command.name.chars().count();
+        return Some((start, registry::names(&command.name)));
+    }
+
+    let completion = registry::lookup(&command.name).ok()?.completion;
+
+    let start = line
+        .char_indices()
+        .rfind(|(_, character)| character.is_whitespace())
+        .map_or(0, |(index, _)| index + 1);
+
+    let word = &line[start..];
+    let start = line[..start].chars().count();
+
+    let candidates = match completion {
+        Completion::Nothing => return None,
+        Completion::Path => paths(word),
+        Completion::Option => options(word),
+        Completion::Buffer => buffers
+            .iter()
+            .filter(|buffer| buffer.contains(word))
+            .cloned()
+            .collect(),
+    };
+
+    Some((start, candidates))
+}
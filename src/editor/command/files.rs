@@ -0,0 +1,201 @@
+use super::parse::{self, ExCommand, WriteArgument};
+use crate::editor::{
+    buffer::{
+        encoding::{Decode, Encoding},
+        Buffer,
+    },
+    status::message::Message,
+    Editor,
+};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+impl Editor {
+    // Works out which file a write goes to, refusing to overwrite a file other than the buffer's
+    // own or one that was changed since it was read unless forced, and creating the directories
+    // leading up to it when asked to
+    fn write_target(&self, argument: &WriteArgument, force: bool) -> Result<PathBuf, String> {
+        let path = match &argument.path {
+            Some(path) => Buffer::resolve(path).map_err(|error| error.to_string())?,
+            None => self.buffer.path().to_path_buf(),
+        };
+
+        let other = path != self.buffer.path();
+
+        if other && !argument.append && !force && path.exists() {
+            return Err(String::from("File exists (add ! to override)"));
+        }
+
+        // Writing over changes another program made to the file would lose them without a word
+        if !other && !argument.append && !force && self.buffer.is_changed_on_disk() {
+            return Err(String::from(
+                "The file has been changed since reading it (add ! to override)",
+            ));
+        }
+
+        if argument.parents {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(|error| error.to_string())?;
+            }
+        }
+
+        Ok(path)
+    }
+
+    pub fn write(&mut self, command: &ExCommand) -> Option<Message> {
+        let argument = match parse::write_argument(&command.argument) {
+            Ok(argument) => argument,
+            Err(error) => return Some(Message::new_err(&error)),
+        };
+
+        let path = match self.write_target(&argument, command.bang) {
+            Ok(path) => path,
+            Err(error) => return Some(Message::new_err(&error)),
+        };
+
+        // A range that covers every line is the same as writing the whole text
+        let lines = match self.command_lines(command) {
+            _ if command.range.is_empty() => None,
+            Ok((0, end)) if end + 1 == self.buffer.line_count() => None,
+            Ok(lines) => Some(lines),
+            Err(error) => return Some(Message::new_err(&error)),
+        };
+
+        let own = path == self.buffer.path();
+
+        if own && lines.is_some() && !argument.append && !command.bang {
+            return Some(Message::new_err("Use ! to write partial buffer"));
+        }
+
+        // Only writing the whole text to the buffer's own file counts as saving it
+        let result = if argument.append || !own || lines.is_some() {
+            self.buffer
+                .write(&path, lines, argument.append, self.options.backupcopy)
+        } else {
+            self.buffer.save(self.options.backupcopy)
+        };
+
+        let verb = if argument.append { "Appended" } else { "Wrote" };
+
+        match result {
+            Ok(bytes) => {
+                let message = format!("{verb} {bytes} bytes to {}", path.display());
+                Some(Message::new(&message))
+            }
+            Err(error) => Some(Message::new_err(&error.to_string())),
+        }
+    }
+
+    pub fn save_as(&mut self, command: &ExCommand) -> Option<Message> {
+        let argument = match parse::write_argument(&command.argument) {
+            Ok(argument) if argument.append => {
+                return Some(Message::new_err("Trailing characters"))
+            }
+            Ok(argument) if argument.path.is_none() => {
+                return Some(Message::new_err("Argument required"))
+            }
+            Ok(argument) => argument,
+            Err(error) => return Some(Message::new_err(&error)),
+        };
+
+        let path = match self.write_target(&argument, command.bang) {
+            Ok(path) => path,
+            Err(error) => return Some(Message::new_err(&error)),
+        };
+
+        self.buffer.set_path(path);
+
+        match self.buffer.save(self.options.backupcopy) {
+            Ok(bytes) => {
+                let path = self.buffer.path().display();
+                let message = format!("Wrote {bytes} bytes to {path}");
+
+                Some(Message::new(&message))
+            }
+            Err(error) => Some(Message::new_err(&error.to_string())),
+        }
+    }
+
+    pub fn edit(&mut self, command: &ExCommand) -> Option<Message> {
+        let (encoding, argument) = command.encoding();
+        let encodings = self.options.fileencodings.clone();
+
+        let decode = match encoding.map(|label| (label, Encoding::parse(label))) {
+            Some((_, Some(encoding))) => Decode::Force(encoding),
+            Some((label, None)) => {
+                return Some(Message::new_err(&format!("Invalid encoding name: {label}")));
+            }
+            None => Decode::Detect(&encodings),
+        };
+
+        if argument.is_empty() {
+            return self.reload(command.bang, decode);
+        }
+
+        let path = match Buffer::resolve(Path::new(argument)) {
+            Ok(path) => path,
+            Err(error) => return Some(Message::new_err(&error.to_string())),
+        };
+
+        // Giving an encoding for the file being edited reads it again in that encoding
+        if path == self.buffer.path() {
+            return encoding.and_then(|_| self.reload(command.bang, decode));
+        }
+
+        // Files that are already open are switched to instead of being opened again
+        if let Some(number) = self.buffers.find(&path) {
+            self.switch_buffer(number);
+            return None;
+        }
+
+        let number = self.buffers.next_number();
+
+        match Buffer::from_file(&path, number, decode) {
+            Ok(buffer) => self.buffers.hide(buffer),
+            Err(error) => return Some(Message::new_err(&error.to_string())),
+        }
+
+        self.switch_buffer(number);
+
+        if path.exists() {
+            None
+        } else {
+            let message = format!("\"{}\" [New]", self.buffer.display_path());
+            Some(Message::new(&message))
+        }
+    }
+
+    // Reads the file of the current buffer again, throwing away any changes when forced
+    fn reload(&mut self, force: bool, decode: Decode) -> Option<Message> {
+        if !force && self.buffer.is_modified() {
+            return Some(Message::new_err(
+                "No write since last change (add ! to override)",
+            ));
+        }
+
+        self.reread(self.buffer.number, decode)
+            .err()
+            .map(|error| Message::new_err(&error))
+    }
+
+    fn buffer_mut(&mut self, number: usize) -> Option<&mut Buffer> {
+        if number == self.buffer.number {
+            Some(&mut self.buffer)
+        } else {
+            self.buffers.get_mut(number)
+        }
+    }
+
+    // Reads the file of a buffer again, keeping the cursors where they were, and returns the name
+    // of the file
+    pub fn reread(&mut self, number: usize, decode: Decode) -> Result<String, String> {
+        let buffer = self
+            .buffer_mut(number)
+            .ok_or_else(|| format!("Buffer {number} does not exist"))?;
+
+        buffer.reload(decode).map_err(|error| error.to_string())?;
+
+        Ok(buffer.file_name())
+    }
+}
@@ -0,0 +1,103 @@
+use crate::system::{dirs, file};
+
+use std::{fs, io::Result, path::PathBuf};
+
+// How many entries are kept, the oldest ones are dropped first
+const HISTORY_SIZE: usize = 200;
+
+// The lines that were entered at a prompt, kept in the state directory so they are still around
+// the next time alice is started
+#[derive(Default)]
+pub struct History {
+    path: Option<PathBuf>,
+    entries: Vec<String>,
+    // The entry that is being shown while going through the history, along with what was typed
+    // before that, which only entries starting with it are shown for
+    index: Option<usize>,
+    draft: String,
+}
+
+impl History {
+    pub fn load(name: &str) -> Self {
+        let path = dirs::state().ok().map(|state| state.join(name));
+
+        let entries = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|data| data.lines().map(String::from).collect())
+            .unwrap_or_default();
+
+        Self {
+            path,
+            entries,
+            ..Self::default()
+        }
+    }
+
+    // Adds the entry and saves the history, which is replaced as a whole so that a crash while
+    // writing it doesn't lose what was there
+    pub fn add(&mut self, entry: String) -> Result<()> {
+        self.reset();
+
+        if entry.is_empty() {
+            return Ok(());
+        }
+
+        self.entries.retain(|existing| *existing != entry);
+        self.entries.push(entry);
+
+        let excess = self.entries.len().saturating_sub(HISTORY_SIZE);
+        self.entries.drain(..excess);
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let mut data = self.entries.join("\n");
+        data.push('\n');
+
+        file::replace(path, |file| {
+            file.write_all(data.as_bytes())?;
+            Ok(data.len())
+        })?;
+
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.index = None;
+        self.draft.clear();
+    }
+
+    pub fn older(&mut self, typed: &str) -> Option<&str> {
+        if self.index.is_none() {
+            self.draft = typed.to_string();
+        }
+
+        let end = self.index.unwrap_or(self.entries.len());
+
+        let index = self.entries[..end]
+            .iter()
+            .rposition(|entry| entry.starts_with(&self.draft))?;
+
+        self.index = Some(index);
+        Some(&self.entries[index])
+    }
+
+    // Going past the newest entry brings back what was typed
+    pub fn newer(&mut self) -> Option<&str> {
+        let start = self.index? + 1;
+
+        let index = self.entries[start..]
+            .iter()
+            .position(|entry| entry.starts_with(&self.draft))
+            .map(|index| index + start);
+
+        self.index = index;
+
+        match index {
+            Some(index) => Some(&self.entries[index]),
+            None => Some(&self.draft),
+        }
+    }
+}
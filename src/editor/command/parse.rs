@@ -0,0 +1,455 @@
+use std::{iter::Peekable, path::PathBuf, str::Chars};
+
+// The pieces a range in front of a command is made of
+#[derive(Clone, PartialEq)]
+enum Token {
+    Number(usize),
+    Current,
+    Last,
+    Whole,
+    Comma,
+    Semicolon,
+    Plus,
+    Minus,
+    Mark(char),
+    Pattern { pattern: String, forward: bool },
+}
+
+// A line given in front of a command, such as the 5 in :5d or the $ in :.,$s/a/b/
+#[derive(Clone)]
+pub enum Address {
+    Number(usize),
+    Current,
+    Last,
+    Mark(char),
+    // The next line (or the previous one with ?pattern?) matching the pattern
+    Pattern { pattern: String, forward: bool },
+}
+
+// An address along with the lines added to or taken away from it, like .+3 or $-1
+#[derive(Clone)]
+pub struct Location {
+    pub address: Address,
+    pub offset: isize,
+    // Given after a semicolon, which makes the line of the location before it the current line
+    // while this one and the ones after it are looked up, like /bar/ in :/foo/;/bar/d
+    pub relative: bool,
+}
+
+impl Location {
+    fn new(address: Address) -> Self {
+        Self {
+            address,
+            offset: 0,
+            relative: false,
+        }
+    }
+}
+
+// A command line split into its parts, :[range]name[!] [count] [argument]
+#[derive(Default)]
+pub struct ExCommand {
+    // Every location given in front of the command, the last two of which are the start and the
+    // end of the range, empty when there is no range
+    pub range: Vec<Location>,
+    pub name: String,
+    pub bang: bool,
+    pub count: Option<usize>,
+    pub argument: String,
+}
+
+impl ExCommand {
+    // Takes a count off the end of the argument, for commands that accept one
+    pub fn take_count(&mut self) {
+        let (rest, count) = self
+            .argument
+            .rsplit_once(' ')
+            .unwrap_or(("", self.argument.as_str()));
+
+        if let Ok(count) = count.parse() {
+            self.count = Some(count);
+            self.argument = rest.trim_end().to_string();
+        }
+    }
+
+    // Splits a ++enc=name in front of the argument off of it, for commands that read a file
+    pub fn encoding(&self) -> (Option<&str>, &str) {
+        let (first, rest) = self
+            .argument
+            .split_once(' ')
+            .unwrap_or((self.argument.as_str(), ""));
+
+        match first
+            .strip_prefix("++enc=")
+            .or_else(|| first.strip_prefix("++encoding="))
+        {
+            Some(encoding) => (Some(encoding), rest.trim_start()),
+            None => (None, self.argument.as_str()),
+        }
+    }
+}
+
+fn number(chars: &mut Peekable<Chars>) -> usize {
+    let mut number: usize = 0;
+
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        number = number.saturating_mul(10).saturating_add(digit as usize);
+        chars.next();
+    }
+
+    number
+}
+
+// Reads a pattern up to the closing delimiter, which may be left out at the end of the line
+fn pattern(chars: &mut Peekable<Chars>, delimiter: char) -> String {
+    let mut pattern = String::new();
+
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' if chars.peek() == Some(&delimiter) => pattern.extend(chars.next()),
+            _ if character == delimiter => break,
+            _ => pattern.push(character),
+        }
+    }
+
+    pattern
+}
+
+const RANGE_CHARACTERS: &str = " \t.$%,;+-'/?";
+
+// Splits the range off the front of a command line, leaving the chars at the command name
+fn tokenize(chars: &mut Peekable<Chars>) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+
+    while let Some(character) = chars.peek().copied() {
+        if character.is_ascii_digit() {
+            tokens.push(Token::Number(number(chars)));
+            continue;
+        }
+
+        if !RANGE_CHARACTERS.contains(character) {
+            break;
+        }
+
+        chars.next();
+
+        let token = match character {
+            '.' => Token::Current,
+            '$' => Token::Last,
+            '%' => Token::Whole,
+            ',' => Token::Comma,
+            ';' => Token::Semicolon,
+            '+' => Token::Plus,
+            '-' => Token::Minus,
+            '\'' => {
+                let mark = chars.next().ok_or_else(|| String::from("Invalid range"))?;
+                Token::Mark(mark)
+            }
+            '/' | '?' => Token::Pattern {
+                pattern: pattern(chars, character),
+                forward: character == '/',
+            },
+            _ => continue,
+        };
+
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+// Parses a single location, which is an address followed by any number of offsets, either of which
+// can be left out
+fn location(tokens: &mut Peekable<std::vec::IntoIter<Token>>) -> Option<Location> {
+    let address = match tokens.peek()? {
+        Token::Number(number) => Some(Address::Number(*number)),
+        Token::Current => Some(Address::Current),
+        Token::Last => Some(Address::Last),
+        Token::Mark(mark) => Some(Address::Mark(*mark)),
+        Token::Pattern { pattern, forward } => Some(Address::Pattern {
+            pattern: pattern.clone(),
+            forward: *forward,
+        }),
+        _ => None,
+    };
+
+    if address.is_some() {
+        tokens.next();
+    }
+
+    let mut offset: isize = 0;
+    let mut offsets = false;
+
+    while let Some(sign) = tokens.next_if(|token| matches!(token, Token::Plus | Token::Minus)) {
+        // A sign without a number after it moves by one line
+        let amount = match tokens.next_if(|token| matches!(token, Token::Number(_))) {
+            Some(Token::Number(number)) => number as isize,
+            _ => 1,
+        };
+
+        offset += if sign == Token::Plus { amount } else { -amount };
+        offsets = true;
+    }
+
+    if address.is_none() && !offsets {
+        return None;
+    }
+
+    Some(Location {
+        address: address.unwrap_or(Address::Current),
+        offset,
+        relative: false,
+    })
+}
+
+fn range(tokens: Vec<Token>) -> Result<Vec<Location>, String> {
+    if tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    if tokens == [Token::Whole] {
+        return Ok(vec![
+            Location::new(Address::Number(1)),
+            Location::new(Address::Last),
+        ]);
+    }
+
+    let mut tokens = tokens.into_iter().peekable();
+    let mut locations = Vec::new();
+    let mut relative = false;
+
+    loop {
+        // A missing location on either side of the separator is the current line, like in vim
+        let mut location = location(&mut tokens).unwrap_or(Location::new(Address::Current));
+        location.relative = relative;
+
+        locations.push(location);
+
+        match tokens.next_if(|token| matches!(token, Token::Comma | Token::Semicolon)) {
+            Some(separator) => relative = separator == Token::Semicolon,
+            None => break,
+        }
+    }
+
+    if tokens.next().is_some() {
+        return Err(String::from("Invalid range"));
+    }
+
+    Ok(locations)
+}
+
+pub fn parse(line: &str) -> Result<ExCommand, String> {
+    let mut chars = line.chars().peekable();
+
+    let range = range(tokenize(&mut chars)?)?;
+
+    let mut name = String::new();
+
+    while let Some(c) = chars.next_if(char::is_ascii_alphabetic) {
+        name.push(c);
+    }
+
+    // The shift commands are the only ones that aren't named with letters
+    if name.is_empty() {
+        name.extend(chars.next_if(|c| matches!(c, '<' | '>')));
+    }
+
+    let bang = chars.next_if_eq(&'!').is_some();
+    let argument = chars.collect::<String>().trim_start().to_string();
+
+    Ok(ExCommand {
+        range,
+        name,
+        bang,
+        count: None,
+        argument,
+    })
+}
+
+// The argument of the commands that write files, [++p] [>>] [path]
+#[derive(Default)]
+pub struct WriteArgument {
+    // Creates the directories leading up to the file when they don't exist
+    pub parents: bool,
+    pub append: bool,
+    pub path: Option<PathBuf>,
+}
+
+pub fn write_argument(argument: &str) -> Result<WriteArgument, String> {
+    let mut write = WriteArgument::default();
+    let mut rest = argument.trim();
+
+    while let Some(option) = rest.strip_prefix("++") {
+        let (option, after) = option.split_once(' ').unwrap_or((option, ""));
+
+        match option {
+            "p" => write.parents = true,
+            _ => return Err(format!("Invalid argument: ++{option}")),
+        }
+
+        rest = after.trim_start();
+    }
+
+    if let Some(after) = rest.strip_prefix(">>") {
+        write.append = true;
+        rest = after.trim_start();
+    }
+
+    if !rest.is_empty() {
+        write.path = Some(PathBuf::from(rest));
+    }
+
+    Ok(write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes the range back out the way it would be typed, with every location after a semicolon
+    // marked by one
+    fn range(line: &str) -> Vec<String> {
+        let command = parse(line).expect("to parse");
+
+        command
+            .range
+            .iter()
+            .map(|location| {
+                let address = match &location.address {
+                    Address::Number(number) => number.to_string(),
+                    Address::Current => String::from("."),
+                    Address::Last => String::from("$"),
+                    Address::Mark(mark) => format!("'{mark}"),
+                    Address::Pattern { pattern, forward } => {
+                        let delimiter = if *forward { '/' } else { '?' };
+                        format!("{delimiter}{pattern}{delimiter}")
+                    }
+                };
+
+                let separator = if location.relative { ";" } else { "" };
+
+                match location.offset {
+                    0 => format!("{separator}{address}"),
+                    offset => format!("{separator}{address}{offset:+}"),
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn no_range() {
+        assert!(range("d").is_empty());
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(range("5d"), ["5"]);
+        assert_eq!(range("3,7d"), ["3", "7"]);
+    }
+
+    #[test]
+    fn whole_file() {
+        assert_eq!(range("%s/a/b/"), ["1", "$"]);
+    }
+
+    #[test]
+    fn marks() {
+        assert_eq!(range("'a,'bd"), ["'a", "'b"]);
+    }
+
+    #[test]
+    fn patterns() {
+        assert_eq!(range("/pat/+1d"), ["/pat/+1"]);
+        assert_eq!(range("?pat?d"), ["?pat?"]);
+        assert_eq!(range(r"/a\/b/d"), ["/a/b/"]);
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(range("$-2,$d"), ["$-2", "$"]);
+        assert_eq!(range(".+3d"), [".+3"]);
+        assert_eq!(range("+,--d"), [".+1", ".-2"]);
+    }
+
+    #[test]
+    fn missing_locations_are_the_current_line() {
+        assert_eq!(range(",5d"), [".", "5"]);
+        assert_eq!(range("5,d"), ["5", "."]);
+    }
+
+    #[test]
+    fn semicolons() {
+        assert_eq!(range("/foo/;/bar/d"), ["/foo/", ";/bar/"]);
+        assert_eq!(range("1;+2,$d"), ["1", ";.+2", "$"]);
+    }
+
+    #[test]
+    fn invalid_range() {
+        assert!(parse("1%d").is_err());
+        assert!(parse("'").is_err());
+    }
+
+    #[test]
+    fn name_bang_and_argument() {
+        let command = parse("2,3w! out.txt").expect("to parse");
+
+        assert_eq!(command.range.len(), 2);
+        assert_eq!(command.name, "w");
+        assert!(command.bang);
+        assert_eq!(command.argument, "out.txt");
+
+        let command = parse("q").expect("to parse");
+
+        assert_eq!(command.name, "q");
+        assert!(!command.bang);
+    }
+
+    #[test]
+    fn shift_commands() {
+        let command = parse(">").expect("to parse");
+
+        assert_eq!(command.name, ">");
+    }
+
+    #[test]
+    fn counts() {
+        let mut command = parse("d 3").expect("to parse");
+        command.take_count();
+
+        assert_eq!(command.count, Some(3));
+        assert!(command.argument.is_empty());
+
+        let mut command = parse("d a 3").expect("to parse");
+        command.take_count();
+
+        assert_eq!(command.count, Some(3));
+        assert_eq!(command.argument, "a");
+    }
+
+    #[test]
+    fn encodings() {
+        let command = parse("e ++enc=latin1 file.txt").expect("to parse");
+
+        assert_eq!(command.encoding(), (Some("latin1"), "file.txt"));
+
+        let command = parse("e file.txt").expect("to parse");
+
+        assert_eq!(command.encoding(), (None, "file.txt"));
+    }
+
+    #[test]
+    fn write_arguments() {
+        let write = write_argument("++p >> out.txt").expect("to parse");
+
+        assert!(write.parents);
+        assert!(write.append);
+        assert_eq!(write.path, Some(PathBuf::from("out.txt")));
+
+        let write = write_argument("").expect("to parse");
+
+        assert!(!write.append);
+        assert_eq!(write.path, None);
+
+        assert!(write_argument("++x").is_err());
+    }
+}
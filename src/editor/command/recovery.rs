@@ -0,0 +1,115 @@
+use crate::editor::{
+    mode::Mode,
+    status::message::Message,
+    swap::{self, Other},
+    Editor, Key,
+};
+
+fn recovery_question(name: &str, pid: u32) -> String {
+    format!(
+        "Found changes to {name} that alice process {pid} never wrote before it ended\n\
+         (r)ecover them, (d)iff them, (D)elete the swap file or (e)dit anyway"
+    )
+}
+
+impl Editor {
+    // Keeps the swap file of the current buffer up to date, the first time a buffer is shown the
+    // swap files other alice processes have for it are looked at
+    pub fn track_buffer(&mut self) {
+        if !self.journal.update(&self.buffer) {
+            return;
+        }
+
+        let number = self.buffer.number;
+        let mut running = Vec::new();
+
+        for other in swap::others(self.buffer.path()) {
+            match other {
+                Other::Running(pid) => running.push(pid.to_string()),
+                // Changes that made it into the file after all have nothing left to recover
+                Other::Crashed(crashed) if crashed.text == self.buffer.contents() => {
+                    crashed.remove();
+                }
+                Other::Crashed(crashed) => {
+                    if !self.recoveries.iter().any(|(buffer, _)| *buffer == number) {
+                        self.recoveries.push((number, crashed));
+                    }
+                }
+            }
+        }
+
+        if !running.is_empty() {
+            let error = format!(
+                "\"{}\" is also being edited by alice process {}",
+                self.buffer.display_path(),
+                running.join(", ")
+            );
+
+            self.status.message = Some(Message::new_err(&error));
+        }
+    }
+
+    pub fn ask_about_recovery(&mut self) {
+        let number = self.buffer.number;
+
+        if self.mode != Mode::Normal {
+            return;
+        }
+
+        let Some(index) = self
+            .recoveries
+            .iter()
+            .position(|(buffer, _)| *buffer == number)
+        else {
+            return;
+        };
+
+        let (_, crashed) = self.recoveries.remove(index);
+        let question = recovery_question(&self.buffer.file_name(), crashed.pid);
+
+        self.recovery = Some(crashed);
+        self.mode = Mode::Recover;
+        self.status.message = Some(Message::new_prompt(&question));
+    }
+
+    pub fn handle_key_recover(&mut self, key: Key) {
+        let Some(crashed) = self.recovery.take() else {
+            self.mode = Mode::Normal;
+            return;
+        };
+
+        self.status.message = match key {
+            // The swap file is kept until the changes have been written, which is when it gets
+            // removed the next time the file is opened
+            Key::Char('r') => {
+                self.buffer.replace_all(&crashed.text);
+
+                Some(Message::new("Recovered the changes, write them with :w"))
+            }
+            Key::Char('d') => {
+                // The question is asked again below the changes
+                let limit = (self.terminal.size.height as usize).saturating_sub(3);
+                let diff = swap::diff(&self.buffer.contents(), &crashed.text, limit);
+
+                let question = recovery_question(&self.buffer.file_name(), crashed.pid);
+                let message = format!("{diff}\n{question}");
+
+                self.status.message = Some(Message::new_prompt(&message));
+                self.recovery = Some(crashed);
+
+                return;
+            }
+            Key::Char('D') => {
+                crashed.remove();
+                Some(Message::new("Deleted the swap file"))
+            }
+            Key::Char('e') | Key::Escape => None,
+            _ => {
+                self.recovery = Some(crashed);
+                return;
+            }
+        };
+
+        self.mode = Mode::Normal;
+    }
+}
@@ -0,0 +1,313 @@
+use super::{complete::Completion, parse::ExCommand};
+use crate::editor::{status::message::Message, window::Split, Editor};
+
+type Run = fn(&mut Editor, &ExCommand) -> Option<Message>;
+
+// A command that can be typed on the command line, which can be shortened to any prefix that is at
+// least as long as its minimum, like :wri for :write
+pub struct Definition {
+    pub name: &'static str,
+    minimum: usize,
+    pub range: bool,
+    pub bang: bool,
+    pub count: bool,
+    pub completion: Completion,
+    pub run: Run,
+}
+
+impl Definition {
+    const fn new(name: &'static str, minimum: usize, run: Run) -> Self {
+        Self {
+            name,
+            minimum,
+            range: false,
+            bang: false,
+            count: false,
+            completion: Completion::Nothing,
+            run,
+        }
+    }
+
+    // Accepts a range of lines in front of the command
+    const fn range(mut self) -> Self {
+        self.range = true;
+        self
+    }
+
+    // Accepts a ! right after the name, which usually forces the command
+    const fn bang(mut self) -> Self {
+        self.bang = true;
+        self
+    }
+
+    // Accepts a count of lines after the command, counting from the end of the range
+    const fn count(mut self) -> Self {
+        self.count = true;
+        self
+    }
+
+    const fn complete(mut self, completion: Completion) -> Self {
+        self.completion = completion;
+        self
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        name.len() >= self.minimum && self.name.starts_with(name)
+    }
+}
+
+const COMMANDS: &[Definition] = &[
+    Definition::new("bNext", 2, |editor, command| {
+        editor.next_buffer(command, false)
+    })
+    .count(),
+    Definition::new("bdelete", 2, |editor, command| {
+        editor.delete_buffer(command)
+    })
+    .bang()
+    .complete(Completion::Buffer),
+    Definition::new("bnext", 2, |editor, command| {
+        editor.next_buffer(command, true)
+    })
+    .count(),
+    Definition::new("bprevious", 2, |editor, command| {
+        editor.next_buffer(command, false)
+    })
+    .count(),
+    Definition::new("buffer", 1, |editor, command| editor.buffer(command))
+        .complete(Completion::Buffer),
+    Definition::new("buffers", 7, |editor, _| editor.list_buffers()),
+    Definition::new("close", 3, |editor, _| editor.close_window()).bang(),
+    Definition::new("delete", 1, |editor, command| editor.delete_lines(command))
+        .range()
+        .count(),
+    Definition::new("display", 2, |editor, command| {
+        editor.list_registers(&command.argument)
+    }),
+    Definition::new("earlier", 2, |editor, command| {
+        editor.travel(&command.argument, true)
+    }),
+    Definition::new("edit", 1, |editor, command| editor.edit(command))
+        .bang()
+        .complete(Completion::Path),
+    Definition::new("exit", 3, |editor, command| editor.exit(command))
+        .bang()
+        .complete(Completion::Path),
+    Definition::new("files", 5, |editor, _| editor.list_buffers()),
+    Definition::new("later", 3, |editor, command| {
+        editor.travel(&command.argument, false)
+    }),
+    Definition::new("ls", 2, |editor, _| editor.list_buffers()),
+    Definition::new("mark", 2, |editor, command| editor.mark(command)).range(),
+    Definition::new("nohlsearch", 3, |editor, _| {
+        editor.search.highlight = false;
+        None
+    }),
+    Definition::new("only", 2, |editor, _| editor.only_window()).bang(),
+    Definition::new("qall", 2, |editor, command| editor.quit_all(command.bang)).bang(),
+    Definition::new("quit", 1, |editor, command| editor.quit(command.bang)).bang(),
+    Definition::new("redo", 3, |editor, _| editor.redo()),
+    Definition::new("registers", 3, |editor, command| {
+        editor.list_registers(&command.argument)
+    }),
+    Definition::new("resize", 3, |editor, command| editor.resize(command)),
+    Definition::new("saveas", 3, |editor, command| editor.save_as(command))
+        .bang()
+        .complete(Completion::Path),
+    Definition::new("set", 2, |editor, command| editor.set(command)).complete(Completion::Option),
+    Definition::new("split", 2, |editor, command| {
+        editor.split_window(command, Split::Horizontal)
+    })
+    .complete(Completion::Path),
+    Definition::new("substitute", 1, |editor, command| {
+        editor.substitute(command)
+    })
+    .range(),
+    Definition::new("tabNext", 4, |editor, command| {
+        editor.previous_tab(command.count.unwrap_or(1));
+        None
+    })
+    .count(),
+    Definition::new("tabclose", 4, |editor, _| editor.close_tab()).bang(),
+    Definition::new("tabedit", 4, |editor, command| editor.new_tab(command))
+        .complete(Completion::Path),
+    Definition::new("tabfirst", 6, |editor, _| {
+        editor.next_tab(Some(1));
+        None
+    }),
+    Definition::new("tablast", 4, |editor, _| {
+        editor.last_tab();
+        None
+    }),
+    Definition::new("tabnew", 6, |editor, command| editor.new_tab(command))
+        .complete(Completion::Path),
+    Definition::new("tabnext", 4, |editor, command| {
+        editor.next_tab(command.count);
+        None
+    })
+    .count(),
+    Definition::new("tabonly", 4, |editor, _| editor.only_tab()).bang(),
+    Definition::new("tabprevious", 4, |editor, command| {
+        editor.previous_tab(command.count.unwrap_or(1));
+        None
+    })
+    .count(),
+    Definition::new("undo", 1, |editor, _| editor.undo()),
+    Definition::new("vsplit", 2, |editor, command| {
+        editor.split_window(command, Split::Vertical)
+    })
+    .complete(Completion::Path),
+    Definition::new("wq", 2, |editor, command| editor.write_quit(command))
+        .bang()
+        .complete(Completion::Path),
+    Definition::new("write", 1, |editor, command| editor.write(command))
+        .range()
+        .bang()
+        .complete(Completion::Path),
+    Definition::new("xit", 1, |editor, command| editor.exit(command))
+        .bang()
+        .complete(Completion::Path),
+    Definition::new("yank", 1, |editor, command| editor.yank_lines(command))
+        .range()
+        .count(),
+    Definition::new("<", 1, |editor, command| editor.shift_lines(command, false))
+        .range()
+        .count(),
+    Definition::new(">", 1, |editor, command| editor.shift_lines(command, true))
+        .range()
+        .count(),
+];
+
+// Finds the command a name refers to, a full name always wins over the abbreviations of others
+pub fn lookup(name: &str) -> Result<&'static Definition, String> {
+    if let Some(definition) = COMMANDS.iter().find(|definition| definition.name == name) {
+        return Ok(definition);
+    }
+
+    let mut candidates = COMMANDS
+        .iter()
+        .filter(|definition| definition.matches(name));
+
+    match (candidates.next(), candidates.next()) {
+        (Some(definition), None) => Ok(definition),
+        (Some(_), Some(_)) => Err(format!("Ambiguous command: {name}")),
+        (None, _) => Err(format!("Not a command: {name}")),
+    }
+}
+
+// The names of every command starting with the given text, for completion
+pub fn names(prefix: &str) -> Vec<String> {
+    COMMANDS
+        .iter()
+        .filter(|definition| definition.name.starts_with(prefix))
+        .filter(|definition| definition.name.starts_with(char::is_alphabetic))
+        .map(|definition| definition.name.to_string())
+        .collect()
+}
+
+// Checks the parsed command against what the command accepts, taking off the bang and count
+pub fn prepare(command: &mut ExCommand) -> Result<&'static Definition, String> {
+    let definition = lookup(&command.name)?;
+
+    // Commands without a bang see the ! as the start of their argument, like the delimiter in
+    // :s!a!b!
+    if command.bang && !definition.bang {
+        command.bang = false;
+        command.argument.insert(0, '!');
+    }
+
+    if definition.count {
+        command.take_count();
+    }
+
+    if !command.range.is_empty() && !definition.range {
+        return Err(String::from("No range allowed"));
+    }
+
+    Ok(definition)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::parse;
+    use super::*;
+
+    fn name(name: &str) -> Result<&'static str, String> {
+        lookup(name).map(|definition| definition.name)
+    }
+
+    #[test]
+    fn shortened_names() {
+        assert_eq!(name("subst"), Ok("substitute"));
+        assert_eq!(name("d"), Ok("delete"));
+        assert_eq!(name("wq"), Ok("wq"));
+        assert_eq!(name("exi"), Ok("exit"));
+        assert_eq!(name("x"), Ok("xit"));
+    }
+
+    #[test]
+    fn too_short() {
+        assert!(name("re").is_err());
+        assert_eq!(name("red"), Ok("redo"));
+        assert!(name("ex").is_err());
+    }
+
+    #[test]
+    fn not_a_command() {
+        assert_eq!(name("nope"), Err(String::from("Not a command: nope")));
+    }
+
+    #[test]
+    fn every_command_can_be_shortened_to_its_minimum() {
+        for definition in COMMANDS {
+            let short = &definition.name[..definition.minimum];
+
+            assert_eq!(name(short), Ok(definition.name));
+        }
+    }
+
+    // The shift commands come after the ones named with letters
+    #[test]
+    fn commands_are_sorted() {
+        let names: Vec<&str> = COMMANDS
+            .iter()
+            .map(|definition| definition.name)
+            .filter(|name| name.starts_with(char::is_alphabetic))
+            .collect();
+
+        assert!(names.is_sorted(), "{names:?}");
+    }
+
+    #[test]
+    fn bang_without_bang_is_the_argument() {
+        let mut command = parse::parse("s!a!b!").expect("to parse");
+
+        assert_eq!(
+            prepare(&mut command).map(|definition| definition.name),
+            Ok("substitute")
+        );
+        assert!(!command.bang);
+        assert_eq!(command.argument, "!a!b!");
+    }
+
+    #[test]
+    fn ranged_write() {
+        let mut command = parse::parse("2,3w! out.txt").expect("to parse");
+
+        assert_eq!(
+            prepare(&mut command).map(|definition| definition.name),
+            Ok("write")
+        );
+        assert!(command.bang);
+    }
+
+    #[test]
+    fn range_not_allowed() {
+        let mut command = parse::parse("1,2q").expect("to parse");
+
+        assert_eq!(
+            prepare(&mut command).map(|definition| definition.name),
+            Err(String::from("No range allowed"))
+        );
+    }
+}
@@ -0,0 +1,113 @@
+use super::parse::ExCommand;
+use crate::editor::{
+    mode::Mode, position::Position, register, status::message::Message, substitute::Substitute,
+    Editor, Key,
+};
+
+impl Editor {
+    pub fn substitute(&mut self, command: &ExCommand) -> Option<Message> {
+        let (start, end) = match self.command_lines(command) {
+            Ok(lines) => lines,
+            Err(error) => return Some(Message::new_err(&error)),
+        };
+
+        let last_pattern = self.search.is_set().then_some(self.search.pattern.as_str());
+
+        let smart_case = self.options.smartcase;
+
+        let mut substitute =
+            match Substitute::new(&command.argument, last_pattern, start, end, smart_case) {
+                Ok(substitute) => substitute,
+                Err(error) => return Some(Message::new_err(&error)),
+            };
+
+        // The pattern becomes the last search pattern, like it would in vim
+        let pattern = substitute.pattern.clone();
+        _ = self.search.set(&pattern, true, self.options.smartcase);
+        self.registers.set(register::LAST_SEARCH, pattern);
+
+        if substitute.flags.confirm && !substitute.flags.count {
+            self.mode = Mode::Confirm;
+            self.substitute = Some(substitute);
+
+            return self.confirm_next();
+        }
+
+        substitute.run(&mut self.buffer);
+        self.finish_substitute(substitute)
+    }
+
+    // Moves on to the next match and asks what to do with it
+    fn confirm_next(&mut self) -> Option<Message> {
+        let mut substitute = self.substitute.take()?;
+
+        let Some(found) = substitute.next(&self.buffer) else {
+            return self.finish_substitute(substitute);
+        };
+
+        self.buffer.cursor.position = Position {
+            x: found.columns.0,
+            y: found.y,
+        };
+
+        let question = format!("replace with {} (y/n/a/q/l)?", substitute.replacement);
+
+        substitute.current = Some(found);
+        self.substitute = Some(substitute);
+
+        Some(Message::new_prompt(&question))
+    }
+
+    fn finish_substitute(&mut self, substitute: Substitute) -> Option<Message> {
+        self.mode = Mode::Normal;
+
+        // The cursor is left on the last line that was changed
+        if let (Some(y), false) = (substitute.last_line, substitute.flags.count) {
+            let x = self.buffer.cursor.first_non_blank(y);
+            self.buffer.cursor.position = Position { x, y };
+        }
+
+        match substitute.report() {
+            Ok(report) => report.map(|report| Message::new(&report)),
+            Err(error) => Some(Message::new_err(&error)),
+        }
+    }
+
+    pub fn handle_key_confirm(&mut self, key: Key) {
+        let Some(mut substitute) = self.substitute.take() else {
+            self.mode = Mode::Normal;
+            return;
+        };
+
+        let Some(found) = substitute.current.take() else {
+            self.status.message = self.finish_substitute(substitute);
+            return;
+        };
+
+        match key {
+            Key::Char('y') => substitute.replace(&mut self.buffer, &found),
+            Key::Char('n') => substitute.skip(&found),
+            Key::Char('a') => {
+                substitute.replace(&mut self.buffer, &found);
+                substitute.run(&mut self.buffer);
+            }
+            Key::Char('l') => {
+                substitute.replace(&mut self.buffer, &found);
+                self.status.message = self.finish_substitute(substitute);
+                return;
+            }
+            Key::Char('q') | Key::Escape => {
+                self.status.message = self.finish_substitute(substitute);
+                return;
+            }
+            _ => {
+                substitute.current = Some(found);
+                self.substitute = Some(substitute);
+                return;
+            }
+        }
+
+        self.substitute = Some(substitute);
+        self.status.message = self.confirm_next();
+    }
+}
@@ -0,0 +1,66 @@
+use super::parse::ExCommand;
+use crate::editor::{status::message::Message, window::Windows, Editor};
+
+impl Editor {
+    // Opens a tab page after the current one with a single window on the current buffer, or on the
+    // file given as the argument
+    pub fn new_tab(&mut self, command: &ExCommand) -> Option<Message> {
+        self.leave_window();
+        self.tabs
+            .open(&mut self.windows, Windows::new(self.buffer.number));
+
+        if command.argument.is_empty() {
+            None
+        } else {
+            self.edit(command)
+        }
+    }
+
+    pub fn switch_tab(&mut self, index: usize) {
+        if index == self.tabs.active || index >= self.tabs.count() {
+            return;
+        }
+
+        self.leave_window();
+        self.tabs.switch(&mut self.windows, index);
+        self.enter_window(self.windows.active);
+    }
+
+    // Goes to the tab page numbered by the count, or to the next one when there is no count
+    pub fn next_tab(&mut self, count: Option<usize>) {
+        let total = self.tabs.count();
+
+        let index = match count {
+            Some(number) => number.saturating_sub(1).min(total - 1),
+            None => (self.tabs.active + 1) % total,
+        };
+
+        self.switch_tab(index);
+    }
+
+    pub fn previous_tab(&mut self, count: usize) {
+        let total = self.tabs.count();
+        let index = (self.tabs.active + total - count % total) % total;
+
+        self.switch_tab(index);
+    }
+
+    pub fn last_tab(&mut self) {
+        self.switch_tab(self.tabs.count() - 1);
+    }
+
+    // The buffers shown in the tab page are left in the buffer list
+    pub fn close_tab(&mut self) -> Option<Message> {
+        if !self.tabs.close(&mut self.windows) {
+            return Some(Message::new_err("Cannot close last tab page"));
+        }
+
+        self.enter_window(self.windows.active);
+        None
+    }
+
+    pub fn only_tab(&mut self) -> Option<Message> {
+        self.tabs.only();
+        None
+    }
+}
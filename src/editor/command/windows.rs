@@ -0,0 +1,208 @@
+use super::parse::ExCommand;
+use crate::editor::{
+    status::message::Message,
+    window::{Rect, Split, Window},
+    Editor,
+};
+
+impl Editor {
+    fn focus_window(&mut self, index: usize) {
+        if index == self.windows.active || index >= self.windows.count() {
+            return;
+        }
+
+        self.leave_window();
+        self.enter_window(index);
+    }
+
+    // The window next to the one being used in the direction of h, j, k or l, when several windows
+    // border it the one lined up with the cursor is picked
+    fn neighbour_window(&self, direction: char) -> Option<usize> {
+        let rects = self.windows.rects(self.area());
+        let current = rects[self.windows.active];
+
+        let goto = self.buffer.cursor.goto(&current);
+        let (row, column) = (goto.0.saturating_sub(1), goto.1.saturating_sub(1));
+
+        let rows =
+            |rect: &Rect| rect.y < current.y + current.height && current.y < rect.y + rect.height;
+        let columns =
+            |rect: &Rect| rect.x <= current.x + current.width && current.x <= rect.x + rect.width;
+
+        let neighbours: Vec<(usize, &Rect)> = rects
+            .iter()
+            .enumerate()
+            .filter(|(_, rect)| match direction {
+                'h' => rect.x + rect.width + 1 == current.x && rows(rect),
+                'l' => current.x + current.width + 1 == rect.x && rows(rect),
+                'k' => rect.y + rect.height == current.y && columns(rect),
+                'j' => current.y + current.height == rect.y && columns(rect),
+                _ => false,
+            })
+            .collect();
+
+        let lined_up = neighbours.iter().find(|(_, rect)| match direction {
+            'h' | 'l' => (rect.y..rect.y + rect.height).contains(&row),
+            _ => (rect.x..=rect.x + rect.width).contains(&column),
+        });
+
+        lined_up.or(neighbours.first()).map(|(index, _)| *index)
+    }
+
+    pub fn split_window(&mut self, command: &ExCommand, split: Split) -> Option<Message> {
+        let window = Window {
+            buffer: self.buffer.number,
+            cursor: None,
+        };
+
+        let area = self.area();
+
+        if let Err(error) = self.windows.split(self.windows.active, split, window, area) {
+            return Some(Message::new_err(&error));
+        }
+
+        // The new window takes the place of the split one, which moves after it and keeps its own
+        // copy of the cursor
+        self.leave_window_at(self.windows.active + 1);
+
+        if command.argument.is_empty() {
+            None
+        } else {
+            self.edit(command)
+        }
+    }
+
+    fn leave_window_at(&mut self, index: usize) {
+        let active = self.windows.active;
+
+        self.windows.active = index;
+        self.leave_window();
+        self.windows.active = active;
+    }
+
+    pub fn close_window(&mut self) -> Option<Message> {
+        if self.is_last_window() {
+            return Some(Message::new_err("Cannot close last window"));
+        }
+
+        // The last window of a tab page closes the tab page
+        if self.windows.count() == 1 {
+            return self.close_tab();
+        }
+
+        let area = self.area();
+
+        if let Some(next) = self.windows.close(self.windows.active, area) {
+            self.enter_window(next);
+        }
+
+        None
+    }
+
+    pub fn only_window(&mut self) -> Option<Message> {
+        self.windows.only(self.windows.active);
+        None
+    }
+
+    // Closes the windows that show a buffer after it has been deleted, in every tab page
+    pub fn close_windows_on(&mut self, number: usize) {
+        let area = self.area();
+
+        // The window being used has to remember which buffer it shows before the windows are
+        // looked through
+        self.leave_window();
+        self.windows.close_buffer(number, area);
+        self.enter_window(self.windows.active);
+
+        self.tabs.close_buffer(number, area);
+    }
+
+    fn resize_window(&mut self, direction: Split, length: impl FnOnce(usize) -> usize) {
+        let area = self.area();
+        self.windows
+            .resize(self.windows.active, direction, area, length);
+    }
+
+    // :resize sets the height of the window, or changes it with a leading + or -
+    pub fn resize(&mut self, command: &ExCommand) -> Option<Message> {
+        let argument = command.argument.as_str();
+
+        let (sign, number) = match argument.strip_prefix(['+', '-']) {
+            Some(number) => (argument.chars().next(), number),
+            None => (None, argument),
+        };
+
+        let number = match number {
+            "" => None,
+            number => match number.parse::<usize>() {
+                Ok(number) => Some(number),
+                Err(_) => {
+                    let error = format!("Invalid argument: {argument}");
+                    return Some(Message::new_err(&error));
+                }
+            },
+        };
+
+        match (sign, number) {
+            (Some('+'), number) => {
+                self.resize_window(Split::Horizontal, |length| length + number.unwrap_or(1));
+            }
+            (Some(_), number) => self.resize_window(Split::Horizontal, |length| {
+                length.saturating_sub(number.unwrap_or(1))
+            }),
+            // The status line is part of the window, but not of the height that is asked for
+            (None, number) => self.resize_window(Split::Horizontal, |_| {
+                number.map_or(usize::MAX, |number| number + 1)
+            }),
+        }
+
+        None
+    }
+
+    // The commands that start with Ctrl-w, the count is how many windows to move or how many rows
+    // and columns to resize by
+    pub fn window_command(&mut self, key: char, count: Option<usize>) -> Option<Message> {
+        let amount = count.unwrap_or(1);
+        let total = self.windows.count();
+        let active = self.windows.active;
+
+        match key {
+            'h' | 'j' | 'k' | 'l' => {
+                for _ in 0..amount {
+                    match self.neighbour_window(key) {
+                        Some(index) => self.focus_window(index),
+                        None => break,
+                    }
+                }
+            }
+            'w' | 'W' => {
+                let index = match count {
+                    Some(number) => number.saturating_sub(1).min(total - 1),
+                    None if key == 'w' => (active + 1) % total,
+                    None => (active + total - 1) % total,
+                };
+
+                self.focus_window(index);
+            }
+            't' => self.focus_window(0),
+            'b' => self.focus_window(total - 1),
+            's' | 'S' => return self.split_window(&ExCommand::default(), Split::Horizontal),
+            'v' => return self.split_window(&ExCommand::default(), Split::Vertical),
+            'c' => return self.close_window(),
+            'q' => return self.quit(false),
+            'o' => return self.only_window(),
+            '+' => self.resize_window(Split::Horizontal, |length| length + amount),
+            '-' => self.resize_window(Split::Horizontal, |length| length.saturating_sub(amount)),
+            '>' => self.resize_window(Split::Vertical, |length| length + amount),
+            '<' => self.resize_window(Split::Vertical, |length| length.saturating_sub(amount)),
+            '_' => self.resize_window(Split::Horizontal, |_| {
+                count.map_or(usize::MAX, |count| count + 1)
+            }),
+            '|' => self.resize_window(Split::Vertical, |_| count.unwrap_or(usize::MAX)),
+            '=' => self.windows.equalize(),
+            _ => {}
+        }
+
+        None
+    }
+}
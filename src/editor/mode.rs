@@ -1,3 +1,5 @@
+use super::operator::Operator;
+
 use std::fmt::Display;
 
 #[derive(PartialEq, Clone, Copy)]
@@ -6,6 +8,30 @@ pub enum Mode {
     Normal,
     Insert,
     Command,
+    // Typing a pattern to search for after / or ?
+    Search { forward: bool },
+    // Answering whether a match should be replaced by :s with the c flag
+    Confirm,
+    // Answering what to do with changes left behind in a swap file by a crash
+    Recover,
+    // Answering what to do with a buffer that has changes when its file was changed by something
+    // else
+    Changed,
+    OperatorPending(Operator),
+    Visual,
+    VisualLine,
+    VisualBlock,
+}
+
+impl Mode {
+    pub fn is_visual(&self) -> bool {
+        matches!(self, Self::Visual | Self::VisualLine | Self::VisualBlock)
+    }
+
+    // Whether keys are typed into the command line at the bottom of the screen
+    pub fn is_prompt(&self) -> bool {
+        matches!(self, Self::Command | Self::Search { .. })
+    }
 }
 
 impl Display for Mode {
@@ -15,6 +41,14 @@ impl Display for Mode {
             Self::Normal => "Normal",
             Self::Insert => "Insert",
             Self::Command => "Command",
+            Self::Search { .. } => "Search",
+            Self::Confirm => "Confirm",
+            Self::Recover => "Recover",
+            Self::Changed => "Changed",
+            Self::OperatorPending(_) => "Pending",
+            Self::Visual => "Visual",
+            Self::VisualLine => "Visual Line",
+            Self::VisualBlock => "Visual Block",
         };
 
         write!(f, "{mode}")
@@ -0,0 +1,230 @@
+use super::buffer::cursor::{Cursor, Direction};
+use super::{Key, Position};
+
+use crate::system::size::TermSize;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Motion {
+    Left,
+    Down,
+    Up,
+    Right,
+    Top,
+    Bottom,
+    Start,
+    End,
+    WordForward { big: bool },
+    WordBackward { big: bool },
+    WordEnd { big: bool },
+    ParagraphForward,
+    ParagraphBackward,
+    SentenceForward,
+    SentenceBackward,
+    ScreenTop,
+    ScreenMiddle,
+    ScreenBottom,
+    // Goes to a line number, which is what g and G turn into when they are given a count
+    Line(usize),
+}
+
+impl Motion {
+    pub fn from_key(key: &Key) -> Option<Self> {
+        let motion = match key {
+            Key::Char('h') | Key::ArrowLeft => Self::Left,
+            Key::Char('j') | Key::ArrowDown => Self::Down,
+            Key::Char('k') | Key::ArrowUp => Self::Up,
+            Key::Char('l') | Key::ArrowRight => Self::Right,
+            Key::Char('g') => Self::Top,
+            Key::Char('G') => Self::Bottom,
+            Key::Char('f' | '0') | Key::Home => Self::Start,
+            Key::Char('F' | '$') | Key::End => Self::End,
+            Key::Char('w') => Self::WordForward { big: false },
+            Key::Char('W') => Self::WordForward { big: true },
+            Key::Char('b') => Self::WordBackward { big: false },
+            Key::Char('B') => Self::WordBackward { big: true },
+            Key::Char('e') => Self::WordEnd { big: false },
+            Key::Char('E') => Self::WordEnd { big: true },
+            Key::Char('}') => Self::ParagraphForward,
+            Key::Char('{') => Self::ParagraphBackward,
+            Key::Char(')') => Self::SentenceForward,
+            Key::Char('(') => Self::SentenceBackward,
+            Key::Char('H') => Self::ScreenTop,
+            Key::Char('M') => Self::ScreenMiddle,
+            Key::Char('L') => Self::ScreenBottom,
+            _ => return None,
+        };
+
+        Some(motion)
+    }
+
+    // Linewise motions make operators act on whole lines, no matter where on the lines the motion
+    // starts and ends
+    pub fn linewise(&self) -> bool {
+        matches!(
+            self,
+            Self::Down
+                | Self::Up
+                | Self::Top
+                | Self::Bottom
+                | Self::ScreenTop
+                | Self::ScreenMiddle
+                | Self::ScreenBottom
+                | Self::Line(_)
+        )
+    }
+
+    // Inclusive motions include the character they end on in the range an operator acts on
+    pub fn inclusive(&self) -> bool {
+        matches!(self, Self::End | Self::WordEnd { .. })
+    }
+
+    pub fn apply(&self, cursor: &mut Cursor, count: usize, size: &TermSize) {
+        let count = count.max(1);
+
+        // The screen relative motions use the count as a line number instead of a repetition
+        match self {
+            Self::ScreenTop => return cursor.screen_top(count, size),
+            Self::ScreenMiddle => return cursor.screen_middle(size),
+            Self::ScreenBottom => return cursor.screen_bottom(count, size),
+            Self::Line(y) => return cursor.goto_line(*y),
+            _ => {}
+        }
+
+        for _ in 0..count {
+            match self {
+                Self::Left => cursor.step(Direction::Left),
+                Self::Down => cursor.step(Direction::Down),
+                Self::Up => cursor.step(Direction::Up),
+                Self::Right => cursor.step(Direction::Right),
+                Self::Top => cursor.top(),
+                Self::Bottom => cursor.bottom(),
+                Self::Start => cursor.start(),
+                Self::End => cursor.end(),
+                Self::WordForward { big } => cursor.word_forward(*big),
+                Self::WordBackward { big } => cursor.word_backward(*big),
+                Self::WordEnd { big } => cursor.word_end(*big),
+                Self::ParagraphForward => cursor.paragraph_forward(),
+                Self::ParagraphBackward => cursor.paragraph_backward(),
+                Self::SentenceForward => cursor.sentence_forward(),
+                Self::SentenceBackward => cursor.sentence_backward(),
+                Self::ScreenTop | Self::ScreenMiddle | Self::ScreenBottom | Self::Line(_) => {
+                    unreachable!()
+                }
+            }
+        }
+    }
+
+    // Moves the cursor to find the target of the motion, and puts it back where it was, returning
+    // the range between the two
+    pub fn range(&self, cursor: &mut Cursor, count: usize, size: &TermSize) -> Range {
+        let origin = cursor.position;
+
+        self.apply(cursor, count, size);
+
+        let target = cursor.position;
+        cursor.position = origin;
+
+        let start = origin.min(target);
+        let mut end = origin.max(target);
+
+        let mut linewise = self.linewise();
+
+        if self.inclusive() {
+            end.x = end.x.saturating_add(1);
+        } else if !linewise && end.x == 0 && end.y > start.y {
+            // An exclusive range ending at the start of a line stops at the end of the line before
+            // instead, so dw on the last word of a line doesn't join it with the next one. When it
+            // also starts before the first non-blank, the range covers whole lines, like d} does
+            end = Position {
+                x: cursor.line_len(end.y - 1),
+                y: end.y - 1,
+            };
+
+            linewise = start.x <= cursor.first_non_blank(start.y);
+        }
+
+        Range {
+            start,
+            end,
+            linewise,
+        }
+    }
+}
+
+// The text a motion moved over, end is exclusive for charwise ranges, while linewise ranges cover
+// every line from the start to the end
+#[derive(Clone, Copy)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+    pub linewise: bool,
+}
+
+impl Range {
+    pub fn lines(start: usize, end: usize) -> Self {
+        Self {
+            start: Position { x: 0, y: start },
+            end: Position { x: 0, y: end },
+            linewise: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::buffer::cursor::tests::cursor;
+
+    const SIZE: TermSize = TermSize {
+        height: 24,
+        width: 80,
+    };
+
+    fn bounds(range: &Range) -> ((usize, usize), (usize, usize)) {
+        ((range.start.x, range.start.y), (range.end.x, range.end.y))
+    }
+
+    #[test]
+    fn range_leaves_the_cursor_in_place() {
+        let mut cursor = cursor("one two\nthree", 2, 0);
+
+        Motion::Right.range(&mut cursor, 3, &SIZE);
+
+        assert_eq!((cursor.position.x, cursor.position.y), (2, 0));
+    }
+
+    #[test]
+    fn backward_ranges_are_ordered() {
+        let mut cursor = cursor("one two\nthree", 5, 0);
+        let range = Motion::Left.range(&mut cursor, 2, &SIZE);
+
+        assert_eq!(bounds(&range), ((3, 0), (5, 0)));
+        assert!(!range.linewise);
+    }
+
+    #[test]
+    fn inclusive_ranges_cover_the_last_character() {
+        let mut cursor = cursor("one two\nthree", 4, 0);
+        let range = Motion::End.range(&mut cursor, 1, &SIZE);
+
+        assert_eq!(bounds(&range), ((4, 0), (7, 0)));
+    }
+
+    #[test]
+    fn vertical_ranges_are_linewise() {
+        let mut cursor = cursor("one\ntwo\nthree", 1, 2);
+        let range = Motion::Top.range(&mut cursor, 1, &SIZE);
+
+        assert_eq!((range.start.y, range.end.y), (0, 2));
+        assert!(range.linewise);
+    }
+
+    #[test]
+    fn word_end_ranges_are_inclusive() {
+        let mut cursor = cursor("one two", 0, 0);
+        let range = Motion::WordEnd { big: false }.range(&mut cursor, 1, &SIZE);
+
+        assert_eq!(bounds(&range), ((0, 0), (3, 0)));
+        assert!(!range.linewise);
+    }
+}
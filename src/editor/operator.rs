@@ -0,0 +1,64 @@
+use super::Key;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Dedent,
+    Lowercase,
+    Uppercase,
+    ToggleCase,
+}
+
+impl Operator {
+    pub fn from_key(key: &Key) -> Option<Self> {
+        let operator = match key {
+            Key::Char('d') => Self::Delete,
+            Key::Char('c') => Self::Change,
+            Key::Char('y') => Self::Yank,
+            Key::Char('>') => Self::Indent,
+            Key::Char('<') => Self::Dedent,
+            _ => return None,
+        };
+
+        Some(operator)
+    }
+
+    // In visual mode the operators act on the selection right away, which frees up some keys that
+    // would otherwise be motions or commands
+    pub fn from_visual_key(key: &Key) -> Option<Self> {
+        let operator = match key {
+            Key::Char('x') => Self::Delete,
+            Key::Char('s') => Self::Change,
+            Key::Char('u') => Self::Lowercase,
+            Key::Char('U') => Self::Uppercase,
+            Key::Char('~') => Self::ToggleCase,
+            key => return Self::from_key(key),
+        };
+
+        Some(operator)
+    }
+
+    // Returns None for operators that don't change the case of the text
+    pub fn convert_case(&self, text: &str) -> Option<String> {
+        let converted = match self {
+            Self::Lowercase => text.to_lowercase(),
+            Self::Uppercase => text.to_uppercase(),
+            Self::ToggleCase => text
+                .chars()
+                .map(|character| {
+                    if character.is_lowercase() {
+                        character.to_uppercase().to_string()
+                    } else {
+                        character.to_lowercase().to_string()
+                    }
+                })
+                .collect(),
+            _ => return None,
+        };
+
+        Some(converted)
+    }
+}
@@ -0,0 +1,249 @@
+use super::buffer::{
+    encoding::Encoding,
+    format::{Format, LineEnding},
+};
+
+// The settings that can be changed with :set. The format of the file is set on the buffer being
+// shown, as it belongs to that file
+pub struct Options {
+    // Highlight every match of the last search pattern
+    pub hlsearch: bool,
+    // Ignore case in patterns unless they contain an uppercase letter
+    pub smartcase: bool,
+    // The amount of spaces a line is shifted by when indenting
+    pub shiftwidth: usize,
+    // How a file is replaced when it is written
+    pub backupcopy: BackupCopy,
+    // The encodings a file is tried in when it is opened, the first one it is valid in is used
+    pub fileencodings: Vec<Encoding>,
+}
+
+// Like in vim, yes means the file is written over and no means a new file is renamed over it
+#[derive(Clone, Copy, PartialEq)]
+pub enum BackupCopy {
+    // Renames unless that would change something about the file, like its owner or its hard links
+    Auto,
+    Yes,
+    No,
+}
+
+impl BackupCopy {
+    const VALUES: [(&'static str, Self); 3] =
+        [("auto", Self::Auto), ("yes", Self::Yes), ("no", Self::No)];
+
+    fn name(self) -> &'static str {
+        Self::VALUES
+            .iter()
+            .find(|(_, value)| *value == self)
+            .map_or("auto", |(name, _)| name)
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        Self::VALUES
+            .iter()
+            .find(|(value, _)| *value == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            hlsearch: true,
+            smartcase: true,
+            shiftwidth: 4,
+            backupcopy: BackupCopy::Auto,
+            fileencodings: vec![
+                Encoding::default(),
+                Encoding::parse("latin1").unwrap_or_default(),
+            ],
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Value {
+    Bool(bool),
+    Number(usize),
+    // Options that are set to one of a few words
+    Word(&'static str),
+    // Options that are set to any text that makes sense for them
+    String(String),
+}
+
+// Every option along with its short name
+pub const NAMES: [(&str, &str); 9] = [
+    ("backupcopy", "bkc"),
+    ("bomb", "bomb"),
+    ("endofline", "eol"),
+    ("fileencoding", "fenc"),
+    ("fileencodings", "fencs"),
+    ("fileformat", "ff"),
+    ("hlsearch", "hls"),
+    ("shiftwidth", "sw"),
+    ("smartcase", "scs"),
+];
+
+fn full_name(name: &str) -> Result<&'static str, String> {
+    NAMES
+        .iter()
+        .find(|(full, short)| name == *full || name == *short)
+        .map(|(full, _)| *full)
+        .ok_or_else(|| format!("Unknown option: {name}"))
+}
+
+// The words an option can be set to, for the options that aren't switches or numbers
+fn words(name: &str) -> Vec<&'static str> {
+    match name {
+        "backupcopy" => BackupCopy::VALUES.iter().map(|(name, _)| *name).collect(),
+        "fileformat" => LineEnding::VALUES.iter().map(|(name, _)| *name).collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Whether a text is something a string option can be set to
+fn is_valid(name: &str, value: &str) -> bool {
+    match name {
+        "fileencoding" => Encoding::parse(value).is_some(),
+        "fileencodings" => value
+            .split(',')
+            .all(|label| Encoding::parse(label).is_some()),
+        _ => true,
+    }
+}
+
+impl Options {
+    fn value(&self, format: &Format, name: &str) -> Value {
+        match name {
+            "hlsearch" => Value::Bool(self.hlsearch),
+            "smartcase" => Value::Bool(self.smartcase),
+            "shiftwidth" => Value::Number(self.shiftwidth),
+            "backupcopy" => Value::Word(self.backupcopy.name()),
+            "bomb" => Value::Bool(format.bom),
+            "endofline" => Value::Bool(format.eol),
+            "fileformat" => Value::Word(format.line_ending.name()),
+            "fileencoding" => Value::String(format.encoding.name()),
+            "fileencodings" => Value::String(
+                self.fileencodings
+                    .iter()
+                    .map(|encoding| encoding.name())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    fn assign(&mut self, format: &mut Format, name: &str, value: Value) {
+        match (name, value) {
+            ("hlsearch", Value::Bool(value)) => self.hlsearch = value,
+            ("smartcase", Value::Bool(value)) => self.smartcase = value,
+            ("shiftwidth", Value::Number(value)) => self.shiftwidth = value,
+            ("backupcopy", Value::Word(value)) => {
+                self.backupcopy = BackupCopy::parse(value).unwrap_or(BackupCopy::Auto);
+            }
+            ("bomb", Value::Bool(value)) => format.bom = value,
+            ("endofline", Value::Bool(value)) => format.eol = value,
+            ("fileformat", Value::Word(value)) => {
+                format.line_ending = LineEnding::parse(value).unwrap_or(LineEnding::Unix);
+            }
+            ("fileencoding", Value::String(value)) => {
+                format.encoding = Encoding::parse(&value).unwrap_or_default();
+            }
+            ("fileencodings", Value::String(value)) => {
+                self.fileencodings = value.split(',').filter_map(Encoding::parse).collect();
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn show(&self, format: &Format, name: &str) -> String {
+        match self.value(format, name) {
+            Value::Bool(true) => format!("  {name}"),
+            Value::Bool(false) => format!("no{name}"),
+            Value::Number(number) => format!("  {name}={number}"),
+            Value::Word(word) => format!("  {name}={word}"),
+            Value::String(string) => format!("  {name}={string}"),
+        }
+    }
+
+    // Handles a single argument to :set, which is one of name, noname, invname, name!, name? or
+    // name=value, and returns the value to show when the option was only asked about
+    fn apply(&mut self, format: &mut Format, argument: &str) -> Result<Option<String>, String> {
+        if let Some((name, value)) = argument.split_once(['=', ':']) {
+            let name = full_name(name)?;
+
+            let value = match self.value(format, name) {
+                Value::Bool(_) => return Err(format!("Invalid argument: {argument}")),
+                Value::Number(_) => value
+                    .parse()
+                    .map(Value::Number)
+                    .map_err(|_| format!("Number required after =: {argument}"))?,
+                Value::Word(_) => words(name)
+                    .iter()
+                    .find(|word| **word == value)
+                    .map(|word| Value::Word(word))
+                    .ok_or_else(|| format!("Invalid argument: {argument}"))?,
+                Value::String(_) if is_valid(name, value) => Value::String(value.to_string()),
+                Value::String(_) => return Err(format!("Invalid argument: {argument}")),
+            };
+
+            self.assign(format, name, value);
+            return Ok(None);
+        }
+
+        if let Some(name) = argument.strip_suffix('?') {
+            return Ok(Some(self.show(format, full_name(name)?)));
+        }
+
+        let (name, toggle) = match argument.strip_suffix('!') {
+            Some(name) => (name, true),
+            None => match argument.strip_prefix("inv") {
+                Some(name) if full_name(name).is_ok() => (name, true),
+                _ => (argument, false),
+            },
+        };
+
+        // Options that aren't on and off switches are shown instead
+        if let Ok(name) = full_name(name) {
+            return match self.value(format, name) {
+                Value::Bool(value) => {
+                    let value = if toggle { !value } else { true };
+
+                    self.assign(format, name, Value::Bool(value));
+                    Ok(None)
+                }
+                Value::Number(_) | Value::Word(_) | Value::String(_) => {
+                    Ok(Some(self.show(format, name)))
+                }
+            };
+        }
+
+        match argument.strip_prefix("no").map(full_name) {
+            Some(Ok(name)) if matches!(self.value(format, name), Value::Bool(_)) => {
+                self.assign(format, name, Value::Bool(false));
+                Ok(None)
+            }
+            _ => Err(format!("Unknown option: {argument}")),
+        }
+    }
+
+    // Applies every argument given to :set, an empty argument lists all of the options
+    pub fn set(&mut self, format: &mut Format, arguments: &str) -> Result<Option<String>, String> {
+        if arguments.trim().is_empty() {
+            let values: Vec<String> = NAMES
+                .iter()
+                .map(|(name, _)| self.show(format, name))
+                .collect();
+            return Ok(Some(values.join("\n")));
+        }
+
+        let mut shown = Vec::new();
+
+        for argument in arguments.split_whitespace() {
+            shown.extend(self.apply(format, argument)?);
+        }
+
+        Ok((!shown.is_empty()).then(|| shown.join("  ")))
+    }
+}
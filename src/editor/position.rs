@@ -1,5 +1,20 @@
-#[derive(Default, Clone, Copy)]
+use std::cmp::Ordering;
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
 }
+
+// Positions are ordered the way they appear in the text, line first and then column
+impl Ord for Position {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.y, self.x).cmp(&(other.y, other.x))
+    }
+}
+
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
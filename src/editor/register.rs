@@ -0,0 +1,250 @@
+use crate::escape::clipboard::Selection;
+
+use std::collections::HashMap;
+
+pub const UNNAMED: char = '"';
+pub const BLACK_HOLE: char = '_';
+pub const SMALL_DELETE: char = '-';
+pub const LAST_SEARCH: char = '/';
+pub const LAST_COMMAND: char = ':';
+pub const LAST_INSERT: char = '.';
+pub const CLIPBOARD: char = '+';
+pub const PRIMARY: char = '*';
+
+const YANK: char = '0';
+const DELETE_RING: [char; 9] = ['1', '2', '3', '4', '5', '6', '7', '8', '9'];
+
+// Text that was yanked or deleted, linewise text always ends with a line break
+#[derive(Clone, Default)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool,
+}
+
+impl Register {
+    pub fn new(text: String, linewise: bool) -> Self {
+        Self { text, linewise }
+    }
+
+    fn append(&mut self, other: Self) {
+        // Appending lines to text that isn't linewise puts them on a line of their own
+        if other.linewise && !self.linewise && !self.text.is_empty() {
+            self.text.push('\n');
+        }
+
+        self.text.push_str(&other.text);
+        self.linewise |= other.linewise;
+    }
+}
+
+// The register store, which follows vim:
+//
+// "    the unnamed register, holding the last yanked or deleted text
+// 0    the last yanked text
+// 1-9  the last deleted text that spans one or more lines, shifted on every delete
+// -    the last deleted text that is within a single line
+// a-z  named registers, writing to A-Z appends to them instead
+// _    the black hole register, writing to it doesn't change anything
+// /    the last search pattern
+// :    the last command line
+// .    the last inserted text
+// + *  the system clipboard and primary selection, which are also kept here in case the clipboard
+//      can't be read from
+#[derive(Default)]
+pub struct Registers {
+    registers: HashMap<char, Register>,
+}
+
+impl Registers {
+    pub fn writable(name: char) -> bool {
+        name.is_ascii_alphanumeric()
+            || [UNNAMED, BLACK_HOLE, SMALL_DELETE, CLIPBOARD, PRIMARY].contains(&name)
+    }
+
+    pub fn readable(name: char) -> bool {
+        Self::writable(name) || [LAST_SEARCH, LAST_COMMAND, LAST_INSERT].contains(&name)
+    }
+
+    pub fn selection(name: char) -> Option<Selection> {
+        match name {
+            CLIPBOARD => Some(Selection::Clipboard),
+            PRIMARY => Some(Selection::Primary),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, name: char) -> Option<&Register> {
+        self.registers.get(&name.to_ascii_lowercase())
+    }
+
+    // Writes to a register chosen by the user, updating the unnamed register as well
+    fn write(&mut self, name: char, value: Register) {
+        if name.is_ascii_uppercase() {
+            let name = name.to_ascii_lowercase();
+
+            let register = self.registers.entry(name).or_default();
+            register.append(value);
+
+            let register = register.clone();
+            self.registers.insert(UNNAMED, register);
+        } else {
+            self.registers.insert(name, value.clone());
+            self.registers.insert(UNNAMED, value);
+        }
+    }
+
+    pub fn yank(&mut self, name: Option<char>, value: Register) {
+        match name {
+            Some(BLACK_HOLE) => {}
+            Some(name) if name != UNNAMED => self.write(name, value),
+            _ => {
+                self.registers.insert(YANK, value.clone());
+                self.registers.insert(UNNAMED, value);
+            }
+        }
+    }
+
+    pub fn delete(&mut self, name: Option<char>, value: Register) {
+        match name {
+            Some(BLACK_HOLE) => {}
+            Some(name) if name != UNNAMED => self.write(name, value),
+            _ => {
+                if value.linewise || value.text.contains('\n') {
+                    for index in (1..DELETE_RING.len()).rev() {
+                        if let Some(register) = self.registers.remove(&DELETE_RING[index - 1]) {
+                            self.registers.insert(DELETE_RING[index], register);
+                        }
+                    }
+
+                    self.registers.insert(DELETE_RING[0], value.clone());
+                } else {
+                    self.registers.insert(SMALL_DELETE, value.clone());
+                }
+
+                self.registers.insert(UNNAMED, value);
+            }
+        }
+    }
+
+    // Sets one of the read-only registers, which can't be written to by yanking or deleting
+    pub fn set(&mut self, name: char, text: String) {
+        self.registers.insert(name, Register::new(text, false));
+    }
+
+    // Every register that holds something, in the order vim lists them
+    pub fn list(&self) -> Vec<(char, &Register)> {
+        let order = [UNNAMED, YANK]
+            .into_iter()
+            .chain(DELETE_RING)
+            .chain('a'..='z')
+            .chain([
+                SMALL_DELETE,
+                LAST_INSERT,
+                LAST_COMMAND,
+                LAST_SEARCH,
+                CLIPBOARD,
+                PRIMARY,
+            ]);
+
+        order
+            .filter_map(|name| self.registers.get(&name).map(|register| (name, register)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(registers: &Registers, name: char) -> Option<&str> {
+        registers.get(name).map(|register| register.text.as_str())
+    }
+
+    fn lines(text: &str) -> Register {
+        Register::new(text.to_string(), true)
+    }
+
+    fn chars(text: &str) -> Register {
+        Register::new(text.to_string(), false)
+    }
+
+    #[test]
+    fn yank_fills_the_yank_register() {
+        let mut registers = Registers::default();
+        registers.yank(None, chars("word"));
+
+        assert_eq!(text(&registers, YANK), Some("word"));
+        assert_eq!(text(&registers, UNNAMED), Some("word"));
+    }
+
+    #[test]
+    fn deletes_shift_the_ring() {
+        let mut registers = Registers::default();
+
+        for line in ["a\n", "b\n", "c\n"] {
+            registers.delete(None, lines(line));
+        }
+
+        assert_eq!(text(&registers, '1'), Some("c\n"));
+        assert_eq!(text(&registers, '2'), Some("b\n"));
+        assert_eq!(text(&registers, '3'), Some("a\n"));
+        assert_eq!(text(&registers, SMALL_DELETE), None);
+    }
+
+    #[test]
+    fn the_ring_drops_the_oldest_delete() {
+        let mut registers = Registers::default();
+
+        for index in 0..10 {
+            registers.delete(None, lines(&format!("{index}\n")));
+        }
+
+        assert_eq!(text(&registers, '1'), Some("9\n"));
+        assert_eq!(text(&registers, '9'), Some("1\n"));
+    }
+
+    #[test]
+    fn small_deletes_skip_the_ring() {
+        let mut registers = Registers::default();
+        registers.delete(None, chars("x"));
+
+        assert_eq!(text(&registers, SMALL_DELETE), Some("x"));
+        assert_eq!(text(&registers, '1'), None);
+        assert_eq!(text(&registers, UNNAMED), Some("x"));
+    }
+
+    #[test]
+    fn uppercase_appends() {
+        let mut registers = Registers::default();
+
+        registers.yank(Some('a'), chars("one"));
+        registers.yank(Some('A'), lines("two\n"));
+
+        let register = registers.get('a').unwrap();
+
+        assert_eq!(register.text, "one\ntwo\n");
+        assert!(register.linewise);
+        assert_eq!(text(&registers, UNNAMED), Some("one\ntwo\n"));
+        assert_eq!(text(&registers, YANK), None);
+    }
+
+    #[test]
+    fn black_hole_keeps_everything() {
+        let mut registers = Registers::default();
+
+        registers.yank(None, chars("kept"));
+        registers.delete(Some(BLACK_HOLE), lines("gone\n"));
+
+        assert_eq!(text(&registers, UNNAMED), Some("kept"));
+        assert_eq!(text(&registers, BLACK_HOLE), None);
+        assert_eq!(text(&registers, '1'), None);
+    }
+
+    #[test]
+    fn read_only_registers() {
+        assert!(!Registers::writable(LAST_SEARCH));
+        assert!(Registers::readable(LAST_SEARCH));
+        assert!(Registers::writable('q'));
+        assert!(!Registers::readable('!'));
+    }
+}
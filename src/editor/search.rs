@@ -0,0 +1,365 @@
+use super::buffer::text::{Text, TextStorage};
+use super::Position;
+
+use regex::{Regex, RegexBuilder};
+use unicode_segmentation::UnicodeSegmentation;
+
+// How many lines past the bottom of the screen are searched while a pattern is being typed, so
+// that typing stays responsive in large files
+const PREVIEW_LOOKAHEAD: usize = 1000;
+
+// The last pattern that was searched for, which n and N keep searching with
+#[derive(Default)]
+pub struct Search {
+    regex: Option<Regex>,
+    pub pattern: String,
+    pub forward: bool,
+    // Cleared by :noh, until the next search
+    pub highlight: bool,
+    pub preview: Option<Preview>,
+}
+
+// The pattern that is being typed in the prompt, along with where the cursor and the view were
+// before the search started, so they can be put back when it is cancelled
+pub struct Preview {
+    regex: Option<Regex>,
+    pub position: Position,
+    pub offset: Position,
+}
+
+// Where a search ended up, and whether it had to wrap around the end of the file to get there
+pub struct Found {
+    pub position: Position,
+    pub wrapped: bool,
+}
+
+// Patterns are only case sensitive when they contain an uppercase letter, escaped characters like
+// \S don't count since they are classes rather than letters
+pub fn compile(pattern: &str, smart_case: bool) -> Result<Regex, regex::Error> {
+    let mut escaped = false;
+
+    let uppercase = pattern.chars().any(|character| {
+        let uppercase = !escaped && character.is_uppercase();
+        escaped = !escaped && character == '\\';
+
+        uppercase
+    });
+
+    RegexBuilder::new(pattern)
+        .case_insensitive(smart_case && !uppercase)
+        .build()
+}
+
+impl Search {
+    pub fn set(
+        &mut self,
+        pattern: &str,
+        forward: bool,
+        smart_case: bool,
+    ) -> Result<(), regex::Error> {
+        self.regex = Some(compile(pattern, smart_case)?);
+        self.pattern = pattern.to_string();
+        self.forward = forward;
+        self.highlight = true;
+
+        Ok(())
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.regex.is_some()
+    }
+
+    pub fn regex(&self) -> Option<&Regex> {
+        self.regex.as_ref()
+    }
+
+    pub fn begin_preview(&mut self, position: Position, offset: Position) {
+        self.preview = Some(Preview {
+            regex: None,
+            position,
+            offset,
+        });
+    }
+
+    // Finds the first match for the pattern being typed, only looking at the lines that could end up
+    // on the screen
+    pub fn update_preview(
+        &mut self,
+        pattern: &str,
+        text: &Text,
+        forward: bool,
+        height: usize,
+        smart_case: bool,
+    ) -> Option<Position> {
+        let preview = self.preview.as_mut()?;
+
+        preview.regex = if pattern.is_empty() {
+            None
+        } else {
+            compile(pattern, smart_case).ok()
+        };
+
+        let regex = preview.regex.as_ref()?;
+        let limit = height.saturating_add(PREVIEW_LOOKAHEAD);
+
+        find(regex, text, preview.position, forward, limit).map(|found| found.position)
+    }
+
+    // The pattern being typed takes over the highlighting while the prompt is open, which happens
+    // even when the highlighting of the last pattern is turned off
+    fn active(&self, hlsearch: bool) -> Option<&Regex> {
+        match &self.preview {
+            Some(preview) => preview.regex.as_ref(),
+            None if self.highlight && hlsearch => self.regex.as_ref(),
+            None => None,
+        }
+    }
+
+    // The grapheme ranges of every match that is highlighted in a line
+    pub fn highlights(&self, line: &str, hlsearch: bool) -> Vec<(usize, usize)> {
+        self.active(hlsearch)
+            .map_or_else(Vec::new, |regex| matches(regex, line))
+    }
+
+    pub fn find(&self, text: &Text, from: Position, forward: bool) -> Option<Found> {
+        let regex = self.regex.as_ref()?;
+        find(regex, text, from, forward, text.len_lines())
+    }
+}
+
+// The grapheme ranges of every match in a line, empty matches are included so that patterns like ^
+// can still be jumped to
+fn matches(regex: &Regex, line: &str) -> Vec<(usize, usize)> {
+    let boundaries: Vec<usize> = line.grapheme_indices(true).map(|(i, _)| i).collect();
+    let column = |byte: usize| boundaries.partition_point(|boundary| *boundary < byte);
+
+    regex
+        .find_iter(line)
+        .map(|found| (column(found.start()), column(found.end())))
+        .collect()
+}
+
+fn line_matches(regex: &Regex, text: &Text, y: usize) -> Vec<usize> {
+    text.line(y).map_or_else(Vec::new, |line| {
+        matches(regex, &line.to_string())
+            .into_iter()
+            .map(|(start, _)| start)
+            .collect()
+    })
+}
+
+// Finds the closest match after (or before) a position, wrapping around the end of the file, at most
+// limit lines away from the position are looked at
+fn find(regex: &Regex, text: &Text, from: Position, forward: bool, limit: usize) -> Option<Found> {
+    let lines = text.len_lines();
+
+    for step in 0..=lines.min(limit) {
+        let y = if forward {
+            (from.y + step) % lines
+        } else {
+            (from.y + lines - step % lines) % lines
+        };
+
+        let wrapped = if forward {
+            from.y + step >= lines
+        } else {
+            step > from.y
+        };
+
+        let matches = line_matches(regex, text, y);
+
+        // The line the search starts on is visited twice, once for the matches on either side of
+        // the position
+        let found = match (forward, step) {
+            (true, 0) => matches.into_iter().find(|x| *x > from.x),
+            (true, _) if step == lines => matches.into_iter().find(|x| *x <= from.x),
+            (true, _) => matches.into_iter().next(),
+            (false, 0) => matches.into_iter().rev().find(|x| *x < from.x),
+            (false, _) if step == lines => matches.into_iter().rev().find(|x| *x >= from.x),
+            (false, _) => matches.into_iter().next_back(),
+        };
+
+        if let Some(x) = found {
+            return Some(Found {
+                position: Position { x, y },
+                wrapped,
+            });
+        }
+    }
+
+    None
+}
+
+// Finds the closest line after (or before) the given one that matches, wrapping around the end of
+// the file, for patterns used as addresses in command ranges
+pub fn find_line(regex: &Regex, text: &Text, from: usize, forward: bool) -> Option<usize> {
+    let lines = text.len_lines();
+
+    (1..=lines)
+        .map(|step| {
+            if forward {
+                (from + step) % lines
+            } else {
+                (from + lines - step) % lines
+            }
+        })
+        .find(|y| {
+            text.line(*y)
+                .is_some_and(|line| regex.is_match(&line.to_string()))
+        })
+}
+
+fn is_keyword(grapheme: &str) -> bool {
+    grapheme
+        .chars()
+        .all(|character| character.is_alphanumeric() || character == '_')
+}
+
+// The keyword under the cursor, or the first one after it on the line, along with where it starts
+pub fn word_under(line: &str, x: usize) -> Option<(usize, String)> {
+    let graphemes: Vec<&str> = line.graphemes(true).collect();
+
+    let mut start = x.min(graphemes.len());
+
+    if graphemes
+        .get(start)
+        .is_some_and(|grapheme| is_keyword(grapheme))
+    {
+        while start > 0 && is_keyword(graphemes[start - 1]) {
+            start -= 1;
+        }
+    } else {
+        start += graphemes[start..]
+            .iter()
+            .position(|grapheme| is_keyword(grapheme))?;
+    }
+
+    let length = graphemes[start..]
+        .iter()
+        .take_while(|grapheme| is_keyword(grapheme))
+        .count();
+
+    Some((start, graphemes[start..start + length].concat()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(pattern: &str) -> Search {
+        let mut search = Search::default();
+        search.set(pattern, true, false).unwrap();
+
+        search
+    }
+
+    fn find(
+        search: &Search,
+        text: &str,
+        (x, y): (usize, usize),
+        forward: bool,
+    ) -> Option<(usize, usize, bool)> {
+        search
+            .find(&Text::from(text), Position { x, y }, forward)
+            .map(|found| (found.position.x, found.position.y, found.wrapped))
+    }
+
+    #[test]
+    fn matches_are_in_graphemes() {
+        let search = search("b+");
+
+        assert_eq!(search.highlights("ée bb b", true), [(3, 5), (6, 7)]);
+    }
+
+    #[test]
+    fn find_forward_skips_the_cursor() {
+        let search = search("a");
+        let text = "a a\nxa";
+
+        assert_eq!(find(&search, text, (0, 0), true), Some((2, 0, false)));
+        assert_eq!(find(&search, text, (2, 0), true), Some((1, 1, false)));
+        assert_eq!(find(&search, text, (1, 1), true), Some((0, 0, true)));
+    }
+
+    #[test]
+    fn find_backward_wraps() {
+        let search = search("a");
+        let text = "xa\na a";
+
+        assert_eq!(find(&search, text, (2, 1), false), Some((0, 1, false)));
+        assert_eq!(find(&search, text, (0, 1), false), Some((1, 0, false)));
+        assert_eq!(find(&search, text, (1, 0), false), Some((2, 1, true)));
+    }
+
+    #[test]
+    fn a_single_match_is_found_again_after_wrapping() {
+        let search = search("only");
+
+        assert_eq!(find(&search, "only\nx", (0, 0), true), Some((0, 0, true)));
+        assert_eq!(find(&search, "x\nnone", (0, 0), true), None);
+    }
+
+    #[test]
+    fn word_under_the_cursor() {
+        assert_eq!(
+            word_under("let foo_bar = 1", 6),
+            Some((4, String::from("foo_bar")))
+        );
+        assert_eq!(word_under("  (value)", 0), Some((3, String::from("value"))));
+        assert_eq!(word_under("a + ", 2), None);
+    }
+
+    #[test]
+    fn smart_case() {
+        let matches = |pattern: &str, smart_case: bool, text: &str| {
+            compile(pattern, smart_case).unwrap().is_match(text)
+        };
+
+        assert!(matches("foo", true, "FOO"));
+        assert!(!matches("Foo", true, "FOO"));
+        assert!(!matches("foo", false, "FOO"));
+
+        // Escapes like \S are classes, not uppercase letters
+        assert!(matches(r"\Sar", true, "BAR"));
+    }
+
+    #[test]
+    fn preview_takes_over_the_highlights() {
+        let mut search = search("a");
+        let text = Text::from("a\nbA");
+
+        search.begin_preview(Position::default(), Position::default());
+
+        assert!(search.highlights("a", true).is_empty());
+
+        let found = search.update_preview("A", &text, true, 10, true);
+
+        assert_eq!(found.map(|position| (position.x, position.y)), Some((1, 1)));
+        assert_eq!(search.highlights("bA", true), [(1, 2)]);
+
+        search.preview = None;
+
+        assert_eq!(search.highlights("bA", true), []);
+
+        // Turning hlsearch off only hides the last pattern, not the one being typed
+        search.set("b", true, false).unwrap();
+
+        assert_eq!(search.highlights("bA", false), []);
+
+        search.begin_preview(Position::default(), Position::default());
+        search.update_preview("b", &text, true, 10, true);
+
+        assert_eq!(search.highlights("bA", false), [(0, 1)]);
+    }
+
+    #[test]
+    fn preview_only_looks_ahead_so_far() {
+        let mut search = Search::default();
+        let text = Text::from(format!("{}x", "\n".repeat(PREVIEW_LOOKAHEAD + 5)).as_str());
+
+        search.begin_preview(Position::default(), Position::default());
+
+        assert!(search.update_preview("x", &text, true, 2, true).is_none());
+        assert!(search.update_preview("x", &text, true, 10, true).is_some());
+    }
+}
@@ -0,0 +1,485 @@
+use super::{mode::Mode, motion::Motion, operator::Operator, Key};
+
+// What a normal mode command does once all of its keys have been typed
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Motion(Motion),
+    Operator(Operator, Target),
+    Insert,
+    Put { before: bool },
+    Undo,
+    Redo,
+    Repeat,
+    Center,
+    Command,
+    // Starts or switches to one of the visual modes
+    Visual(Mode),
+    // Moves the cursor to the other end of the selection
+    SwapAnchor,
+    // Inserts text on every line of a block selection, before or after it
+    BlockInsert { append: bool },
+    // Opens the prompt for a pattern to search for
+    Search { forward: bool },
+    // Searches for the last pattern again, in the same direction or the opposite one
+    SearchNext { reverse: bool },
+    // Searches for the word under the cursor
+    SearchWord { forward: bool },
+    // Remembers the cursor position under a name, for use in command ranges
+    Mark(char),
+    // Ctrl-w followed by another key, which moves between, splits and resizes windows
+    Window(char),
+    // gt and gT, which go to the next or previous tab page
+    Tab { forward: bool },
+    // Switches to the alternate buffer, or to the one numbered by the count
+    Alternate,
+    // ZZ and ZQ, which quit with or without writing the changes first
+    Exit { write: bool },
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Target {
+    Motion(Motion),
+    // A doubled operator, such as dd or >>, which acts on whole lines
+    Line,
+    // The selection in visual mode
+    Selection,
+}
+
+// A complete normal mode command, such as "a3dw or 5j
+#[derive(Clone, Copy)]
+pub struct NormalCommand {
+    pub count: Option<usize>,
+    pub register: Option<char>,
+    pub action: Action,
+}
+
+impl NormalCommand {
+    pub fn count(&self) -> usize {
+        self.count.unwrap_or(1)
+    }
+
+    // Whether the command changes the text, and should be repeated by '.'
+    pub fn is_change(&self) -> bool {
+        match self.action {
+            Action::Operator(operator, _) => operator != Operator::Yank,
+            Action::Insert | Action::Put { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+pub enum Parsed {
+    Pending,
+    Complete(NormalCommand),
+    Invalid,
+}
+
+// Collects the keys of a normal mode command as they are typed, a command is made up of:
+//
+// ["x][count][operator][count]motion
+//
+// where both counts are multiplied together, or a single key command like 'u' or 'p'
+#[derive(Default)]
+pub struct Sequence {
+    count: Option<usize>,
+    register: Option<char>,
+    awaiting_register: bool,
+    awaiting_mark: bool,
+    awaiting_exit: bool,
+    awaiting_window: bool,
+    // The count a g that went to the top was given, which gt and gT take as theirs
+    after_g: Option<Option<usize>>,
+    operator: Option<Operator>,
+    motion_count: Option<usize>,
+}
+
+impl Sequence {
+    pub fn operator(&self) -> Option<Operator> {
+        self.operator
+    }
+
+    fn count(&self) -> Option<usize> {
+        match (self.count, self.motion_count) {
+            (Some(count), Some(motion_count)) => Some(count.saturating_mul(motion_count)),
+            (count, motion_count) => count.or(motion_count),
+        }
+    }
+
+    fn complete(&mut self, action: Action) -> Parsed {
+        let mut command = NormalCommand {
+            count: self.count(),
+            register: self.register,
+            action,
+        };
+
+        // A count in front of g or G is the line to go to, rather than a repetition
+        let target = match action {
+            Action::Motion(motion) => Some(motion),
+            Action::Operator(_, Target::Motion(motion)) => Some(motion),
+            _ => None,
+        };
+
+        if let (Some(Motion::Top | Motion::Bottom), Some(count)) = (target, command.count) {
+            let line = Motion::Line(count.saturating_sub(1));
+
+            command.count = None;
+            command.action = match action {
+                Action::Operator(operator, _) => Action::Operator(operator, Target::Motion(line)),
+                _ => Action::Motion(line),
+            };
+        }
+
+        *self = Self::default();
+
+        Parsed::Complete(command)
+    }
+
+    fn push_digit(&mut self, digit: usize) {
+        let count = if self.operator.is_some() {
+            &mut self.motion_count
+        } else {
+            &mut self.count
+        };
+
+        *count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+    }
+
+    fn has_count(&self) -> bool {
+        if self.operator.is_some() {
+            self.motion_count.is_some()
+        } else {
+            self.count.is_some()
+        }
+    }
+
+    // Handles the register and count that may come before any command
+    fn push_prefix(&mut self, key: Key) -> Option<Parsed> {
+        if self.awaiting_register {
+            self.awaiting_register = false;
+
+            return match key {
+                Key::Char(register) => {
+                    self.register = Some(register);
+                    Some(Parsed::Pending)
+                }
+                _ => Some(self.invalid()),
+            };
+        }
+
+        match key {
+            // A zero on its own is the motion to the start of the line
+            Key::Char(digit @ '0'..='9') if digit != '0' || self.has_count() => {
+                self.push_digit(digit as usize - '0' as usize);
+                Some(Parsed::Pending)
+            }
+            Key::Char('"') if self.operator.is_none() => {
+                self.awaiting_register = true;
+                Some(Parsed::Pending)
+            }
+            _ => None,
+        }
+    }
+
+    // g goes to the top right away, like it always has, but a t or T right after it switches tabs
+    // instead, with the count that was given to the g
+    fn push_tab(&mut self, key: Key, after_g: Option<Option<usize>>) -> Option<Parsed> {
+        let count = after_g?;
+
+        let forward = match key {
+            Key::Char('t') => true,
+            Key::Char('T') => false,
+            _ => return None,
+        };
+
+        *self = Self::default();
+
+        Some(Parsed::Complete(NormalCommand {
+            count,
+            register: None,
+            action: Action::Tab { forward },
+        }))
+    }
+
+    pub fn push(&mut self, key: Key) -> Parsed {
+        let after_g = self.after_g.take();
+
+        if let Some(parsed) = self.push_tab(key, after_g) {
+            return parsed;
+        }
+
+        if self.awaiting_mark {
+            return match key {
+                Key::Char(mark @ 'a'..='z') => self.complete(Action::Mark(mark)),
+                _ => self.invalid(),
+            };
+        }
+
+        if self.awaiting_exit {
+            return match key {
+                Key::Char('Z') => self.complete(Action::Exit { write: true }),
+                Key::Char('Q') => self.complete(Action::Exit { write: false }),
+                _ => self.invalid(),
+            };
+        }
+
+        if self.awaiting_window {
+            return match key {
+                // The second key can be typed with Ctrl held down as well, like Ctrl-w Ctrl-w
+                Key::Char(key) | Key::Ctrl(key) => self.complete(Action::Window(key)),
+                Key::ArrowLeft => self.complete(Action::Window('h')),
+                Key::ArrowDown => self.complete(Action::Window('j')),
+                Key::ArrowUp => self.complete(Action::Window('k')),
+                Key::ArrowRight => self.complete(Action::Window('l')),
+                _ => self.invalid(),
+            };
+        }
+
+        if let Some(parsed) = self.push_prefix(key) {
+            return parsed;
+        }
+
+        if let Some(operator) = self.operator {
+            if Operator::from_key(&key) == Some(operator) {
+                return self.complete(Action::Operator(operator, Target::Line));
+            }
+
+            return match Motion::from_key(&key) {
+                Some(motion) => self.complete(Action::Operator(operator, Target::Motion(motion))),
+                None => self.invalid(),
+            };
+        }
+
+        if let Some(operator) = Operator::from_key(&key) {
+            self.operator = Some(operator);
+            return Parsed::Pending;
+        }
+
+        if let Some(motion) = Motion::from_key(&key) {
+            let count = self.count;
+            let parsed = self.complete(Action::Motion(motion));
+
+            if key == Key::Char('g') {
+                self.after_g = Some(count);
+            }
+
+            return parsed;
+        }
+
+        let action = match key {
+            Key::Char(' ') => Action::Command,
+            Key::Char('i') => Action::Insert,
+            Key::Char('p') => Action::Put { before: false },
+            Key::Char('P') => Action::Put { before: true },
+            Key::Char('u') => Action::Undo,
+            Key::Ctrl('r') => Action::Redo,
+            Key::Char('m') => {
+                self.awaiting_mark = true;
+                return Parsed::Pending;
+            }
+            Key::Char('Z') => {
+                self.awaiting_exit = true;
+                return Parsed::Pending;
+            }
+            Key::Ctrl('w') => {
+                self.awaiting_window = true;
+                return Parsed::Pending;
+            }
+            // Ctrl-^, which terminals send the same way as Ctrl-6
+            Key::Ctrl('6') => Action::Alternate,
+            Key::Char('.') => Action::Repeat,
+            Key::Char('C') => Action::Center,
+            Key::Char('v') => Action::Visual(Mode::Visual),
+            Key::Char('V') => Action::Visual(Mode::VisualLine),
+            Key::Ctrl('v') => Action::Visual(Mode::VisualBlock),
+            Key::Char('/') => Action::Search { forward: true },
+            Key::Char('?') => Action::Search { forward: false },
+            Key::Char('n') => Action::SearchNext { reverse: false },
+            Key::Char('N') => Action::SearchNext { reverse: true },
+            Key::Char('*') => Action::SearchWord { forward: true },
+            Key::Char('#') => Action::SearchWord { forward: false },
+            _ => return self.invalid(),
+        };
+
+        self.complete(action)
+    }
+
+    // Operators act on the selection as soon as they are typed in visual mode
+    pub fn push_visual(&mut self, key: Key) -> Parsed {
+        if let Some(parsed) = self.push_prefix(key) {
+            return parsed;
+        }
+
+        if let Some(operator) = Operator::from_visual_key(&key) {
+            return self.complete(Action::Operator(operator, Target::Selection));
+        }
+
+        if let Some(motion) = Motion::from_key(&key) {
+            return self.complete(Action::Motion(motion));
+        }
+
+        let action = match key {
+            Key::Char('v') => Action::Visual(Mode::Visual),
+            Key::Char('V') => Action::Visual(Mode::VisualLine),
+            Key::Ctrl('v') => Action::Visual(Mode::VisualBlock),
+            Key::Char('o') => Action::SwapAnchor,
+            Key::Char('I') => Action::BlockInsert { append: false },
+            Key::Char('A') => Action::BlockInsert { append: true },
+            Key::Char('n') => Action::SearchNext { reverse: false },
+            Key::Char('N') => Action::SearchNext { reverse: true },
+            Key::Char('*') => Action::SearchWord { forward: true },
+            Key::Char('#') => Action::SearchWord { forward: false },
+            _ => return self.invalid(),
+        };
+
+        self.complete(action)
+    }
+
+    fn invalid(&mut self) -> Parsed {
+        *self = Self::default();
+        Parsed::Invalid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Feeds the keys of a command one at a time, all but the last one should leave it pending
+    fn parse(keys: &str) -> Option<NormalCommand> {
+        let mut sequence = Sequence::default();
+        let mut keys: Vec<Key> = keys.chars().map(Key::Char).collect();
+
+        let last = keys.pop()?;
+
+        for key in keys {
+            assert!(matches!(sequence.push(key), Parsed::Pending));
+        }
+
+        match sequence.push(last) {
+            Parsed::Complete(command) => Some(command),
+            Parsed::Pending => panic!("the command to be complete"),
+            Parsed::Invalid => None,
+        }
+    }
+
+    #[test]
+    fn single_motion() {
+        let command = parse("j").unwrap();
+
+        assert!(command.action == Action::Motion(Motion::Down));
+        assert_eq!(command.count, None);
+        assert_eq!(command.count(), 1);
+    }
+
+    #[test]
+    fn counts_are_multiplied() {
+        let command = parse("3d2w").unwrap();
+        let word = Motion::WordForward { big: false };
+
+        assert!(command.action == Action::Operator(Operator::Delete, Target::Motion(word)));
+        assert_eq!(command.count, Some(6));
+    }
+
+    #[test]
+    fn zero_is_a_motion_without_a_count() {
+        let command = parse("0").unwrap();
+        assert!(command.action == Action::Motion(Motion::Start));
+
+        assert_eq!(parse("10j").unwrap().count, Some(10));
+    }
+
+    #[test]
+    fn doubled_operator_acts_on_lines() {
+        let command = parse("\"a2yy").unwrap();
+
+        assert!(command.action == Action::Operator(Operator::Yank, Target::Line));
+        assert_eq!(command.register, Some('a'));
+        assert_eq!(command.count, Some(2));
+    }
+
+    #[test]
+    fn count_before_g_is_a_line() {
+        let command = parse("5G").unwrap();
+
+        assert!(command.action == Action::Motion(Motion::Line(4)));
+        assert_eq!(command.count, None);
+
+        let command = parse("d3G").unwrap();
+
+        assert!(
+            command.action == Action::Operator(Operator::Delete, Target::Motion(Motion::Line(2)))
+        );
+    }
+
+    #[test]
+    fn g_goes_to_the_top_right_away() {
+        assert!(parse("g").unwrap().action == Action::Motion(Motion::Top));
+        assert!(parse("3g").unwrap().action == Action::Motion(Motion::Line(2)));
+
+        let command = parse("dg").unwrap();
+        assert!(command.action == Action::Operator(Operator::Delete, Target::Motion(Motion::Top)));
+    }
+
+    #[test]
+    fn tabs_after_g() {
+        let mut sequence = Sequence::default();
+
+        assert!(matches!(sequence.push(Key::Char('3')), Parsed::Pending));
+        assert!(matches!(sequence.push(Key::Char('g')), Parsed::Complete(_)));
+
+        let Parsed::Complete(command) = sequence.push(Key::Char('t')) else {
+            panic!("the command to be complete");
+        };
+
+        assert!(command.action == Action::Tab { forward: true });
+        assert_eq!(command.count, Some(3));
+
+        // Only the key right after the g switches tabs
+        assert!(matches!(sequence.push(Key::Char('g')), Parsed::Complete(_)));
+        assert!(matches!(sequence.push(Key::Char('j')), Parsed::Complete(_)));
+        assert!(matches!(sequence.push(Key::Char('T')), Parsed::Invalid));
+    }
+
+    #[test]
+    fn invalid_keys_reset_the_sequence() {
+        let mut sequence = Sequence::default();
+
+        assert!(matches!(sequence.push(Key::Char('d')), Parsed::Pending));
+        assert!(matches!(sequence.push(Key::Char('i')), Parsed::Invalid));
+        assert!(sequence.operator().is_none());
+
+        assert!(parse("ZX").is_none());
+    }
+
+    #[test]
+    fn changes_are_repeatable() {
+        assert!(parse("dw").unwrap().is_change());
+        assert!(parse("p").unwrap().is_change());
+        assert!(!parse("yw").unwrap().is_change());
+        assert!(!parse("w").unwrap().is_change());
+    }
+
+    #[test]
+    fn visual_changes_are_repeatable() {
+        let mut sequence = Sequence::default();
+
+        let Parsed::Complete(command) = sequence.push_visual(Key::Char('d')) else {
+            panic!("the command to be complete");
+        };
+
+        assert!(command.action == Action::Operator(Operator::Delete, Target::Selection));
+        assert!(command.is_change());
+
+        let Parsed::Complete(command) = sequence.push_visual(Key::Char('y')) else {
+            panic!("the command to be complete");
+        };
+
+        assert!(!command.is_change());
+    }
+
+    #[test]
+    fn exits() {
+        assert!(parse("ZZ").unwrap().action == Action::Exit { write: true });
+        assert!(parse("ZQ").unwrap().action == Action::Exit { write: false });
+    }
+}
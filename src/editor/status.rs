@@ -1,13 +1,20 @@
 pub mod message;
 
-use super::{Command, Mode, Position};
+use super::{buffer::format::LineEnding, window::Rect, Buffer, Command, Mode, Position};
 
 use crate::escape;
 use crate::system::size::TermSize;
 
 use message::{Message, MessageVariant};
 
-pub const RESERVED_HEIGHT: u16 = 2;
+// The row below the windows, which is the command line
+pub const COMMAND_LINE_HEIGHT: u16 = 1;
+
+// The row above the windows, which is the tab line. It is only there when there is more than one
+// tab page
+pub fn tab_line_height(tabs: usize) -> u16 {
+    u16::from(tabs > 1)
+}
 
 #[derive(Default)]
 pub struct Status {
@@ -17,90 +24,246 @@ pub struct Status {
 impl Status {
     fn draw_message(&self, mode: &Mode, size: &TermSize) {
         if let Some(message) = &self.message {
-            if *mode != Mode::Command && !message.is_old() {
+            if !mode.is_prompt() && !message.is_old() {
                 let foreground = match message.variant {
                     MessageVariant::Normal => escape::color::DEFAULT_FOREGROUND,
                     MessageVariant::Error => escape::color::RED_FOREGROUND,
+                    MessageVariant::Prompt => escape::color::GREEN_FOREGROUND,
                 };
 
-                let mut message = message.data.clone();
-                message.truncate(size.width.saturating_sub(1) as usize);
+                let width = size.width.saturating_sub(1) as usize;
+                let lines: Vec<&str> = message.data.lines().collect();
+
+                // Only the end of a message that doesn't fit on the screen is shown
+                let rows = size.height as usize;
+                let lines = &lines[lines.len().saturating_sub(rows)..];
+
+                // Messages that span several lines are drawn upwards over the windows, so that the
+                // last line ends up where a message normally goes
+                let top = rows.saturating_add(1).saturating_sub(lines.len());
 
                 print!("{foreground}");
-                print!("{message}");
+
+                for (index, line) in lines.iter().enumerate() {
+                    let line: String = line.chars().take(width).collect();
+                    let goto = escape::cursor::Goto(top + index, 0);
+
+                    print!("{goto}{}{line}", escape::clear::ENTIRE_LINE);
+                }
+
                 print!("{}", escape::color::RESET);
             }
         }
     }
 
     fn draw_command(mode: &Mode, command: &Command) {
-        if *mode == Mode::Command {
-            print!(":{command}");
+        match mode {
+            Mode::Command => print!(":{command}"),
+            Mode::Search { forward: true } => print!("/{command}"),
+            Mode::Search { forward: false } => print!("?{command}"),
+            _ => {}
+        }
+    }
+
+    // Lists the completion candidates over the status line above the command line, starting from
+    // the page the selected one is on when they don't all fit
+    fn draw_wildmenu(command: &Command, size: &TermSize) {
+        let Some(wildmenu) = &command.wildmenu else {
+            return;
+        };
+
+        let width = size.width.saturating_sub(1) as usize;
+
+        let mut pages = vec![0];
+        let mut used = 0;
+
+        for (index, candidate) in wildmenu.candidates.iter().enumerate() {
+            let length = candidate.chars().count() + 2;
+
+            if used + length > width && used > 0 {
+                pages.push(index);
+                used = 0;
+            }
+
+            used += length;
+        }
+
+        let first = pages
+            .into_iter()
+            .rfind(|page| *page <= wildmenu.selected)
+            .unwrap_or(0);
+
+        let goto = escape::cursor::Goto(size.height.saturating_sub(1) as usize, 0);
+        print!("{goto}{}", escape::clear::ENTIRE_LINE);
+
+        let mut used = 0;
+
+        for (index, candidate) in wildmenu.candidates.iter().enumerate().skip(first) {
+            let length = candidate.chars().count() + 2;
+
+            if used + length > width && used > 0 {
+                break;
+            }
+
+            used += length;
+
+            if index == wildmenu.selected {
+                print!(
+                    "{}{candidate}{}  ",
+                    escape::color::INVERT,
+                    escape::color::NO_INVERT
+                );
+            } else {
+                print!("{candidate}  ");
+            }
         }
     }
 
-    fn draw_mode(mode: &Mode) {
+    fn draw_mode(mode: &Mode, width: usize) -> usize {
         let background = match mode {
             Mode::Exit => escape::color::RED_BACKGROUND,
             Mode::Normal => escape::color::BRIGHT_GREEN_BACKGROUND,
             Mode::Insert => escape::color::BRIGHT_WHITE_BACKGROUND,
             Mode::Command => escape::color::YELLOW_BACKGROUND,
+            Mode::Search { .. } => escape::color::BRIGHT_YELLOW_BACKGROUND,
+            Mode::Confirm | Mode::Recover | Mode::Changed => escape::color::BRIGHT_RED_BACKGROUND,
+            Mode::OperatorPending(_) => escape::color::BRIGHT_CYAN_BACKGROUND,
+            Mode::Visual => escape::color::MAGENTA_BACKGROUND,
+            Mode::VisualLine => escape::color::BRIGHT_MAGENTA_BACKGROUND,
+            Mode::VisualBlock => escape::color::BRIGHT_BLUE_BACKGROUND,
         };
 
         print!("{background}");
         print!("{}", escape::color::BLACK_FOREGROUND);
 
-        print!(" {} ", mode.to_string().to_uppercase());
+        let text = fit(&format!(" {} ", mode.to_string().to_uppercase()), width);
+
+        print!("{text}");
         print!("{}", escape::color::RESET);
+
+        text.chars().count()
     }
 
-    fn draw_file_name(file_name: String) {
+    fn draw_file_name(buffer: &Buffer, width: usize) -> usize {
+        let modified = if buffer.is_modified() { "[+] " } else { "" };
+
+        // The format is only shown when it isn't the usual one
+        let format = &buffer.format;
+        let encoding = if format.encoding.is_utf8() {
+            String::new()
+        } else {
+            format!("[{}] ", format.encoding.name())
+        };
+        let line_ending = if format.line_ending == LineEnding::Dos {
+            "[dos] "
+        } else {
+            ""
+        };
+        let eol = if format.eol { "" } else { "[noeol] " };
+        let bom = if format.bom { "[bom] " } else { "" };
+
+        let text = format!(
+            " [{}] {} {modified}{encoding}{line_ending}{eol}{bom}",
+            buffer.number,
+            buffer.file_name()
+        );
+        let text = fit(&text, width);
+
         print!("{}", escape::color::BRIGHT_BLACK_FOREGROUND);
-        print!(" {file_name} ");
+        print!("{text}");
         print!("{}", escape::color::RESET);
-    }
 
-    fn draw_position(position: &Position, size: &TermSize) {
-        print!("{}", escape::color::DEFAULT_BACKGROUND);
-        print!("{}", escape::color::BRIGHT_BLACK_FOREGROUND);
+        text.chars().count()
+    }
 
+    // The position is left out when it doesn't fit next to the file name
+    fn draw_position(position: &Position, row: usize, rect: &Rect, used: usize) {
         let position = format!(
             " {}:{} ",
             position.y.saturating_add(1),
             position.x.saturating_add(1)
         );
 
-        let goto = escape::cursor::Goto(
-            size.height as usize,
-            (size.width as usize).saturating_sub(position.len()),
-        );
+        if used + position.len() > rect.width {
+            return;
+        }
+
+        print!("{}", escape::color::DEFAULT_BACKGROUND);
+        print!("{}", escape::color::BRIGHT_BLACK_FOREGROUND);
+
+        let goto = escape::cursor::Goto(row, rect.x + rect.width - position.len() + 1);
 
         print!("{goto}{position}");
         print!("{}", escape::color::RESET);
     }
 
-    pub fn draw(
-        &self,
-        size: &TermSize,
-        mode: &Mode,
-        file_name: String,
-        position: &Position,
-        command: &Command,
-    ) {
-        let height = size.height.saturating_add(1);
-        let width = size.width.saturating_add(1);
+    // Every window has a status line below it, only the one being used shows the mode
+    pub fn draw_window(mode: Option<&Mode>, buffer: &Buffer, position: &Position, rect: &Rect) {
+        let row = rect.y + rect.height;
+        print!("{}", escape::cursor::Goto(row, rect.x + 1));
 
-        let size = &TermSize { height, width };
+        let mut used = 0;
 
-        let goto = escape::cursor::Goto(size.height as usize, 0);
+        if let Some(mode) = mode {
+            used += Self::draw_mode(mode, rect.width);
+        }
+
+        used += Self::draw_file_name(buffer, rect.width.saturating_sub(used));
+        Self::draw_position(position, row, rect, used);
+    }
+
+    // Lists the tab pages across the first row, the current one is highlighted
+    pub fn draw_tab_line(labels: &[String], active: usize, width: usize) {
+        let goto = escape::cursor::Goto(1, 0);
         print!("{goto}{}", escape::clear::ENTIRE_LINE);
-        Self::draw_mode(mode);
-        Self::draw_file_name(file_name);
-        Self::draw_position(position, size);
 
-        let goto = escape::cursor::Goto(size.height.saturating_add(1) as usize, 0);
+        let mut used = 0;
+
+        for (index, label) in labels.iter().enumerate() {
+            let text = fit(&format!(" {label} "), width.saturating_sub(used));
+
+            if index == active {
+                print!(
+                    "{}{text}{}",
+                    escape::color::INVERT,
+                    escape::color::NO_INVERT
+                );
+            } else {
+                print!("{}", escape::color::BRIGHT_BLACK_FOREGROUND);
+                print!("{text}");
+                print!("{}", escape::color::RESET);
+            }
+
+            used += text.chars().count();
+        }
+    }
+
+    // Draws the command line at the bottom of the screen, along with the message or the prompt
+    pub fn draw(&self, size: &TermSize, mode: &Mode, command: &Command) {
+        let width = size.width.saturating_add(1);
+
+        let size = &TermSize {
+            height: size.height,
+            width,
+        };
+
+        let goto = escape::cursor::Goto(size.height as usize, 0);
         print!("{goto}{}", escape::clear::ENTIRE_LINE);
         Self::draw_command(mode, command);
         self.draw_message(mode, size);
+
+        if mode.is_prompt() {
+            Self::draw_wildmenu(command, size);
+
+            // The prompt character takes up the first column
+            let column = command.cursor_column() + 2;
+            let goto = escape::cursor::Goto(size.height as usize, column);
+
+            print!("{goto}");
+        }
     }
 }
+
+fn fit(text: &str, width: usize) -> String {
+    text.chars().take(width).collect()
+}
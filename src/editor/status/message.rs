@@ -6,6 +6,8 @@ const OLD_DURATION: Duration = Duration::from_secs(5);
 pub enum MessageVariant {
     Normal,
     Error,
+    // A question that is waiting for an answer, which stays around until it is answered
+    Prompt,
 }
 
 #[derive(Clone)]
@@ -36,7 +38,21 @@ impl Message {
         }
     }
 
+    pub fn new_prompt(data: &str) -> Self {
+        let data = data.to_string();
+
+        Self {
+            data,
+            variant: MessageVariant::Prompt,
+            time: Instant::now(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self.variant, MessageVariant::Error)
+    }
+
     pub fn is_old(&self) -> bool {
-        Instant::now() - self.time > OLD_DURATION
+        !matches!(self.variant, MessageVariant::Prompt) && Instant::now() - self.time > OLD_DURATION
     }
 }
@@ -0,0 +1,400 @@
+use super::buffer::Buffer;
+use super::search;
+use super::Position;
+
+use regex::{Regex, RegexBuilder};
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Default)]
+pub struct Flags {
+    // Replace every match in a line instead of only the first one
+    pub global: bool,
+    pub ignore_case: bool,
+    // Ask before every replacement
+    pub confirm: bool,
+    // Only count the matches, without replacing anything
+    pub count: bool,
+}
+
+// A match in the text, given as byte offsets into its line along with the graphemes it covers
+pub struct Match {
+    pub y: usize,
+    start: usize,
+    end: usize,
+    pub columns: (usize, usize),
+}
+
+// A substitution that is going through the lines it was given, one match at a time so that it can
+// stop to ask in confirm mode
+pub struct Substitute {
+    regex: Regex,
+    pub pattern: String,
+    // The replacement as it was typed, and converted to the syntax the regex crate expands
+    pub replacement: String,
+    expansion: String,
+    pub flags: Flags,
+    y: usize,
+    x: usize,
+    end: usize,
+    // Set after an empty match, which would otherwise be found at the same place again
+    step: bool,
+    // Set after a match that wasn't empty, since an empty match right after it isn't counted
+    adjacent: bool,
+    matches: usize,
+    substitutions: usize,
+    lines: usize,
+    pub last_line: Option<usize>,
+    pub current: Option<Match>,
+}
+
+// Splits off the text up to the next delimiter, a delimiter preceded by a backslash is part of the
+// text instead
+fn split(chars: &mut std::str::Chars, delimiter: char) -> String {
+    let mut part = String::new();
+
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter => part.push(next),
+                Some(next) => {
+                    part.push('\\');
+                    part.push(next);
+                }
+                None => part.push('\\'),
+            },
+            _ if character == delimiter => break,
+            _ => part.push(character),
+        }
+    }
+
+    part
+}
+
+// Converts the replacement from vim's syntax, where \1 and & refer to the captures, to the one used
+// by the regex crate. Like in vim, \r breaks the line and \n inserts a NUL
+fn expansion(replacement: &str) -> String {
+    let mut expansion = String::new();
+    let mut chars = replacement.chars();
+
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' => match chars.next() {
+                Some(digit @ '0'..='9') => expansion.push_str(&format!("${{{digit}}}")),
+                Some('r') => expansion.push('\n'),
+                Some('n') => expansion.push('\0'),
+                Some('t') => expansion.push('\t'),
+                Some('$') => expansion.push_str("$$"),
+                Some(other) => expansion.push(other),
+                None => expansion.push('\\'),
+            },
+            '&' => expansion.push_str("${0}"),
+            '$' => expansion.push_str("$$"),
+            _ => expansion.push(character),
+        }
+    }
+
+    expansion
+}
+
+fn plural(count: usize, one: &str, many: &str) -> String {
+    if count == 1 {
+        format!("{count} {one}")
+    } else {
+        format!("{count} {many}")
+    }
+}
+
+impl Substitute {
+    // Parses the argument of :s, which looks like /pattern/replacement/flags, where any character
+    // that isn't a letter or a digit can be used in place of the slashes. An empty pattern uses the
+    // last search pattern instead
+    pub fn new(
+        argument: &str,
+        last_pattern: Option<&str>,
+        start: usize,
+        end: usize,
+        smart_case: bool,
+    ) -> Result<Self, String> {
+        let mut chars = argument.chars();
+
+        let delimiter = chars
+            .next()
+            .filter(|delimiter| !delimiter.is_alphanumeric() && !delimiter.is_whitespace())
+            .filter(|delimiter| !['\\', '"', '|'].contains(delimiter))
+            .ok_or_else(|| String::from("Invalid substitute"))?;
+
+        let mut pattern = split(&mut chars, delimiter);
+        let replacement = split(&mut chars, delimiter);
+
+        let mut flags = Flags::default();
+
+        for flag in chars {
+            match flag {
+                'g' => flags.global = true,
+                'i' => flags.ignore_case = true,
+                'c' => flags.confirm = true,
+                'n' => flags.count = true,
+                _ => return Err(format!("Trailing characters: {flag}")),
+            }
+        }
+
+        if pattern.is_empty() {
+            pattern = last_pattern
+                .ok_or_else(|| String::from("No previous regular expression"))?
+                .to_string();
+        }
+
+        let regex = if flags.ignore_case {
+            RegexBuilder::new(&pattern).case_insensitive(true).build()
+        } else {
+            search::compile(&pattern, smart_case)
+        };
+
+        let regex = regex.map_err(|_| format!("Invalid pattern: {pattern}"))?;
+
+        Ok(Self {
+            regex,
+            pattern,
+            expansion: expansion(&replacement),
+            replacement,
+            flags,
+            y: start,
+            x: 0,
+            end,
+            step: false,
+            adjacent: false,
+            matches: 0,
+            substitutions: 0,
+            lines: 0,
+            last_line: None,
+            current: None,
+        })
+    }
+
+    // Finds the next match that hasn't been dealt with yet
+    pub fn next(&mut self, buffer: &Buffer) -> Option<Match> {
+        while self.y <= self.end && self.y < buffer.line_count() {
+            let line = buffer.line(self.y).map(|line| line.to_string());
+            let line = line.unwrap_or_default();
+
+            if self.step {
+                self.step = false;
+                self.x += line[self.x..].chars().next().map_or(1, char::len_utf8);
+            }
+
+            if let Some(found) = line.get(self.x..).and(self.regex.find_at(&line, self.x)) {
+                if found.is_empty() && found.start() == self.x && self.adjacent {
+                    self.adjacent = false;
+                    self.step = true;
+                    continue;
+                }
+
+                let column = |byte: usize| line[..byte].graphemes(true).count();
+
+                self.matches += 1;
+
+                return Some(Match {
+                    y: self.y,
+                    start: found.start(),
+                    end: found.end(),
+                    columns: (column(found.start()), column(found.end())),
+                });
+            }
+
+            self.y += 1;
+            self.x = 0;
+            self.adjacent = false;
+        }
+
+        None
+    }
+
+    // Moves past a match, which may have been replaced with some text
+    fn advance(&mut self, found: &Match, replaced: Option<&str>) {
+        let newlines = replaced.map_or(0, |text| text.matches('\n').count());
+
+        self.y += newlines;
+        self.end += newlines;
+
+        // Only the first match in a line is looked at without the g flag
+        if !self.flags.global {
+            self.y += 1;
+            self.x = 0;
+            return;
+        }
+
+        self.x = match replaced {
+            Some(text) => match text.rfind('\n') {
+                Some(index) => text.len() - index - 1,
+                None => found.start + text.len(),
+            },
+            None => found.end,
+        };
+
+        self.step = found.start == found.end;
+        self.adjacent = !self.step;
+    }
+
+    fn count_line(&mut self, y: usize) {
+        if self.last_line != Some(y) {
+            self.lines += 1;
+            self.last_line = Some(y);
+        }
+    }
+
+    pub fn skip(&mut self, found: &Match) {
+        self.advance(found, None);
+    }
+
+    pub fn replace(&mut self, buffer: &mut Buffer, found: &Match) {
+        let line = buffer.line(found.y).map(|line| line.to_string());
+        let line = line.unwrap_or_default();
+
+        let mut text = String::new();
+
+        if let Some(captures) = self.regex.captures_at(&line, found.start) {
+            captures.expand(&self.expansion, &mut text);
+        }
+
+        let base = buffer.char_index(Position { x: 0, y: found.y });
+        let start = base + line[..found.start].chars().count();
+        let end = base + line[..found.end].chars().count();
+
+        buffer.remove(start, end);
+        buffer.insert_str(start, &text);
+
+        self.substitutions += 1;
+        self.count_line(found.y);
+
+        self.advance(found, Some(&text));
+    }
+
+    // Replaces (or counts) every match that is left without asking
+    pub fn run(&mut self, buffer: &mut Buffer) {
+        while let Some(found) = self.next(buffer) {
+            if self.flags.count {
+                self.count_line(found.y);
+                self.skip(&found);
+            } else {
+                self.replace(buffer, &found);
+            }
+        }
+    }
+
+    pub fn report(&self) -> Result<Option<String>, String> {
+        if self.matches == 0 {
+            return Err(format!("Pattern not found: {}", self.pattern));
+        }
+
+        if self.flags.count {
+            let matches = plural(self.matches, "match", "matches");
+            let lines = plural(self.lines, "line", "lines");
+
+            return Ok(Some(format!("{matches} on {lines}")));
+        }
+
+        if self.substitutions == 0 {
+            return Ok(None);
+        }
+
+        let substitutions = plural(self.substitutions, "substitution", "substitutions");
+        let lines = plural(self.lines, "line", "lines");
+
+        Ok(Some(format!("{substitutions} on {lines}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replace(pattern: &str, replacement: &str, line: &str) -> String {
+        let regex = Regex::new(pattern).expect("a valid pattern");
+        let captures = regex.captures(line).expect("a match");
+
+        let mut text = String::new();
+        captures.expand(&expansion(replacement), &mut text);
+
+        text
+    }
+
+    fn substitute(argument: &str) -> Result<Substitute, String> {
+        Substitute::new(argument, Some("last"), 0, 0, false)
+    }
+
+    #[test]
+    fn whole_match() {
+        assert_eq!(replace("b+", "<&>", "abbc"), "<bb>");
+    }
+
+    #[test]
+    fn captures() {
+        assert_eq!(replace("(a)(b)", r"\2\1\0", "ab"), "baab");
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(replace("a", r"\&\\", "a"), "&\\");
+        assert_eq!(replace("a", r"\t", "a"), "\t");
+    }
+
+    #[test]
+    fn line_breaks() {
+        assert_eq!(replace("a", r"x\ry", "a"), "x\ny");
+        assert_eq!(replace("a", r"x\ny", "a"), "x\0y");
+    }
+
+    #[test]
+    fn dollars_are_literal() {
+        assert_eq!(replace("(a)", "$1", "a"), "$1");
+        assert_eq!(replace("(a)", r"\$", "a"), "$");
+    }
+
+    #[test]
+    fn parts() {
+        let substitute = substitute("#a/b#c\\#d#").expect("to parse");
+
+        assert_eq!(substitute.pattern, "a/b");
+        assert_eq!(substitute.replacement, "c#d");
+    }
+
+    #[test]
+    fn flags() {
+        let flags = substitute("/a/b/gicn").expect("to parse").flags;
+
+        assert!(flags.global);
+        assert!(flags.ignore_case);
+        assert!(flags.confirm);
+        assert!(flags.count);
+
+        let flags = substitute("/a/b").expect("to parse").flags;
+
+        assert!(!flags.global);
+        assert!(!flags.count);
+    }
+
+    #[test]
+    fn invalid_flag() {
+        assert_eq!(
+            substitute("/a/b/x").err(),
+            Some(String::from("Trailing characters: x"))
+        );
+    }
+
+    #[test]
+    fn invalid_delimiter() {
+        assert!(substitute("a").is_err());
+        assert!(substitute("\\a\\b\\").is_err());
+    }
+
+    #[test]
+    fn last_pattern() {
+        assert_eq!(substitute("//b/").expect("to parse").pattern, "last");
+        assert!(Substitute::new("//b/", None, 0, 0, false).is_err());
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        assert!(substitute("/(/b/").is_err());
+    }
+}
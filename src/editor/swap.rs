@@ -0,0 +1,386 @@
+use super::buffer::{
+    text::{Text, TextStorage},
+    Buffer,
+};
+
+use crate::system::{dirs, file, process};
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Result,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+const SWAP_DIRECTORY: &str = "swap";
+const HEADER: &str = "alice-swap 1";
+
+// The text is written once it has stopped changing for a moment, or after a while when it keeps
+// changing, so that typing doesn't write the whole file on every key
+const IDLE: Duration = Duration::from_secs(1);
+const MAX_WAIT: Duration = Duration::from_secs(10);
+
+// Every alice process keeps a swap file for each file it has open, named after the full path of
+// the file like undo files are, followed by the process id:
+//
+// alice-swap 1
+// <pid> <clean or modified>
+// <text, when modified>
+fn prefix(path: &Path) -> String {
+    format!("{}.", dirs::state_name(path))
+}
+
+fn swap_name(path: &Path) -> String {
+    format!("{}{}.swp", prefix(path), std::process::id())
+}
+
+// The swap file is replaced as a whole, so that a crash while writing it leaves the one from before
+fn write(swap: &Path, text: Option<&Text>) -> Result<()> {
+    let state = if text.is_some() { "modified" } else { "clean" };
+
+    file::replace(swap, |mut file| {
+        writeln!(file, "{HEADER}")?;
+        writeln!(file, "{} {state}", std::process::id())?;
+
+        match text {
+            Some(text) => text.write_to(&mut file),
+            None => Ok(0),
+        }
+    })?;
+
+    Ok(())
+}
+
+// Changes that were never written, left behind by an alice process that is gone
+pub struct Crashed {
+    pub pid: u32,
+    pub text: String,
+    swap: PathBuf,
+}
+
+impl Crashed {
+    pub fn remove(&self) {
+        _ = fs::remove_file(&self.swap);
+    }
+}
+
+// The swap files of other alice processes for the same file
+pub enum Other {
+    // The file is open in another alice process right now
+    Running(u32),
+    Crashed(Crashed),
+}
+
+fn read(swap: PathBuf, pid: u32) -> Option<Other> {
+    if process::is_running(pid) {
+        return Some(Other::Running(pid));
+    }
+
+    let data = fs::read_to_string(&swap).ok()?;
+
+    // A process that had no changes left nothing to recover, so its swap file is only in the way
+    let Some(text) = parse(&data)? else {
+        _ = fs::remove_file(&swap);
+        return None;
+    };
+
+    Some(Other::Crashed(Crashed {
+        pid,
+        text: text.to_string(),
+        swap,
+    }))
+}
+
+// The changes in a swap file, nothing when the process that wrote it had none, or None when it
+// isn't a swap file at all
+fn parse(data: &str) -> Option<Option<&str>> {
+    let (header, rest) = data.split_once('\n')?;
+    let (state, text) = rest.split_once('\n').unwrap_or((rest, ""));
+
+    if header != HEADER {
+        return None;
+    }
+
+    Some((!state.ends_with(" clean")).then_some(text))
+}
+
+pub fn others(path: &Path) -> Vec<Other> {
+    let Ok(directory) = dirs::state_subdirectory(SWAP_DIRECTORY) else {
+        return Vec::new();
+    };
+
+    let Ok(entries) = fs::read_dir(directory) else {
+        return Vec::new();
+    };
+
+    let prefix = prefix(path);
+
+    let mut others: Vec<Other> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            let pid = name.strip_prefix(&prefix)?.strip_suffix(".swp")?;
+            let pid: u32 = pid.parse().ok()?;
+
+            (pid != std::process::id()).then(|| read(entry.path(), pid))?
+        })
+        .collect();
+
+    others.sort_by_key(|other| match other {
+        Other::Running(pid) => *pid,
+        Other::Crashed(crashed) => crashed.pid,
+    });
+
+    others
+}
+
+// The lines from the first to the last one that differ between the file and the swap file, cut
+// off at a number of lines so that they fit on the screen
+pub fn diff(file: &str, swap: &str, limit: usize) -> String {
+    let old: Vec<&str> = file.split('\n').collect();
+    let new: Vec<&str> = swap.split('\n').collect();
+
+    let start = old
+        .iter()
+        .zip(&new)
+        .take_while(|(old, new)| old == new)
+        .count();
+
+    let end = old[start..]
+        .iter()
+        .rev()
+        .zip(new[start..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+
+    let mut lines = vec![format!("@@ line {} @@", start + 1)];
+
+    lines.extend(
+        old[start..old.len() - end]
+            .iter()
+            .map(|line| format!("-{line}")),
+    );
+    lines.extend(
+        new[start..new.len() - end]
+            .iter()
+            .map(|line| format!("+{line}")),
+    );
+
+    if lines.len() > limit {
+        let hidden = lines.len() - limit.saturating_sub(1);
+
+        lines.truncate(limit.saturating_sub(1));
+        lines.push(format!("... {hidden} more lines"));
+    }
+
+    lines.join("\n")
+}
+
+enum Request {
+    // The text is left out when the buffer has no changes
+    Write(PathBuf, Option<Text>),
+    Remove(PathBuf),
+    Finish,
+}
+
+struct Pending {
+    text: Option<Text>,
+    first: Instant,
+    last: Instant,
+}
+
+impl Pending {
+    fn due(&self) -> Instant {
+        (self.last + IDLE).min(self.first + MAX_WAIT)
+    }
+}
+
+// Writes the swap files on a thread of its own, so that saving them never holds up typing
+fn work(receiver: &Receiver<Request>) {
+    let mut pending: HashMap<PathBuf, Pending> = HashMap::new();
+
+    loop {
+        let request = match pending.values().map(Pending::due).min() {
+            Some(due) => receiver.recv_timeout(due.saturating_duration_since(Instant::now())),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+
+        let now = Instant::now();
+
+        match request {
+            Ok(Request::Write(swap, text)) => {
+                let first = pending.get(&swap).map_or(now, |pending| pending.first);
+
+                pending.insert(
+                    swap,
+                    Pending {
+                        text,
+                        first,
+                        last: now,
+                    },
+                );
+            }
+            Ok(Request::Remove(swap)) => {
+                pending.remove(&swap);
+                _ = fs::remove_file(&swap);
+            }
+            Ok(Request::Finish) | Err(RecvTimeoutError::Disconnected) => return,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        pending.retain(|swap, pending| {
+            if pending.due() > now {
+                return true;
+            }
+
+            _ = write(swap, pending.text.as_ref());
+            false
+        });
+    }
+}
+
+struct Tracked {
+    // The file the buffer belonged to when its swap file was written
+    path: PathBuf,
+    swap: PathBuf,
+    revision: usize,
+    modified: bool,
+}
+
+// Keeps the swap files of the open buffers up to date with their text
+pub struct Journal {
+    sender: Sender<Request>,
+    worker: Option<JoinHandle<()>>,
+    // Where the swap files go, nothing when the state directory can't be made
+    directory: Option<PathBuf>,
+    // The swap file of every buffer that has been shown, by buffer number
+    tracked: HashMap<usize, Tracked>,
+}
+
+impl Journal {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let worker = thread::spawn(move || work(&receiver));
+
+        Self {
+            sender,
+            worker: Some(worker),
+            directory: dirs::state_subdirectory(SWAP_DIRECTORY).ok(),
+            tracked: HashMap::new(),
+        }
+    }
+
+    // Sends the text of a buffer off to its swap file when it has changed, returns true the first
+    // time a buffer is seen, which is when the swap files of other processes should be looked at.
+    // This runs after every key, so nothing is looked up when the buffer hasn't changed
+    pub fn update(&mut self, buffer: &Buffer) -> bool {
+        let path = buffer.path();
+        let revision = buffer.revision();
+        let modified = buffer.is_modified();
+
+        let first = match self.tracked.get(&buffer.number) {
+            Some(tracked) if tracked.path != path => {
+                _ = self.sender.send(Request::Remove(tracked.swap.clone()));
+                true
+            }
+            Some(tracked) if tracked.revision == revision && tracked.modified == modified => {
+                return false;
+            }
+            Some(_) => false,
+            None => true,
+        };
+
+        let Some(directory) = &self.directory else {
+            return false;
+        };
+
+        let swap = directory.join(swap_name(path));
+
+        let text = modified.then(|| buffer.text().snapshot());
+        _ = self.sender.send(Request::Write(swap.clone(), text));
+
+        self.tracked.insert(
+            buffer.number,
+            Tracked {
+                path: path.to_path_buf(),
+                swap,
+                revision,
+                modified,
+            },
+        );
+
+        first
+    }
+
+    pub fn remove(&mut self, number: usize) {
+        if let Some(tracked) = self.tracked.remove(&number) {
+            _ = self.sender.send(Request::Remove(tracked.swap));
+        }
+    }
+
+    // Removes every swap file, waiting for them to be gone, when the editor quits normally
+    pub fn finish(&mut self) {
+        for (_, tracked) in self.tracked.drain() {
+            _ = self.sender.send(Request::Remove(tracked.swap));
+        }
+
+        _ = self.sender.send(Request::Finish);
+
+        if let Some(worker) = self.worker.take() {
+            _ = worker.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modified() {
+        let data = format!("{HEADER}\n42 modified\nfirst\n\nlast");
+
+        assert_eq!(parse(&data), Some(Some("first\n\nlast")));
+    }
+
+    #[test]
+    fn modified_to_nothing() {
+        assert_eq!(parse(&format!("{HEADER}\n42 modified\n")), Some(Some("")));
+        assert_eq!(parse(&format!("{HEADER}\n42 modified")), Some(Some("")));
+    }
+
+    #[test]
+    fn clean() {
+        assert_eq!(parse(&format!("{HEADER}\n42 clean\n")), Some(None));
+    }
+
+    #[test]
+    fn not_a_swap_file() {
+        assert_eq!(parse(""), None);
+        assert_eq!(parse(HEADER), None);
+        assert_eq!(parse("alice-swap 2\n42 modified\ntext"), None);
+    }
+
+    #[test]
+    fn diff_shows_the_changed_lines() {
+        let diff = diff("a\nb\nc\nd", "a\nB\nC\nd", 10);
+
+        assert_eq!(diff, "@@ line 2 @@\n-b\n-c\n+B\n+C");
+    }
+
+    #[test]
+    fn diff_of_added_lines() {
+        assert_eq!(diff("a\nb", "a\nnew\nb", 10), "@@ line 2 @@\n+new");
+    }
+
+    #[test]
+    fn long_diffs_are_cut_off() {
+        let diff = diff("a\nb\nc", "x\ny\nz", 4);
+
+        assert_eq!(diff, "@@ line 1 @@\n-a\n-b\n... 4 more lines");
+    }
+}
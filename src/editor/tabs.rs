@@ -0,0 +1,101 @@
+use super::window::{Rect, Windows};
+
+// The tab pages that are open but not being shown, each with its own layout of windows. The layout
+// of the tab page being shown is owned by the editor itself, like the buffer being shown
+#[derive(Default)]
+pub struct Tabs {
+    // Kept in the order the tab pages are shown in
+    hidden: Vec<Windows>,
+    // Where the tab page being shown sits between the hidden ones, counting from the left
+    pub active: usize,
+}
+
+impl Tabs {
+    pub fn count(&self) -> usize {
+        self.hidden.len() + 1
+    }
+
+    // Every layout including the current one, in the order they are shown in
+    pub fn all<'a>(&'a self, current: &'a Windows) -> Vec<&'a Windows> {
+        let mut all: Vec<&Windows> = self.hidden.iter().collect();
+        all.insert(self.active, current);
+
+        all
+    }
+
+    // Shows a new tab page right after the current one
+    pub fn open(&mut self, current: &mut Windows, windows: Windows) {
+        let previous = std::mem::replace(current, windows);
+
+        self.hidden.insert(self.active, previous);
+        self.active += 1;
+    }
+
+    // Shows the tab page at an index in place of the current one, which is hidden where it was
+    pub fn switch(&mut self, current: &mut Windows, index: usize) {
+        if index == self.active || index >= self.count() {
+            return;
+        }
+
+        let position = if index > self.active {
+            index - 1
+        } else {
+            index
+        };
+
+        let windows = self.hidden.remove(position);
+
+        let previous = std::mem::replace(current, windows);
+        let position = if index > self.active {
+            self.active
+        } else {
+            self.active - 1
+        };
+
+        self.hidden.insert(position, previous);
+        self.active = index;
+    }
+
+    // Replaces the current tab page with the one after it, or the one before it when it was the
+    // last, returning false when there is no other tab page
+    pub fn close(&mut self, current: &mut Windows) -> bool {
+        if self.hidden.is_empty() {
+            return false;
+        }
+
+        if self.active == self.hidden.len() {
+            self.active -= 1;
+        }
+
+        *current = self.hidden.remove(self.active);
+
+        true
+    }
+
+    pub fn only(&mut self) {
+        self.hidden.clear();
+        self.active = 0;
+    }
+
+    // Closes the windows on a buffer that is being deleted, along with the tab pages that have
+    // nothing else to show
+    pub fn close_buffer(&mut self, number: usize, area: Rect) {
+        for windows in &mut self.hidden {
+            windows.close_buffer(number, area);
+        }
+
+        let mut index = 0;
+
+        while index < self.hidden.len() {
+            if self.hidden[index].count() == 1 && self.hidden[index].windows()[0].buffer == number {
+                self.hidden.remove(index);
+
+                if index < self.active {
+                    self.active -= 1;
+                }
+            } else {
+                index += 1;
+            }
+        }
+    }
+}
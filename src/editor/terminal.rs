@@ -1,5 +1,3 @@
-use super::status::RESERVED_HEIGHT;
-
 use crate::alternate::{AlternateBuffer, IntoAlternateBuffer};
 use crate::raw::{IntoRawMode, RawTerminal};
 use crate::system::size::{self, TermSize};
@@ -7,6 +5,7 @@ use crate::system::size::{self, TermSize};
 use std::io::{self, Result, Stdout, Write};
 
 pub struct Terminal {
+    // The size of the whole screen, the editor decides how much of it the windows get
     pub size: TermSize,
     _out: RawTerminal<AlternateBuffer<Stdout>>,
 }
@@ -14,9 +13,7 @@ pub struct Terminal {
 impl Terminal {
     pub fn new() -> Result<Self> {
         let _out = io::stdout().into_alternate_buffer()?.into_raw_mode()?;
-        let mut size = size::get_terminal_size()?;
-
-        size.height = size.height.saturating_sub(RESERVED_HEIGHT);
+        let size = size::get_terminal_size()?;
 
         Ok(Self { size, _out })
     }
@@ -0,0 +1,675 @@
+use super::buffer::cursor::Cursor;
+
+use crate::system::size::TermSize;
+
+// A window needs a line of text above its status line, and enough columns to show some of it
+const MIN_HEIGHT: usize = 2;
+const MIN_WIDTH: usize = 8;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Split {
+    // Windows above each other, like with :split
+    Horizontal,
+    // Windows next to each other with a separator between them, like with :vsplit
+    Vertical,
+}
+
+impl Split {
+    fn gap(self) -> usize {
+        match self {
+            Self::Horizontal => 0,
+            Self::Vertical => 1,
+        }
+    }
+
+    fn minimum(self) -> usize {
+        match self {
+            Self::Horizontal => MIN_HEIGHT,
+            Self::Vertical => MIN_WIDTH,
+        }
+    }
+}
+
+// The part of the screen a window is drawn in, the last row of which is its status line
+#[derive(Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    // The size of the text shown in a window, which is everything above its status line
+    pub fn text_size(&self) -> TermSize {
+        TermSize {
+            height: self.height.saturating_sub(1) as u16,
+            width: self.width as u16,
+        }
+    }
+
+    fn length(&self, split: Split) -> usize {
+        match split {
+            Split::Horizontal => self.height,
+            Split::Vertical => self.width,
+        }
+    }
+
+    fn part(&self, split: Split, start: usize, length: usize) -> Self {
+        match split {
+            Split::Horizontal => Self {
+                y: self.y + start,
+                height: length,
+                ..*self
+            },
+            Split::Vertical => Self {
+                x: self.x + start,
+                width: length,
+                ..*self
+            },
+        }
+    }
+}
+
+// A view onto a buffer. The cursor of the window being used is the cursor of the buffer itself, so
+// only the other windows keep one of their own
+pub struct Window {
+    pub buffer: usize,
+    pub cursor: Option<Cursor>,
+}
+
+enum Node {
+    Window(Window),
+    // The sizes are the lengths of the children along the direction of the split
+    Split {
+        split: Split,
+        children: Vec<Self>,
+        sizes: Vec<usize>,
+    },
+}
+
+// Shares the length of a split between its children in proportion to their sizes, as the sizes
+// stop adding up to the length once the terminal has been resized
+fn distribute(sizes: &[usize], length: usize) -> Vec<usize> {
+    let total = sizes.iter().sum::<usize>().max(1);
+
+    let mut lengths: Vec<usize> = sizes.iter().map(|size| size * length / total).collect();
+    let used: usize = lengths.iter().sum();
+
+    if let Some(last) = lengths.last_mut() {
+        *last += length.saturating_sub(used);
+    }
+
+    lengths
+}
+
+impl Node {
+    fn count(&self) -> usize {
+        match self {
+            Self::Window(_) => 1,
+            Self::Split { children, .. } => children.iter().map(Self::count).sum(),
+        }
+    }
+
+    // The child indices leading down to the index'th window, counting from the top left
+    fn path(&self, mut index: usize) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut node = self;
+
+        while let Self::Split { children, .. } = node {
+            for (child_index, child) in children.iter().enumerate() {
+                let count = child.count();
+
+                if index < count {
+                    path.push(child_index);
+                    node = child;
+                    break;
+                }
+
+                index -= count;
+            }
+        }
+
+        path
+    }
+
+    fn node_mut(&mut self, path: &[usize]) -> &mut Self {
+        match (self, path.split_first()) {
+            (Self::Split { children, .. }, Some((index, rest))) => children[*index].node_mut(rest),
+            (node, _) => node,
+        }
+    }
+
+    fn rects(&self, rect: Rect, rects: &mut Vec<Rect>) {
+        let Self::Split {
+            split,
+            children,
+            sizes,
+        } = self
+        else {
+            rects.push(rect);
+            return;
+        };
+
+        let gaps = split.gap() * (children.len() - 1);
+        let lengths = distribute(sizes, rect.length(*split).saturating_sub(gaps));
+
+        let mut start = 0;
+
+        for (child, length) in children.iter().zip(lengths) {
+            child.rects(rect.part(*split, start, length), rects);
+            start += length + split.gap();
+        }
+    }
+
+    // Turns the sizes into the lengths the children actually have, so they can be changed exactly
+    fn normalize(&mut self, rect: Rect) {
+        let Self::Split {
+            split,
+            children,
+            sizes,
+        } = self
+        else {
+            return;
+        };
+
+        let gaps = split.gap() * (children.len() - 1);
+        *sizes = distribute(sizes, rect.length(*split).saturating_sub(gaps));
+
+        let mut start = 0;
+
+        for (child, length) in children.iter_mut().zip(sizes.iter()) {
+            child.normalize(rect.part(*split, start, *length));
+            start += length + split.gap();
+        }
+    }
+
+    // The smallest length the node can be given along the direction of a split
+    fn minimum(&self, direction: Split) -> usize {
+        match self {
+            Self::Window(_) => direction.minimum(),
+            Self::Split {
+                split, children, ..
+            } if *split == direction => {
+                let gaps = split.gap() * (children.len() - 1);
+                children
+                    .iter()
+                    .map(|child| child.minimum(direction))
+                    .sum::<usize>()
+                    + gaps
+            }
+            Self::Split { children, .. } => children
+                .iter()
+                .map(|child| child.minimum(direction))
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    fn equalize(&mut self) {
+        if let Self::Split {
+            children, sizes, ..
+        } = self
+        {
+            sizes.fill(1);
+            children.iter_mut().for_each(Self::equalize);
+        }
+    }
+
+    fn into_windows(self, windows: &mut Vec<Window>) {
+        match self {
+            Self::Window(window) => windows.push(window),
+            Self::Split { children, .. } => {
+                for child in children {
+                    child.into_windows(windows);
+                }
+            }
+        }
+    }
+
+    fn windows<'a>(&'a self, windows: &mut Vec<&'a Window>) {
+        match self {
+            Self::Window(window) => windows.push(window),
+            Self::Split { children, .. } => {
+                children.iter().for_each(|child| child.windows(windows));
+            }
+        }
+    }
+
+    fn windows_mut<'a>(&'a mut self, windows: &mut Vec<&'a mut Window>) {
+        match self {
+            Self::Window(window) => windows.push(window),
+            Self::Split { children, .. } => {
+                for child in children {
+                    child.windows_mut(windows);
+                }
+            }
+        }
+    }
+}
+
+// The windows on the screen, laid out as a tree of splits
+pub struct Windows {
+    root: Node,
+    // The window being used, counting from the top left
+    pub active: usize,
+}
+
+impl Windows {
+    pub fn new(buffer: usize) -> Self {
+        Self {
+            root: Node::Window(Window {
+                buffer,
+                cursor: None,
+            }),
+            active: 0,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.root.count()
+    }
+
+    pub fn rects(&self, area: Rect) -> Vec<Rect> {
+        let mut rects = Vec::new();
+        self.root.rects(area, &mut rects);
+
+        rects
+    }
+
+    pub fn windows(&self) -> Vec<&Window> {
+        let mut windows = Vec::new();
+        self.root.windows(&mut windows);
+
+        windows
+    }
+
+    pub fn windows_mut(&mut self) -> Vec<&mut Window> {
+        let mut windows = Vec::new();
+        self.root.windows_mut(&mut windows);
+
+        windows
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Window> {
+        self.windows_mut().into_iter().nth(index)
+    }
+
+    // Splits a window in two, the new window takes the place of the first half and gets the index
+    // the split window had
+    pub fn split(
+        &mut self,
+        index: usize,
+        split: Split,
+        window: Window,
+        area: Rect,
+    ) -> Result<(), String> {
+        self.root.normalize(area);
+
+        let rect = self.rects(area)[index];
+        let length = rect.length(split).saturating_sub(split.gap());
+
+        if length < split.minimum() * 2 {
+            return Err(String::from("Not enough room"));
+        }
+
+        let first = length - length / 2;
+        let second = length / 2;
+
+        let path = self.root.path(index);
+
+        // A window that is already part of a split in the same direction gets a new sibling
+        if let Some((child, parent)) = path.split_last() {
+            if let Node::Split {
+                split: direction,
+                children,
+                sizes,
+            } = self.root.node_mut(parent)
+            {
+                if *direction == split {
+                    children.insert(*child, Node::Window(window));
+
+                    sizes[*child] = second;
+                    sizes.insert(*child, first);
+
+                    return Ok(());
+                }
+            }
+        }
+
+        let node = self.root.node_mut(&path);
+
+        let split = Node::Split {
+            split,
+            children: Vec::new(),
+            sizes: vec![first, second],
+        };
+
+        let old = std::mem::replace(node, split);
+
+        if let Node::Split { children, .. } = node {
+            children.extend([Node::Window(window), old]);
+        }
+
+        Ok(())
+    }
+
+    // Removes a window, giving its space to the window before it, or the one after it when it was
+    // the first in its split. Returns the index of the window that was given the space
+    pub fn close(&mut self, index: usize, area: Rect) -> Option<usize> {
+        self.root.normalize(area);
+
+        let path = self.root.path(index);
+        let (child, parent) = path.split_last()?;
+
+        let parent = self.root.node_mut(parent);
+
+        let Node::Split {
+            split,
+            children,
+            sizes,
+        } = parent
+        else {
+            return None;
+        };
+
+        let node = children.remove(*child);
+        let size = sizes.remove(*child);
+
+        let neighbour = child.saturating_sub(1).min(children.len() - 1);
+        sizes[neighbour] += size + split.gap();
+
+        // A split that is left with one window is replaced by that window
+        if children.len() == 1 {
+            let last = children.remove(0);
+            *parent = last;
+        }
+
+        if index < self.active {
+            self.active -= 1;
+        }
+
+        match node {
+            Node::Window(_) if *child > 0 => Some(index - 1),
+            Node::Window(_) => Some(index),
+            Node::Split { .. } => None,
+        }
+    }
+
+    // Closes the windows on a buffer that is being deleted, as long as there are other windows left
+    pub fn close_buffer(&mut self, number: usize, area: Rect) {
+        while self.count() > 1 {
+            let Some(index) = self
+                .windows()
+                .iter()
+                .position(|window| window.buffer == number)
+            else {
+                return;
+            };
+
+            let next = self.close(index, area);
+
+            if index == self.active {
+                self.active = next.unwrap_or(0);
+            }
+        }
+    }
+
+    // Closes every window but one
+    pub fn only(&mut self, index: usize) {
+        let empty = Node::Split {
+            split: Split::Horizontal,
+            children: Vec::new(),
+            sizes: Vec::new(),
+        };
+
+        let mut windows = Vec::new();
+        std::mem::replace(&mut self.root, empty).into_windows(&mut windows);
+
+        self.root = Node::Window(windows.swap_remove(index));
+        self.active = 0;
+    }
+
+    // Changes the length of a window in the direction of a split, taking the space from or giving
+    // it to the window next to it. The new length is worked out from the current one
+    pub fn resize(
+        &mut self,
+        index: usize,
+        direction: Split,
+        area: Rect,
+        length: impl FnOnce(usize) -> usize,
+    ) {
+        self.root.normalize(area);
+
+        let path = self.root.path(index);
+
+        // The closest split in the same direction decides the length of the window
+        for depth in (0..path.len()).rev() {
+            let Node::Split {
+                split,
+                children,
+                sizes,
+            } = self.root.node_mut(&path[..depth])
+            else {
+                continue;
+            };
+
+            if *split != direction {
+                continue;
+            }
+
+            let child = path[depth];
+            let neighbour = if child + 1 < sizes.len() {
+                child + 1
+            } else {
+                child - 1
+            };
+
+            let total = sizes[child] + sizes[neighbour];
+
+            let minimum = children[child].minimum(direction);
+            let maximum = total.saturating_sub(children[neighbour].minimum(direction));
+
+            let size = length(sizes[child]).clamp(minimum, maximum.max(minimum));
+
+            sizes[child] = size;
+            sizes[neighbour] = total - size;
+
+            return;
+        }
+    }
+
+    pub fn equalize(&mut self) {
+        self.root.equalize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AREA: Rect = Rect {
+        x: 0,
+        y: 0,
+        width: 80,
+        height: 24,
+    };
+
+    fn window(buffer: usize) -> Window {
+        Window {
+            buffer,
+            cursor: None,
+        }
+    }
+
+    // The rects as (x, y, width, height)
+    fn rects(windows: &Windows) -> Vec<(usize, usize, usize, usize)> {
+        windows
+            .rects(AREA)
+            .into_iter()
+            .map(|rect| (rect.x, rect.y, rect.width, rect.height))
+            .collect()
+    }
+
+    fn buffers(windows: &Windows) -> Vec<usize> {
+        windows
+            .windows()
+            .into_iter()
+            .map(|window| window.buffer)
+            .collect()
+    }
+
+    #[test]
+    fn horizontal_split() {
+        let mut windows = Windows::new(1);
+        windows
+            .split(0, Split::Horizontal, window(2), AREA)
+            .unwrap();
+
+        assert_eq!(rects(&windows), [(0, 0, 80, 12), (0, 12, 80, 12)]);
+        assert_eq!(buffers(&windows), [2, 1]);
+    }
+
+    #[test]
+    fn vertical_split_leaves_room_for_the_separator() {
+        let mut windows = Windows::new(1);
+        windows.split(0, Split::Vertical, window(2), AREA).unwrap();
+
+        assert_eq!(rects(&windows), [(0, 0, 40, 24), (41, 0, 39, 24)]);
+    }
+
+    #[test]
+    fn splitting_in_the_same_direction_adds_a_sibling() {
+        let mut windows = Windows::new(1);
+
+        windows
+            .split(0, Split::Horizontal, window(2), AREA)
+            .unwrap();
+        windows
+            .split(0, Split::Horizontal, window(3), AREA)
+            .unwrap();
+
+        assert_eq!(
+            rects(&windows),
+            [(0, 0, 80, 6), (0, 6, 80, 6), (0, 12, 80, 12)]
+        );
+        assert_eq!(buffers(&windows), [3, 2, 1]);
+    }
+
+    #[test]
+    fn nested_splits() {
+        let mut windows = Windows::new(1);
+
+        windows
+            .split(0, Split::Horizontal, window(2), AREA)
+            .unwrap();
+        windows.split(1, Split::Vertical, window(3), AREA).unwrap();
+
+        assert_eq!(
+            rects(&windows),
+            [(0, 0, 80, 12), (0, 12, 40, 12), (41, 12, 39, 12)]
+        );
+        assert_eq!(windows.count(), 3);
+    }
+
+    #[test]
+    fn not_enough_room() {
+        let area = Rect { height: 3, ..AREA };
+        let mut windows = Windows::new(1);
+
+        assert!(windows
+            .split(0, Split::Horizontal, window(2), area)
+            .is_err());
+        assert_eq!(windows.count(), 1);
+    }
+
+    #[test]
+    fn close_gives_the_space_to_the_window_before() {
+        let mut windows = Windows::new(1);
+
+        windows
+            .split(0, Split::Horizontal, window(2), AREA)
+            .unwrap();
+        windows
+            .split(0, Split::Horizontal, window(3), AREA)
+            .unwrap();
+
+        assert_eq!(windows.close(2, AREA), Some(1));
+        assert_eq!(rects(&windows), [(0, 0, 80, 6), (0, 6, 80, 18)]);
+        assert_eq!(buffers(&windows), [3, 2]);
+    }
+
+    #[test]
+    fn closing_the_first_window_gives_the_space_to_the_next() {
+        let mut windows = Windows::new(1);
+
+        windows.split(0, Split::Vertical, window(2), AREA).unwrap();
+        windows.active = 1;
+
+        assert_eq!(windows.close(0, AREA), Some(0));
+        assert_eq!(rects(&windows), [(0, 0, 80, 24)]);
+        assert_eq!(buffers(&windows), [1]);
+        assert_eq!(windows.active, 0);
+    }
+
+    #[test]
+    fn the_last_window_cant_be_closed() {
+        let mut windows = Windows::new(1);
+
+        assert_eq!(windows.close(0, AREA), None);
+        assert_eq!(windows.count(), 1);
+    }
+
+    #[test]
+    fn only() {
+        let mut windows = Windows::new(1);
+
+        windows
+            .split(0, Split::Horizontal, window(2), AREA)
+            .unwrap();
+        windows.split(1, Split::Vertical, window(3), AREA).unwrap();
+        windows.active = 2;
+
+        windows.only(1);
+
+        assert_eq!(buffers(&windows), [3]);
+        assert_eq!(rects(&windows), [(0, 0, 80, 24)]);
+        assert_eq!(windows.active, 0);
+    }
+
+    #[test]
+    fn resize_is_clamped_to_the_neighbour() {
+        let mut windows = Windows::new(1);
+        windows
+            .split(0, Split::Horizontal, window(2), AREA)
+            .unwrap();
+
+        windows.resize(0, Split::Horizontal, AREA, |_| 5);
+        assert_eq!(rects(&windows), [(0, 0, 80, 5), (0, 5, 80, 19)]);
+
+        windows.resize(1, Split::Horizontal, AREA, |length| length + 100);
+        assert_eq!(rects(&windows), [(0, 0, 80, 2), (0, 2, 80, 22)]);
+
+        windows.equalize();
+        assert_eq!(rects(&windows), [(0, 0, 80, 12), (0, 12, 80, 12)]);
+    }
+
+    #[test]
+    fn sizes_follow_the_terminal() {
+        let mut windows = Windows::new(1);
+        windows
+            .split(0, Split::Horizontal, window(2), AREA)
+            .unwrap();
+
+        let area = Rect { height: 48, ..AREA };
+
+        assert_eq!(
+            windows
+                .rects(area)
+                .iter()
+                .map(|rect| rect.height)
+                .collect::<Vec<_>>(),
+            [24, 24]
+        );
+    }
+}
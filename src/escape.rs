@@ -11,5 +11,6 @@ impl Display for CSI {
 
 pub mod alternate;
 pub mod clear;
+pub mod clipboard;
 pub mod color;
 pub mod cursor;
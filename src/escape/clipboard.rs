@@ -0,0 +1,77 @@
+use std::fmt::Display;
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Clone, Copy)]
+pub enum Selection {
+    Clipboard,
+    Primary,
+}
+
+// OSC 52, which asks the terminal to put text on the clipboard, this also works over SSH and
+// inside of tmux as long as the terminal allows it
+pub struct Copy<'a>(pub Selection, pub &'a str);
+
+impl Display for Copy<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let selection = match self.0 {
+            Selection::Clipboard => 'c',
+            Selection::Primary => 'p',
+        };
+
+        write!(f, "\x1b]52;{selection};{}\x07", encode(self.1.as_bytes()))
+    }
+}
+
+fn encode(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+
+        // Every three bytes become four characters, with padding for the bytes that are missing
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - index * 6)) & 0x3f;
+                encoded.push(BASE64[sextet as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_pads_partial_groups() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn encode_high_bytes() {
+        assert_eq!(encode(&[0xff, 0xfe, 0xfd]), "//79");
+        assert_eq!(encode("é".as_bytes()), "w6k=");
+    }
+
+    #[test]
+    fn copy_sequence() {
+        let copy = Copy(Selection::Primary, "hi\n");
+
+        assert_eq!(copy.to_string(), "\x1b]52;p;aGkK\x07");
+    }
+}
@@ -2,6 +2,9 @@ use super::CSI;
 
 pub const RESET: CSI = CSI("0m");
 
+pub const INVERT: CSI = CSI("7m");
+pub const NO_INVERT: CSI = CSI("27m");
+
 pub const BLACK_FOREGROUND: CSI = CSI("30m");
 pub const BLACK_BACKGROUND: CSI = CSI("40m");
 pub const BRIGHT_BLACK_FOREGROUND: CSI = CSI("90m");
@@ -5,7 +5,7 @@ pub use key::Key;
 use std::io::{Error, ErrorKind, Result};
 
 const CTRL_1_OFFSET: u8 = 0x60;
-const CTRL_2_OFFSET: u8 = 0x34;
+const CTRL_2_OFFSET: u8 = 0x18;
 
 const PARSE_ERROR: &str = "Could not parse event";
 const UTF8_ERROR: &str = "Character is not valid UTF-8";
@@ -26,9 +26,7 @@ where
         // The first byte in an escape sequence is always:
         // 0x1b (hexadecimal) / 27 (decimal)
         b'\x1b' => {
-            let byte = iter
-                .next()
-                .ok_or(Error::new(ErrorKind::Other, PARSE_ERROR))?;
+            let byte = iter.next().ok_or(Error::other(PARSE_ERROR))?;
 
             match byte {
                 // A CSI (Control Sequence Introducer)
@@ -36,25 +34,23 @@ where
                 // The first two bytes in a CSI is always:
                 // 1: 0x1b (hexadecimal) / 27 (decimal)
                 // 2: 0x5b (hexadecimal) / 91 (decimal)
-                Ok(b'[') => parse_csi(iter).ok_or(Error::new(ErrorKind::Other, PARSE_ERROR)),
+                Ok(b'[') => parse_csi(iter).ok_or(Error::other(PARSE_ERROR)),
                 Ok(b'O') => {
-                    let byte = iter
-                        .next()
-                        .ok_or(Error::new(ErrorKind::Other, PARSE_ERROR))?;
+                    let byte = iter.next().ok_or(Error::other(PARSE_ERROR))?;
 
                     match byte {
                         Ok(b'P') => Ok(Event::Key(Key::F1)),
                         Ok(b'Q') => Ok(Event::Key(Key::F2)),
                         Ok(b'R') => Ok(Event::Key(Key::F3)),
                         Ok(b'S') => Ok(Event::Key(Key::F4)),
-                        _ => Err(Error::new(ErrorKind::Other, PARSE_ERROR)),
+                        _ => Err(Error::other(PARSE_ERROR)),
                     }
                 }
                 Ok(c) => {
                     let utf8_char = parse_utf8(c, iter)?;
                     Ok(Event::Key(Key::Alt(utf8_char)))
                 }
-                _ => Err(Error::new(ErrorKind::Other, PARSE_ERROR)),
+                _ => Err(Error::other(PARSE_ERROR)),
             }
         }
         b'\t' => Ok(Event::Key(Key::Tab)),
@@ -85,6 +81,7 @@ where
         b'D' => Some(Event::Key(Key::ArrowLeft)),
         b'F' => Some(Event::Key(Key::End)),
         b'H' => Some(Event::Key(Key::Home)),
+        b'Z' => Some(Event::Key(Key::BackTab)),
         b'0'..=b'9' => {
             let mut buf = vec![byte];
 
@@ -1,6 +1,6 @@
 use std::fmt::Display;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Key {
     Escape,
     Insert,
@@ -15,6 +15,7 @@ pub enum Key {
     PageUp,
     PageDown,
     Tab,
+    BackTab,
     Enter,
     F1,
     F2,
@@ -50,6 +51,7 @@ impl Display for Key {
             Self::PageUp => String::from("PageUp"),
             Self::PageDown => String::from("PageDown"),
             Self::Tab => String::from("Tab"),
+            Self::BackTab => String::from("BackTab"),
             Self::Enter => String::from("Enter"),
             Self::F1 => String::from("F1"),
             Self::F2 => String::from("F2"),
@@ -3,8 +3,8 @@ mod rust;
 use crate::escape::{self, CSI};
 use rust::Rust;
 use std::{ffi::OsStr, fmt::Write, path::Path};
-use tree_sitter::QueryError;
-use tree_sitter_highlight::{Error, HighlightConfiguration, HighlightEvent, Highlighter};
+use tree_sitter::{InputEdit, Node, Parser, Point, QueryCursor, QueryError, Tree};
+use tree_sitter_highlight::HighlightConfiguration;
 
 const DEFAULT_HIGHLIGHT_NAMES: [&str; 59] = [
     "variable",
@@ -72,50 +72,162 @@ pub trait HighlightConfig {
     fn config() -> Result<HighlightConfiguration, QueryError>;
 }
 
-pub fn colors(config: &HighlightConfiguration, source: &[u8]) -> Result<Vec<CSI>, Error> {
-    let mut highlighter = Highlighter::new();
+// The name from DEFAULT_HIGHLIGHT_NAMES that fits a capture best, which is the one with the most
+// parts that all appear in the name of the capture, the same way tree-sitter-highlight picks them
+fn highlight(capture: &str) -> Option<usize> {
+    let parts: Vec<&str> = capture.split('.').collect();
 
-    let highlights = highlighter.highlight(config, source, None, |_| None)?;
+    DEFAULT_HIGHLIGHT_NAMES
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| name.split('.').all(|part| parts.contains(&part)))
+        .max_by_key(|(index, name)| (name.split('.').count(), usize::MAX - index))
+        .map(|(index, _)| index)
+}
 
-    let mut colors = vec![escape::color::DEFAULT_FOREGROUND; source.len()];
-    let mut color: Option<CSI> = None;
+fn color(highlight: usize) -> CSI {
+    match highlight {
+        2 => escape::color::DEFAULT_FOREGROUND,
+        11 => escape::color::GREEN_FOREGROUND,
+        24 => escape::color::CYAN_FOREGROUND,
+        6 | 31 => escape::color::BLUE_FOREGROUND,
+        35 | 25 => escape::color::BRIGHT_BLUE_FOREGROUND,
+        10 | 30 | 38 => escape::color::YELLOW_FOREGROUND,
+        39 => escape::color::MAGENTA_FOREGROUND,
+        1 | 34 => escape::color::RED_FOREGROUND,
+        37 => escape::color::BRIGHT_YELLOW_FOREGROUND,
+        28 | 54 | 55 | 57 => escape::color::BRIGHT_BLACK_FOREGROUND,
+        // Captures without a color of their own are drawn like the rest of the text, rather than
+        // taking the editor down along with any unsaved changes
+        _ => escape::color::DEFAULT_FOREGROUND,
+    }
+}
 
-    for event in highlights.flatten() {
-        match event {
-            HighlightEvent::Source { start, end } => {
-                if let Some(color) = color {
-                    for c in colors.iter_mut().take(end).skip(start) {
-                        *c = color;
-                    }
-                }
-            }
-            HighlightEvent::HighlightStart(highlight) => {
-                color = Some(match highlight.0 {
-                    2 => escape::color::DEFAULT_FOREGROUND,
-                    11 => escape::color::GREEN_FOREGROUND,
-                    24 => escape::color::CYAN_FOREGROUND,
-                    6 | 31 => escape::color::BLUE_FOREGROUND,
-                    35 | 25 => escape::color::BRIGHT_BLUE_FOREGROUND,
-                    10 | 30 | 38 => escape::color::YELLOW_FOREGROUND,
-                    39 => escape::color::MAGENTA_FOREGROUND,
-                    1 | 34 => escape::color::RED_FOREGROUND,
-                    37 => escape::color::BRIGHT_YELLOW_FOREGROUND,
-                    28 | 54 | 55 | 57 => escape::color::BRIGHT_BLACK_FOREGROUND,
-                    n => {
-                        unimplemented!("highlight {n}");
-                    }
-                });
+// Where an edit leaves a position that was at the start of it, going by the text it put there
+fn advance(start: Point, text: &str) -> Point {
+    match text.rfind('\n') {
+        Some(index) => Point {
+            row: start.row + text.matches('\n').count(),
+            column: text.len() - index - 1,
+        },
+        None => Point {
+            row: start.row,
+            column: start.column + text.len(),
+        },
+    }
+}
+
+// The syntax tree of a file, which is kept between edits so that only the parts of the text that
+// were changed have to be parsed again, and only the lines that are shown are highlighted
+pub struct Syntax {
+    config: HighlightConfiguration,
+    parser: Parser,
+    tree: Option<Tree>,
+    // The color of each capture in the highlights query, nothing for the ones that aren't drawn
+    colors: Vec<Option<CSI>>,
+}
+
+impl Syntax {
+    pub fn new(path: &Path) -> Option<Self> {
+        let config = config(path)?;
+
+        let mut parser = Parser::new();
+        parser.set_language(&config.language).ok()?;
+
+        let colors = config
+            .query
+            .capture_names()
+            .iter()
+            .map(|capture| highlight(capture).map(color))
+            .collect();
+
+        Some(Self {
+            config,
+            parser,
+            tree: None,
+            colors,
+        })
+    }
+
+    // Moves the tree along with an edit at a byte offset, which is at a row and column, so that
+    // the next parse can reuse everything the edit didn't touch
+    pub fn edit(&mut self, byte: usize, (row, column): (usize, usize), removed: &str, text: &str) {
+        let Some(tree) = &mut self.tree else {
+            return;
+        };
+
+        let start = Point { row, column };
+
+        tree.edit(&InputEdit {
+            start_byte: byte,
+            old_end_byte: byte + removed.len(),
+            new_end_byte: byte + text.len(),
+            start_position: start,
+            old_end_position: advance(start, removed),
+            new_end_position: advance(start, text),
+        });
+    }
+
+    // Drops the tree when the whole text has been replaced, as none of it can be reused
+    pub fn forget(&mut self) {
+        self.tree = None;
+    }
+
+    // Parses the text again, which is read a chunk at a time starting from a byte offset
+    pub fn parse<T: AsRef<[u8]>>(&mut self, mut read: impl FnMut(usize) -> T) {
+        self.tree = self
+            .parser
+            .parse_with(&mut |byte, _| read(byte), self.tree.as_ref());
+    }
+
+    // The colors of the bytes between two offsets. The text of a node is needed for the patterns
+    // that only match some names, like constants being in uppercase
+    pub fn colors<R, I>(&self, start: usize, end: usize, text: impl FnMut(Node) -> R) -> Vec<CSI>
+    where
+        R: Iterator<Item = I>,
+        I: AsRef<[u8]>,
+    {
+        let mut colors = vec![escape::color::DEFAULT_FOREGROUND; end.saturating_sub(start)];
+
+        let Some(tree) = &self.tree else {
+            return colors;
+        };
+
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(start..end);
+
+        let mut captures = cursor
+            .captures(&self.config.query, tree.root_node(), text)
+            .map(|(found, index)| found.captures[index])
+            .peekable();
+
+        while let Some(mut capture) = captures.next() {
+            // The last pattern that matches a node decides its color, the same as it does in
+            // tree-sitter-highlight, and inner nodes are drawn over the outer ones
+            while let Some(next) = captures.next_if(|next| next.node == capture.node) {
+                capture = next;
             }
-            HighlightEvent::HighlightEnd => {
-                color = None;
+
+            let Some(color) = self.colors[capture.index as usize] else {
+                continue;
+            };
+
+            let range = capture.node.byte_range();
+
+            for c in colors
+                .iter_mut()
+                .take(range.end.saturating_sub(start))
+                .skip(range.start.saturating_sub(start))
+            {
+                *c = color;
             }
         }
-    }
 
-    Ok(colors)
+        colors
+    }
 }
 
-pub fn config(path: &Path) -> Option<HighlightConfiguration> {
+fn config(path: &Path) -> Option<HighlightConfiguration> {
     let extension = path.extension().and_then(OsStr::to_str)?;
 
     match extension {
@@ -124,18 +236,71 @@ pub fn config(path: &Path) -> Option<HighlightConfiguration> {
     }
 }
 
+// A style drawn over a range of graphemes on top of the syntax highlighting, such as the
+// background of a selection
+#[derive(Clone, Copy)]
+pub struct Overlay {
+    pub start: usize,
+    pub end: usize,
+    pub style: CSI,
+}
+
+impl Overlay {
+    fn contains(&self, column: usize) -> bool {
+        (self.start..self.end).contains(&column)
+    }
+}
+
+// A grapheme of a line the way it is drawn, along with where it comes from in the line
+pub struct Cell {
+    // What is drawn, which is the grapheme itself unless it can't be shown as it is
+    pub text: String,
+    // The byte offset of the grapheme in the line, which the syntax highlighting is kept by
+    pub byte: usize,
+    // The index of the grapheme in the line, which positions and overlays are given in
+    pub index: usize,
+}
+
 pub trait Highlight {
-    fn highlight(self, highlights: &[CSI], offset: usize) -> String;
+    fn highlight(self, highlights: Option<&[CSI]>, overlays: &[Overlay]) -> String;
 }
 
-impl Highlight for &str {
-    fn highlight(self, highlights: &[CSI], offset: usize) -> String {
-        self.chars()
-            .enumerate()
-            .fold(String::new(), |mut output, (index, char)| {
-                _ = write!(output, "{}{char}", highlights[index + offset]);
+impl Highlight for &[Cell] {
+    fn highlight(self, highlights: Option<&[CSI]>, overlays: &[Overlay]) -> String {
+        let mut active: Option<CSI> = None;
+
+        let mut output = self.iter().fold(String::new(), |mut output, cell| {
+            if let Some(color) = highlights.and_then(|highlights| highlights.get(cell.byte)) {
+                _ = write!(output, "{color}");
+            }
+
+            // Later overlays are drawn on top of earlier ones
+            let overlay = overlays
+                .iter()
+                .rev()
+                .find(|overlay| overlay.contains(cell.index))
+                .map(|overlay| overlay.style);
+
+            if overlay.map(|style| style.0) != active.map(|style| style.0) {
+                _ = write!(output, "{}", escape::color::NO_INVERT);
+                _ = write!(output, "{}", escape::color::DEFAULT_BACKGROUND);
+
+                if let Some(style) = overlay {
+                    _ = write!(output, "{style}");
+                }
+
+                active = overlay;
+            }
+
+            output.push_str(&cell.text);
+            output
+        });
+
+        if active.is_some() {
+            _ = write!(output, "{}", escape::color::NO_INVERT);
+            _ = write!(output, "{}", escape::color::DEFAULT_BACKGROUND);
+        }
 
-                output
-            })
+        output
     }
 }
@@ -13,6 +13,8 @@ pub struct TTYReader {
 }
 
 impl TTYReader {
+    // The bytes are passed on one at a time as they arrive, which buffering would only hold back
+    #[allow(clippy::unbuffered_bytes)]
     pub fn new() -> Self {
         let (sender, receiver) = mpsc::channel();
 
@@ -71,6 +73,16 @@ pub struct RawEvents<R: Read> {
     remainder: Option<u8>,
 }
 
+impl<R: Read> RawEvents<R> {
+    // Whether a byte has already been read for the next event, which waiting for input would miss
+    pub fn is_pending(&self) -> bool {
+        self.remainder.is_some()
+    }
+}
+
+// The source is read a couple of bytes at a time on purpose, buffering it would swallow the bytes
+// that belong to the next event
+#[allow(clippy::unbuffered_bytes)]
 impl<R: Read> Iterator for RawEvents<R> {
     type Item = Result<Event>;
 
@@ -149,3 +161,50 @@ impl<R: Read> EventIterator for R {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pasted_text_is_every_key() {
+        let mut source: &[u8] = "rüst\x1b[A".as_bytes();
+
+        let keys: Vec<Key> = (&mut source).keys().map(Result::unwrap).collect();
+
+        assert_eq!(
+            keys,
+            [
+                Key::Char('r'),
+                Key::Char('ü'),
+                Key::Char('s'),
+                Key::Char('t'),
+                Key::ArrowUp
+            ]
+        );
+    }
+
+    // Anything that waits for more input has to be able to tell that the next key has been read
+    // already, so no more is taken from the source than the event needs
+    #[test]
+    fn bursts_are_not_read_ahead() {
+        let mut source: &[u8] = b"ab\x1b[A";
+
+        {
+            let mut events = (&mut source).events();
+
+            assert!(matches!(
+                events.next(),
+                Some(Ok(Event::Key(Key::Char('a'))))
+            ));
+            assert!(events.is_pending());
+            assert!(matches!(
+                events.next(),
+                Some(Ok(Event::Key(Key::Char('b'))))
+            ));
+            assert!(!events.is_pending());
+        }
+
+        assert_eq!(source, b"\x1b[A");
+    }
+}
@@ -9,6 +9,7 @@ pub mod arg;
 pub mod editor;
 pub mod escape;
 pub mod event;
+pub mod hash;
 pub mod highlight;
 pub mod input;
 pub mod raw;
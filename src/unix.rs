@@ -1,7 +1,12 @@
 pub mod attr;
+pub mod clipboard;
+pub mod dirs;
+pub mod file;
 pub mod log;
+pub mod process;
 pub mod size;
 pub mod tty;
+pub mod watch;
 
 pub use libc::termios as Termios;
 
@@ -0,0 +1,129 @@
+use crate::escape::clipboard::Selection;
+
+use std::{
+    env,
+    io::{Error, ErrorKind, Result, Write},
+    process::{Command, Stdio},
+};
+
+// Commands that are run through the shell instead of the helpers below, for clipboards that aren't
+// supported out of the box
+const COPY_VARIABLE: &str = "ALICE_CLIPBOARD_COPY";
+const PASTE_VARIABLE: &str = "ALICE_CLIPBOARD_PASTE";
+
+fn on_path(program: &str) -> bool {
+    let Some(path) = env::var_os("PATH") else {
+        return false;
+    };
+
+    env::split_paths(&path).any(|directory| directory.join(program).is_file())
+}
+
+fn shell(command: String) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+
+    shell
+}
+
+// Finds something that can talk to the clipboard, the helpers are only used when there is a display
+// to talk to, since they are often installed on machines that are only reached over SSH
+fn helper(selection: Selection, paste: bool) -> Option<Command> {
+    let variable = if paste { PASTE_VARIABLE } else { COPY_VARIABLE };
+
+    if let Ok(command) = env::var(variable) {
+        return Some(shell(command));
+    }
+
+    let primary = matches!(selection, Selection::Primary);
+
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        let program = if paste { "wl-paste" } else { "wl-copy" };
+
+        if on_path(program) {
+            let mut command = Command::new(program);
+
+            if paste {
+                command.arg("--no-newline");
+            }
+
+            if primary {
+                command.arg("--primary");
+            }
+
+            return Some(command);
+        }
+    }
+
+    if env::var_os("DISPLAY").is_some() {
+        if on_path("xclip") {
+            let mut command = Command::new("xclip");
+            command
+                .arg("-selection")
+                .arg(if primary { "primary" } else { "clipboard" });
+
+            if paste {
+                command.arg("-out");
+            }
+
+            return Some(command);
+        }
+
+        if on_path("xsel") {
+            let mut command = Command::new("xsel");
+            command
+                .arg(if primary { "--primary" } else { "--clipboard" })
+                .arg(if paste { "--output" } else { "--input" });
+
+            return Some(command);
+        }
+    }
+
+    None
+}
+
+fn not_found() -> Error {
+    Error::new(ErrorKind::NotFound, "No clipboard helper found")
+}
+
+pub fn copy(selection: Selection, text: &str) -> Result<()> {
+    let mut command = helper(selection, false).ok_or_else(not_found)?;
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+
+    let status = child.wait()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(format!(
+            "Clipboard helper exited with {status}"
+        )))
+    }
+}
+
+pub fn paste(selection: Selection) -> Result<String> {
+    let mut command = helper(selection, true).ok_or_else(not_found)?;
+
+    let output = command
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        let status = output.status;
+        Err(Error::other(format!(
+            "Clipboard helper exited with {status}"
+        )))
+    }
+}
@@ -0,0 +1,62 @@
+use crate::hash::Fnv;
+
+use std::{
+    env, fs,
+    hash::Hasher,
+    io::{Error, ErrorKind, Result},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+const PACKAGE_NAME: &str = env!("CARGO_PKG_NAME");
+
+// The longest a file name can be, less some room for what is added to the names of state files,
+// like the process id of a swap file or the temporary name a file is written under first
+const NAME_MAX: usize = 255 - 32;
+
+// Resolves (and creates) the directory alice keeps its state in, which is $XDG_STATE_HOME/alice/
+// or ~/.local/state/alice/ when XDG_STATE_HOME isn't set
+pub fn state() -> Result<PathBuf> {
+    let path = if let Some(xdg) = env::var_os("XDG_STATE_HOME") {
+        PathBuf::from(xdg).join(PACKAGE_NAME)
+    } else if let Some(home) = env::var_os("HOME") {
+        PathBuf::from(home)
+            .join(".local")
+            .join("state")
+            .join(PACKAGE_NAME)
+    } else {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            "Neither XDG_STATE_HOME nor HOME is set",
+        ));
+    };
+
+    fs::create_dir_all(&path)?;
+
+    Ok(path)
+}
+
+// A directory inside of the state directory, such as the one holding undo files
+pub fn state_subdirectory(name: &str) -> Result<PathBuf> {
+    let path = state()?.join(name);
+
+    fs::create_dir_all(&path)?;
+
+    Ok(path)
+}
+
+// The name a state file that belongs to a file is kept under, which is the full path of the file
+// with every slash swapped for a percent sign, the same way vim names them. Paths that are too long
+// for that are named after a hash of the path instead
+pub fn state_name(path: &Path) -> String {
+    let name = path.to_string_lossy().replace('/', "%");
+
+    if name.len() <= NAME_MAX {
+        return name;
+    }
+
+    let mut hasher = Fnv::default();
+    hasher.write(path.as_os_str().as_bytes());
+
+    format!("{:016x}", hasher.finish())
+}
@@ -0,0 +1,305 @@
+use super::{c_result, dirs};
+use libc::{access, fchown, geteuid, W_OK};
+use std::{
+    ffi::CString,
+    fs::{self, File, Metadata, OpenOptions, Permissions},
+    io::{self, BufWriter, Error, Result, Write},
+    os::{
+        fd::AsRawFd,
+        unix::{
+            ffi::OsStrExt,
+            fs::{MetadataExt, OpenOptionsExt, PermissionsExt},
+        },
+    },
+    path::{Path, PathBuf},
+    process,
+};
+
+const BACKUP_DIRECTORY: &str = "backup";
+
+// Writing to a link writes to the file it points to, so that the link itself is left alone
+fn resolve(path: &Path) -> PathBuf {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+fn directory(path: &Path) -> &Path {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+fn c_path(path: &Path) -> Result<CString> {
+    Ok(CString::new(path.as_os_str().as_bytes())?)
+}
+
+// Whether a file can be replaced by renaming a new file over it without anything about it
+// changing. Hard links would be split off from the file, the owner of a file can only be kept by
+// root, and the new file has to be created next to the old one
+pub fn can_rename(path: &Path) -> bool {
+    let path = resolve(path);
+
+    let Ok(metadata) = fs::metadata(&path) else {
+        return true;
+    };
+
+    if metadata.nlink() > 1 {
+        return false;
+    }
+
+    let user = unsafe { geteuid() };
+
+    if user != 0 && metadata.uid() != user {
+        return false;
+    }
+
+    let Ok(directory) = c_path(directory(&path)) else {
+        return false;
+    };
+
+    unsafe { access(directory.as_ptr(), W_OK) == 0 }
+}
+
+// Makes the new file look like the old one, as far as that is possible
+fn preserve(metadata: &Metadata, file: &File, original: &Path) {
+    let fd = file.as_raw_fd();
+
+    // Giving the file away only works for root, the group can still be kept when it is one of ours
+    unsafe {
+        if c_result(fchown(fd, metadata.uid(), metadata.gid())).is_err() {
+            _ = fchown(fd, u32::MAX, metadata.gid());
+        }
+    }
+
+    // Changing the owner can clear the setuid and setgid bits, so the mode is set afterwards
+    _ = file.set_permissions(Permissions::from_mode(metadata.mode() & 0o7777));
+
+    copy_attributes(original, file);
+}
+
+#[cfg(target_os = "linux")]
+fn copy_attributes(original: &Path, file: &File) {
+    use libc::{c_char, c_void, fsetxattr, getxattr, listxattr};
+
+    let Ok(path) = c_path(original) else {
+        return;
+    };
+
+    unsafe {
+        let length = listxattr(path.as_ptr(), std::ptr::null_mut(), 0);
+
+        if length <= 0 {
+            return;
+        }
+
+        let mut names = vec![0u8; length as usize];
+        let length = listxattr(
+            path.as_ptr(),
+            names.as_mut_ptr().cast::<c_char>(),
+            names.len(),
+        );
+
+        if length <= 0 {
+            return;
+        }
+
+        names.truncate(length as usize);
+
+        // The names are separated by nul bytes, each one is copied on its own so that the ones that
+        // can't be set, like those only root may set, don't stop the rest
+        for name in names
+            .split(|byte| *byte == 0)
+            .filter(|name| !name.is_empty())
+        {
+            let Ok(name) = CString::new(name) else {
+                continue;
+            };
+
+            let length = getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0);
+
+            if length < 0 {
+                continue;
+            }
+
+            let mut value = vec![0u8; length as usize];
+            let length = getxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr().cast::<c_void>(),
+                value.len(),
+            );
+
+            if length < 0 {
+                continue;
+            }
+
+            _ = fsetxattr(
+                file.as_raw_fd(),
+                name.as_ptr(),
+                value.as_ptr().cast::<c_void>(),
+                length as usize,
+                0,
+            );
+        }
+    }
+}
+
+// Extended attributes are only carried over on Linux, the other systems have their own interfaces
+// for them
+#[cfg(not(target_os = "linux"))]
+fn copy_attributes(_original: &Path, _file: &File) {}
+
+fn write_all(file: &File, write: impl FnOnce(&mut dyn Write) -> Result<usize>) -> Result<usize> {
+    let mut writer = BufWriter::new(file);
+
+    let bytes = write(&mut writer)?;
+    writer.flush()?;
+
+    Ok(bytes)
+}
+
+// Writes a temporary file next to the file and renames it over the file once everything has been
+// written and synced, so that a crash or a full disk leaves either the old or the new file behind
+pub fn replace(path: &Path, write: impl FnOnce(&mut dyn Write) -> Result<usize>) -> Result<usize> {
+    let path = resolve(path);
+    let original = fs::metadata(&path).ok();
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = directory(&path).join(format!(".{name}.{}.tmp", process::id()));
+
+    // A new file gets the usual permissions, an existing one gets its own once it has been written
+    let mode = if original.is_some() { 0o600 } else { 0o666 };
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(mode)
+        .open(&temporary)?;
+
+    let result = write_all(&file, write).and_then(|bytes| {
+        if let Some(metadata) = &original {
+            preserve(metadata, &file, &path);
+        }
+
+        file.sync_all()?;
+        fs::rename(&temporary, &path)?;
+
+        // The rename itself only lasts once the directory has been synced too
+        if let Ok(directory) = File::open(directory(&path)) {
+            _ = directory.sync_all();
+        }
+
+        Ok(bytes)
+    });
+
+    if result.is_err() {
+        _ = fs::remove_file(&temporary);
+    }
+
+    result
+}
+
+pub fn append(path: &Path, write: impl FnOnce(&mut dyn Write) -> Result<usize>) -> Result<usize> {
+    let file = OpenOptions::new().append(true).create(true).open(path)?;
+
+    let bytes = write_all(&file, write)?;
+    file.sync_all()?;
+
+    Ok(bytes)
+}
+
+// Copies a file to a new file and syncs the copy. Nothing that is already at the path is touched,
+// so the copy fails when there is
+fn copy(from: &Path, to: &Path) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(to)?;
+
+    let result = io::copy(&mut File::open(from)?, &mut file).and_then(|_| file.sync_all());
+
+    if result.is_err() {
+        _ = fs::remove_file(to);
+    } else if let Ok(directory) = File::open(directory(to)) {
+        _ = directory.sync_all();
+    }
+
+    result
+}
+
+// Copies the file to a backup before it is written over, named after the file and the process with
+// a tilde at the end so that it can't be a file of the user's. It goes next to the file, or into
+// the state directory when it can't
+fn backup(path: &Path) -> Result<Option<PathBuf>> {
+    if fs::metadata(path).is_err() {
+        return Ok(None);
+    }
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let pid = process::id();
+
+    let next_to = directory(path).join(format!(".{name}.{pid}~"));
+    let state = dirs::state_subdirectory(BACKUP_DIRECTORY)
+        .map(|directory| directory.join(format!("{}.{pid}~", dirs::state_name(path))));
+
+    for backup in [Ok(next_to), state].into_iter().flatten() {
+        if copy(path, &backup).is_ok() {
+            return Ok(Some(backup));
+        }
+    }
+
+    Err(Error::other("Can't make a backup of the file"))
+}
+
+// Puts the contents of the backup back into the file after writing it failed
+fn restore(backup: &Path, path: &Path) -> Result<()> {
+    let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+
+    io::copy(&mut File::open(backup)?, &mut file)?;
+    file.sync_all()
+}
+
+// Writes over the file itself, which keeps everything about it the same. A backup of the file is
+// made first and only removed once the new contents have been synced, so a crash or a full disk
+// partway through leaves the old contents behind in it
+pub fn overwrite(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> Result<usize>,
+) -> Result<usize> {
+    let path = resolve(path);
+    let backup = backup(&path)?;
+
+    let result = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&path)
+        .and_then(|file| {
+            let bytes = write_all(&file, write)?;
+            file.sync_all()?;
+
+            Ok(bytes)
+        });
+
+    let Some(backup) = backup else {
+        return result;
+    };
+
+    // When the write failed the old contents are put back, the backup is only kept when even that
+    // doesn't work
+    if result.is_ok() || restore(&backup, &path).is_ok() {
+        _ = fs::remove_file(&backup);
+        return result;
+    }
+
+    result.map_err(|error| {
+        let message = format!("{error}, the file is backed up in {}", backup.display());
+        Error::new(error.kind(), message)
+    })
+}
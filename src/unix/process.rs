@@ -0,0 +1,16 @@
+use libc::{kill, pid_t, EPERM};
+use std::io::Error;
+
+// Whether a process is still running, a process that belongs to another user can't be signalled
+// but still counts
+pub fn is_running(pid: u32) -> bool {
+    let Ok(pid) = pid_t::try_from(pid) else {
+        return false;
+    };
+
+    if unsafe { kill(pid, 0) } == 0 {
+        return true;
+    }
+
+    Error::last_os_error().raw_os_error() == Some(EPERM)
+}
@@ -1,8 +1,15 @@
 use std::{
     fs::{File, OpenOptions},
-    io::Result,
+    io::{self, Result},
+    os::fd::AsFd,
 };
 
 pub fn tty() -> Result<File> {
     OpenOptions::new().write(false).read(true).open("/dev/tty")
 }
+
+// Standard input read straight from its file descriptor. The standard library buffers stdin, which
+// would keep bytes that have already arrived out of sight of anything polling the descriptor
+pub fn stdin() -> Result<File> {
+    Ok(File::from(io::stdin().as_fd().try_clone_to_owned()?))
+}
@@ -0,0 +1,163 @@
+use super::c_result;
+use libc::{
+    c_void, inotify_add_watch, inotify_event, inotify_init1, inotify_rm_watch, poll, pollfd, read,
+    EINTR, IN_CLOEXEC, IN_CLOSE_WRITE, IN_DELETE, IN_MOVED_FROM, IN_MOVED_TO, IN_NONBLOCK,
+    IN_Q_OVERFLOW, POLLIN,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{CString, OsStr},
+    io::{Error, Result},
+    mem,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::ffi::OsStrExt,
+    },
+    path::{Path, PathBuf},
+    ptr,
+};
+
+// A file is only looked at once whatever wrote it is done, a file that is still being written
+// would be read half finished
+const EVENTS: u32 = IN_CLOSE_WRITE | IN_MOVED_TO | IN_DELETE | IN_MOVED_FROM;
+
+// Watches files for being written or removed by other programs. The directories the files are in
+// are watched instead of the files themselves, as a file that is replaced by renaming a new one
+// over it would stop being watched along with the old one
+pub struct Watcher {
+    fd: OwnedFd,
+    // The directories being watched, by watch descriptor
+    directories: HashMap<i32, PathBuf>,
+}
+
+impl Watcher {
+    pub fn new() -> Result<Self> {
+        let fd = c_result(unsafe { inotify_init1(IN_NONBLOCK | IN_CLOEXEC) })?;
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            directories: HashMap::new(),
+        })
+    }
+
+    // Watches the directories of the files, and stops watching the ones none of them are in
+    pub fn watch<'a>(&mut self, files: impl IntoIterator<Item = &'a Path>) {
+        let fd = self.fd.as_raw_fd();
+        let wanted: HashSet<&Path> = files.into_iter().filter_map(Path::parent).collect();
+
+        self.directories.retain(|wd, directory| {
+            let keep = wanted.contains(directory.as_path());
+
+            if !keep {
+                unsafe { inotify_rm_watch(fd, *wd) };
+            }
+
+            keep
+        });
+
+        for directory in wanted {
+            if self
+                .directories
+                .values()
+                .any(|watched| watched == directory)
+            {
+                continue;
+            }
+
+            let Ok(path) = CString::new(directory.as_os_str().as_bytes()) else {
+                continue;
+            };
+
+            // A directory that doesn't exist yet is tried again the next time
+            let wd = unsafe { inotify_add_watch(fd, path.as_ptr(), EVENTS) };
+
+            if wd >= 0 {
+                self.directories.insert(wd, directory.to_path_buf());
+            }
+        }
+    }
+
+    // Waits until there is input to read or a file has changed, returning true for the latter.
+    // Input goes first so that typing is never held up by files that keep changing
+    pub fn wait(&self, input: RawFd) -> bool {
+        let mut fds = [
+            pollfd {
+                fd: input,
+                events: POLLIN,
+                revents: 0,
+            },
+            pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: POLLIN,
+                revents: 0,
+            },
+        ];
+
+        loop {
+            if unsafe { poll(fds.as_mut_ptr(), 2, -1) } >= 0 {
+                break;
+            }
+
+            if Error::last_os_error().raw_os_error() != Some(EINTR) {
+                return false;
+            }
+        }
+
+        fds[0].revents == 0 && fds[1].revents & POLLIN != 0
+    }
+
+    // The files that were written or removed since the last time, nothing when so much happened
+    // that some of it was lost and every file has to be looked at
+    pub fn changes(&mut self) -> Option<HashSet<PathBuf>> {
+        let mut changes = HashSet::new();
+        let mut lost = false;
+
+        let mut buffer = [0u8; 4096];
+        let header = mem::size_of::<inotify_event>();
+
+        loop {
+            let length = unsafe {
+                read(
+                    self.fd.as_raw_fd(),
+                    buffer.as_mut_ptr().cast::<c_void>(),
+                    buffer.len(),
+                )
+            };
+
+            let Ok(length) = usize::try_from(length) else {
+                break;
+            };
+
+            let mut offset = 0;
+
+            while offset + header <= length {
+                let event: inotify_event =
+                    unsafe { ptr::read_unaligned(buffer.as_ptr().add(offset).cast()) };
+
+                let name = &buffer[offset + header..offset + header + event.len as usize];
+                offset += header + event.len as usize;
+
+                if event.mask & IN_Q_OVERFLOW != 0 {
+                    lost = true;
+                }
+
+                let Some(directory) = self.directories.get(&event.wd) else {
+                    continue;
+                };
+
+                // The name is padded with nul bytes
+                let name = name.split(|byte| *byte == 0).next().unwrap_or_default();
+
+                if !name.is_empty() {
+                    changes.insert(directory.join(OsStr::from_bytes(name)));
+                }
+            }
+
+            if length == 0 {
+                break;
+            }
+        }
+
+        (!lost).then_some(changes)
+    }
+}